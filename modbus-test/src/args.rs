@@ -5,7 +5,7 @@ use clap::{Args, Parser, Subcommand, ValueEnum};
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
-    /// Hostname or ip address. (or "server" to run a test server).
+    /// Hostname or ip address. (or "server" to run a test server, or "gateway" to run a TCP/RTU gateway).
     pub host: String,
 
     /// TCP port number
@@ -15,6 +15,14 @@ pub struct Cli {
     /// Network timeout in ms
     #[arg(short, long, default_value = "2000", value_parser = parse_duration)]
     pub timeout: Duration,
+
+    /// Serial port of the RTU bus, or "tcp:<host>:<port>" for RTU over TCP (gateway only)
+    #[arg(long)]
+    pub bus: Option<String>,
+
+    /// Baud rate of the serial port (gateway only)
+    #[arg(long, default_value = "9600")]
+    pub baud_rate: u32,
 }
 
 #[derive(Parser, Debug)]
@@ -2,7 +2,7 @@ use std::{error::Error, io::Write, sync::Arc, time::Duration};
 
 use clap::Parser;
 use comfy_table::{presets, CellAlignment, ColumnConstraint, Table, Width};
-use modbus::{ModbusError, ModbusTCPClient};
+use modbus::{BitBuf, ModbusError, ModbusTCPClient};
 use rustyline::{completion::Completer, history::MemHistory, Editor, Helper, Highlighter, Hinter, Validator};
 use tokio::{net::TcpStream, select, sync::Mutex, time::Instant};
 
@@ -242,7 +242,7 @@ impl ClientImpl {
         let client = self.connect_if_needed().await?;
 
         enum ResultType {
-            Coils(Vec<bool>),
+            Coils(BitBuf),
             Registers(Vec<u16>),
         }
 
@@ -333,7 +333,7 @@ impl ClientImpl {
         Ok(())
     }
 
-    fn print_coils(&mut self, address: Address, values: &[bool]) {
+    fn print_coils(&mut self, address: Address, values: &BitBuf) {
         let mut table = Table::new();
         table.load_preset(presets::NOTHING);
         table.set_header(["Address", "Value"]);
@@ -0,0 +1,37 @@
+use std::{error::Error, sync::Arc, time::Duration};
+
+use modbus::{ModbusRTUClient, ModbusTCPServer, RtuGateway};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    signal,
+};
+
+use super::args::Cli;
+
+pub async fn run(args: Cli) -> Result<(), Box<dyn Error>> {
+    let Some(bus_str) = args.bus else {
+        return Err("The gateway needs a bus, use --bus".into());
+    };
+
+    let bus = match bus_str.strip_prefix("tcp:") {
+        Some(addr) => ModbusRTUClient::new(TcpStream::connect(addr).await?),
+        None => ModbusRTUClient::open(&bus_str, args.baud_rate)?,
+    };
+    let gateway = RtuGateway::new().with_units(UNITS, bus.with_timeout(args.timeout));
+
+    let listener = TcpListener::bind(format!("localhost:{}", args.port)).await?;
+    let listener_str = listener.local_addr()?.to_string();
+
+    let server = ModbusTCPServer::run(listener, Arc::new(gateway));
+
+    println!("Gateway listening on {} for units {:?} on {}. Press Ctrl-C to stop.", listener_str, UNITS, bus_str);
+
+    signal::ctrl_c().await?;
+
+    server.shutdown(Duration::from_secs(5)).await;
+
+    Ok(())
+}
+
+/// The unit ids that can be assigned to devices on an RTU bus.
+const UNITS: std::ops::RangeInclusive<u8> = 1..=247;
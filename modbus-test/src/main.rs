@@ -1,6 +1,7 @@
 mod address;
 pub mod args;
 pub mod connect;
+pub mod gateway;
 pub mod server;
 mod util;
 
@@ -15,6 +16,7 @@ async fn main() -> ExitCode {
 
     let result = match cli.host.as_str() {
         "server" => server::run(cli).await,
+        "gateway" => gateway::run(cli).await,
         _ => connect::run(cli).await,
     };
 
@@ -1,7 +1,10 @@
-use std::{borrow::Cow, collections::HashMap, error::Error, net::SocketAddr, sync::Arc};
+use std::{borrow::Cow, collections::BTreeMap, error::Error, net::SocketAddr, sync::Arc, time::Duration};
 
-use modbus::{DeviceIdentification, ModbusException, ModbusTCPServer, ModbusTCPServerHandler};
-use tokio::{net::TcpListener, signal, sync::Mutex};
+use modbus::{
+    BitBuf, Bits, DeviceIdentification, DisconnectReason, Extensions, MemoryStore, ModbusException, ModbusTCPServer, ModbusTCPServerHandler,
+    RequestContext,
+};
+use tokio::{net::TcpListener, signal};
 
 use super::args::Cli;
 
@@ -14,125 +17,151 @@ pub async fn run(args: Cli) -> Result<(), Box<dyn Error>> {
         product_name: Some(env!("CARGO_PKG_NAME").into()),
         user_application_name: None,
         vendor_url: None,
-        objects: HashMap::new(),
+        objects: BTreeMap::new(),
     };
 
-    let handler = Arc::new(ServerImpl {
-        device_info,
-        coils: Vec::from_iter((0..=0xFFFF).map(|v| v % 2 == 0)).into(),
-        holding_registers: Vec::from_iter(0..=0xFFFF).into(),
-    });
+    let coils: BitBuf = (0..=0xFFFF).map(|v| v % 2 == 0).collect();
+    let registers: Vec<u16> = (0..=0xFFFF).collect();
+
+    let mut store = MemoryStore::new().with_device_identification(device_info);
+    for unit_id in UNITS {
+        store = store.with_unit(unit_id);
+        store.set_coils(unit_id, 0, &coils)?;
+        store.set_discrete_inputs(unit_id, 0, &coils)?;
+        store.set_input_registers(unit_id, 0, &registers)?;
+        store.set_holding_registers(unit_id, 0, &registers)?;
+    }
+
+    let handler = Arc::new(ServerImpl { store });
 
     let listener = TcpListener::bind(format!("localhost:{}", args.port)).await?;
     let listener_str = listener.local_addr()?.to_string();
 
-    _ = ModbusTCPServer::run(listener, handler);
+    let server = ModbusTCPServer::run(listener, handler);
 
-    println!("Server listening on {}. Press Ctrl-C to stop.", listener_str);
+    println!(
+        "Server listening on {} with units {:?}, other units share the data of unit {}. Press Ctrl-C to stop.",
+        listener_str, UNITS, FALLBACK_UNIT
+    );
 
     signal::ctrl_c().await?;
 
+    server.shutdown(Duration::from_secs(5)).await;
+
     Ok(())
 }
 
-struct ServerImpl<'a> {
-    device_info: DeviceIdentification<'a>,
-    coils: Mutex<Vec<bool>>,
-    holding_registers: Mutex<Vec<u16>>,
+const UNITS: std::ops::RangeInclusive<u8> = 0..=3;
+
+/// Requests to units outside [`UNITS`] are answered with the data of this unit.
+const FALLBACK_UNIT: u8 = 0;
+
+struct ServerImpl {
+    store: MemoryStore,
+}
+
+impl ServerImpl {
+    /// The context to pass to the store, with the unit replaced by [`FALLBACK_UNIT`] if the store doesn't have it.
+    fn store_ctx(ctx: &RequestContext) -> Cow<'_, RequestContext> {
+        if UNITS.contains(&ctx.unit_id) {
+            Cow::Borrowed(ctx)
+        } else {
+            Cow::Owned(RequestContext {
+                unit_id: FALLBACK_UNIT,
+                ..ctx.clone()
+            })
+        }
+    }
 }
 
-impl ModbusTCPServerHandler for ServerImpl<'static> {
-    async fn accept_connection(&self, addr: SocketAddr) -> bool {
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn accept_connection(&self, addr: SocketAddr, _extensions: &mut Extensions) -> bool {
         println!("[{}] Connected", addr);
         true
     }
 
-    async fn disconnected(&self, addr: SocketAddr) -> () {
-        println!("[{}] Disconnected", addr);
+    async fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) -> () {
+        println!("[{}] Disconnected: {}", addr, reason);
     }
 
-    async fn handle_read_coils(&self, addr: SocketAddr, unit_id: u8, address: u16, length: u16) -> Result<Cow<'_, [bool]>, ModbusException> {
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
         println!(
             "[{}] Read coils: unit: {}, address: 0{:05}-0{:05}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address + (length - 1)
         );
-        let coils = self.coils.lock().await;
-        let result = &coils[address as usize..address as usize + length as usize];
-        Ok(result.to_vec().into())
+        self.store.handle_read_coils(&Self::store_ctx(ctx), address, length).await
     }
 
-    async fn handle_read_discrete_inputs(&self, addr: SocketAddr, unit_id: u8, address: u16, length: u16) -> Result<Cow<'_, [bool]>, ModbusException> {
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
         println!(
             "[{}] Read discrete inputs: unit: {}, address: 1{:05}-1{:05}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address + (length - 1)
         );
-        Ok((address..=(address + (length - 1))).map(|v| v % 2 == 0).collect())
+        self.store.handle_read_discrete_inputs(&Self::store_ctx(ctx), address, length).await
     }
 
-    async fn handle_read_input_registers(&self, addr: SocketAddr, unit_id: u8, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
         println!(
             "[{}] Read input registers: unit: {}, address: 3{:05}-3{:05}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address + (length - 1)
         );
-        Ok((address..=(address + (length - 1))).collect())
+        self.store.handle_read_input_registers(&Self::store_ctx(ctx), address, length).await
     }
 
-    async fn handle_read_holding_registers(&self, addr: SocketAddr, unit_id: u8, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
         println!(
             "[{}] Read holding registers: unit: {}, address: 4{:05}-4{:05}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address + (length - 1)
         );
-        let holding_registers = self.holding_registers.lock().await;
-        let result = &holding_registers[address as usize..address as usize + length as usize];
-        Ok(result.to_vec().into())
+        self.store.handle_read_holding_registers(&Self::store_ctx(ctx), address, length).await
     }
 
-    async fn handle_write_coils(&self, addr: SocketAddr, unit_id: u8, address: u16, values: &[bool]) -> Result<(), ModbusException> {
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
         println!(
             "[{}] Write coils: unit: {}, address: 0{:05}-0{:05}, values: {:?}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address as usize + values.len() - 1,
             values
         );
-        let mut coils = self.coils.lock().await;
-        for i in 0..values.len() {
-            coils[address as usize + i] = values[i];
-        }
-        Ok(())
+        self.store.handle_write_coils(&Self::store_ctx(ctx), address, values).await
     }
 
-    async fn handle_write_holding_registers(&self, addr: SocketAddr, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
         println!(
             "[{}] Write holding registers: unit: {}, address: 4{:05}-4{:05}, values: {:?}",
-            addr,
-            unit_id,
+            ctx.addr,
+            ctx.unit_id,
             address,
             address as usize + values.len() - 1,
             values
         );
-        let mut holding_registers = self.holding_registers.lock().await;
-        for i in 0..values.len() {
-            holding_registers[address as usize + i] = values[i];
-        }
-        Ok(())
+        self.store.handle_write_holding_registers(&Self::store_ctx(ctx), address, values).await
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        println!(
+            "[{}] Mask write holding register: unit: {}, address: 4{:05}, and: {:#06x}, or: {:#06x}",
+            ctx.addr, ctx.unit_id, address, and_mask, or_mask
+        );
+        self.store.handle_mask_write_holding_register(&Self::store_ctx(ctx), address, and_mask, or_mask).await
     }
 
-    async fn handle_read_device_identification(&self, addr: SocketAddr, unit_id: u8) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
-        println!("[{}] Read device identification: unit: {}", addr, unit_id);
-        Ok(Cow::Borrowed(&self.device_info))
+    async fn handle_read_device_identification(&self, ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        println!("[{}] Read device identification: unit: {}", ctx.addr, ctx.unit_id);
+        self.store.handle_read_device_identification(&Self::store_ctx(ctx)).await
     }
 }
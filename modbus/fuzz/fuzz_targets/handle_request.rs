@@ -0,0 +1,17 @@
+#![no_main]
+
+use std::sync::{Arc, LazyLock};
+
+use libfuzzer_sys::fuzz_target;
+use modbus::{fuzzing, MemoryStore};
+use tokio::runtime::Runtime;
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| tokio::runtime::Builder::new_current_thread().build().unwrap());
+static HANDLER: LazyLock<Arc<MemoryStore>> = LazyLock::new(fuzzing::memory_store);
+
+// The first byte picks the unit, so most requests reach the memory store.
+fuzz_target!(|data: &[u8]| {
+    let Some((&unit_id, pdu)) = data.split_first() else { return };
+    let unit_id = if unit_id < 0x80 { fuzzing::UNIT_ID } else { unit_id };
+    RUNTIME.block_on(fuzzing::handle_request(&HANDLER, unit_id, pdu));
+});
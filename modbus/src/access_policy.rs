@@ -0,0 +1,383 @@
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    ops::RangeInclusive,
+    path::Path,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{
+    middleware::{Middleware, Next},
+    modbus_exception::ModbusException,
+    pdu::{Request, Response},
+    request_context::{Extensions, RequestContext},
+    server::ModbusTCPServerHandler,
+    table::Table,
+};
+
+/// A range of IP addresses, e.g. `192.168.1.0/24`, `10.0.0.1` or `fd00::/8`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_length: u8,
+}
+
+impl Cidr {
+    /// Panics if `prefix_length` is longer than the address.
+    pub fn new(addr: IpAddr, prefix_length: u8) -> Self {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        assert!(prefix_length <= max, "Prefix length exceeds address length");
+        Self { addr, prefix_length }
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        // Clients connecting to a dual stack listener over IPv4 have IPv4-mapped IPv6 addresses.
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => prefix_matches(u32::from(network).into(), u32::from(addr).into(), 32, self.prefix_length),
+            (IpAddr::V6(network), IpAddr::V6(addr)) => prefix_matches(network.into(), addr.into(), 128, self.prefix_length),
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: u128, addr: u128, bits: u8, prefix_length: u8) -> bool {
+    let host_bits = (bits - prefix_length) as u32;
+    network.checked_shr(host_bits).unwrap_or(0) == addr.checked_shr(host_bits).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_length) = match s.split_once('/') {
+            Some((addr, prefix_length)) => (addr, Some(prefix_length)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| format!("Invalid address: {}", addr))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length
+                .parse()
+                .ok()
+                .filter(|prefix_length| *prefix_length <= max)
+                .ok_or_else(|| format!("Invalid prefix length: {}", prefix_length))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix_length })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_length)
+    }
+}
+
+/// What an [`AccessRule`] allows.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn allows(self, write: bool) -> bool {
+        match self {
+            Access::Read => !write,
+            Access::Write => write,
+            Access::ReadWrite => true,
+        }
+    }
+}
+
+/// Allows requests matching all of its conditions. Conditions that aren't set match everything.
+#[derive(Debug, Clone)]
+pub struct AccessRule {
+    access: Access,
+    sources: Vec<Cidr>,
+    unit_ids: Vec<u8>,
+    function_codes: Vec<u8>,
+    addresses: Vec<(Table, RangeInclusive<u16>)>,
+}
+
+impl AccessRule {
+    pub fn new(access: Access) -> Self {
+        Self {
+            access,
+            sources: Vec::new(),
+            unit_ids: Vec::new(),
+            function_codes: Vec::new(),
+            addresses: Vec::new(),
+        }
+    }
+
+    /// Only matches connections from the addresses.
+    pub fn with_source(mut self, source: Cidr) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Only matches requests to the units.
+    pub fn with_units(mut self, unit_ids: impl IntoIterator<Item = u8>) -> Self {
+        self.unit_ids.extend(unit_ids);
+        self
+    }
+
+    /// Only matches requests with the function codes.
+    pub fn with_function_codes(mut self, function_codes: impl IntoIterator<Item = u8>) -> Self {
+        self.function_codes.extend(function_codes);
+        self
+    }
+
+    /// Only matches requests entirely within the addresses of the table.
+    /// Requests without addresses, such as Read Device Identification, don't match rules with addresses.
+    pub fn with_addresses(mut self, table: Table, addresses: RangeInclusive<u16>) -> Self {
+        self.addresses.push((table, addresses));
+        self
+    }
+
+    fn matches_source(&self, addr: IpAddr) -> bool {
+        self.sources.is_empty() || self.sources.iter().any(|source| source.contains(addr))
+    }
+
+    fn matches(&self, ctx: &RequestContext, request: &Request) -> bool {
+        let function_code = u8::from(request.function_code());
+        self.access.allows(request.is_write())
+            && self.matches_source(ctx.addr.ip())
+            && (self.unit_ids.is_empty() || self.unit_ids.contains(&ctx.unit_id))
+            && (self.function_codes.is_empty() || self.function_codes.contains(&function_code))
+            && (self.addresses.is_empty()
+                || request.addresses().is_some_and(|(table, range)| {
+                    self.addresses
+                        .iter()
+                        .any(|(allowed_table, allowed)| *allowed_table == table && allowed.start() <= range.start() && range.end() <= allowed.end())
+                }))
+    }
+}
+
+/// An error loading an [`AccessPolicy`].
+#[derive(Error, Debug)]
+pub enum AccessPolicyError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error("Line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/**
+ * Middleware restricting which clients can access what, applied to a handler with [`crate::Layered`].
+ *
+ * Connections from addresses not matched by any rule are rejected.
+ * Requests not allowed by any rule are answered with [`ModbusException::IllegalFunction`], or the exception set with
+ * [`Self::with_denied_exception`]. A policy without rules denies everything.
+ *
+ * Policies can be loaded from text, one rule per line. `#` starts a comment.
+ *
+ * ```text
+ * # Denied requests get Illegal Data Address instead of Illegal Function.
+ * deny-with illegal-data-address
+ * # Anyone can read.
+ * allow read
+ * # The local network can write holding registers 0-99 of units 1 and 2 with Write Multiple Registers.
+ * allow write from 192.168.1.0/24 units 1,2 holding-registers 0-99 functions 16
+ * ```
+ *
+ * The access is one of `read`, `write` or `read-write`, followed by any of `from <cidr>,..`, `units <id or range>,..`,
+ * `functions <code>,..` and `<table> <address or range>`, where the table is one of `coils`, `discrete-inputs`,
+ * `input-registers` or `holding-registers`.
+ */
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+    rules: Vec<AccessRule>,
+    denied_exception: ModbusException,
+}
+
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            denied_exception: ModbusException::IllegalFunction,
+        }
+    }
+}
+
+impl AccessPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rule(mut self, rule: AccessRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The exception denied requests are answered with. Default is [`ModbusException::IllegalFunction`].
+    pub fn with_denied_exception(mut self, exception: ModbusException) -> Self {
+        self.denied_exception = exception;
+        self
+    }
+
+    /// Loads a policy from a file in the text format.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AccessPolicyError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn is_allowed(&self, ctx: &RequestContext, request: &Request) -> bool {
+        self.rules.iter().any(|rule| rule.matches(ctx, request))
+    }
+}
+
+impl Middleware for AccessPolicy {
+    async fn accept_connection(&self, addr: SocketAddr, _extensions: &mut Extensions) -> bool {
+        self.rules.iter().any(|rule| rule.matches_source(addr.ip()))
+    }
+
+    async fn call<H: ModbusTCPServerHandler>(&self, ctx: &RequestContext, request: Request, next: Next<'_, H>) -> Result<Response, ModbusException> {
+        if !self.is_allowed(ctx, &request) {
+            return Err(self.denied_exception);
+        }
+        next.run(ctx, request).await
+    }
+}
+
+impl FromStr for AccessPolicy {
+    type Err = AccessPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = AccessPolicy::new();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let syntax_error = |message: String| AccessPolicyError::Syntax { line: index + 1, message };
+            match words.next() {
+                None => {}
+                Some("allow") => policy.rules.push(parse_rule(&mut words).map_err(syntax_error)?),
+                Some("deny-with") => {
+                    policy.denied_exception = match words.next() {
+                        Some("illegal-function") => ModbusException::IllegalFunction,
+                        Some("illegal-data-address") => ModbusException::IllegalDataAddress,
+                        other => return Err(syntax_error(format!("Expected illegal-function or illegal-data-address, got {:?}", other))),
+                    };
+                }
+                Some(other) => return Err(syntax_error(format!("Unknown statement: {}", other))),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+fn parse_rule<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<AccessRule, String> {
+    let access = match words.next() {
+        Some("read") => Access::Read,
+        Some("write") => Access::Write,
+        Some("read-write") => Access::ReadWrite,
+        other => return Err(format!("Expected read, write or read-write, got {:?}", other)),
+    };
+    let mut rule = AccessRule::new(access);
+
+    while let Some(word) = words.next() {
+        let value = words.next().ok_or_else(|| format!("Missing value for {}", word))?;
+        let list = value.split(',');
+        match word {
+            "from" => {
+                for source in list {
+                    rule = rule.with_source(source.parse()?);
+                }
+            }
+            "units" => {
+                for units in list {
+                    rule = rule.with_units(parse_range::<u8>(units)?);
+                }
+            }
+            "functions" => {
+                for function_code in list {
+                    rule = rule.with_function_codes([parse_number(function_code)?]);
+                }
+            }
+            "coils" => rule = rule.with_addresses(Table::Coils, parse_range(value)?),
+            "discrete-inputs" => rule = rule.with_addresses(Table::DiscreteInputs, parse_range(value)?),
+            "input-registers" => rule = rule.with_addresses(Table::InputRegisters, parse_range(value)?),
+            "holding-registers" => rule = rule.with_addresses(Table::HoldingRegisters, parse_range(value)?),
+            other => return Err(format!("Unknown condition: {}", other)),
+        }
+    }
+    Ok(rule)
+}
+
+/// A number, or two numbers separated by `-`.
+fn parse_range<T: FromStr + Copy>(s: &str) -> Result<RangeInclusive<T>, String> {
+    match s.split_once('-') {
+        Some((start, end)) => Ok(parse_number(start)?..=parse_number(end)?),
+        None => {
+            let value = parse_number(s)?;
+            Ok(value..=value)
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid number: {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr() {
+        let network: Cidr = "192.168.1.0/24".parse().unwrap();
+        assert!(network.contains("192.168.1.200".parse().unwrap()));
+        assert!(network.contains("::ffff:192.168.1.200".parse().unwrap()));
+        assert!(!network.contains("192.168.2.1".parse().unwrap()));
+        assert!(!network.contains("fd00::1".parse().unwrap()));
+
+        let host: Cidr = "10.0.0.1".parse().unwrap();
+        assert_eq!(host.to_string(), "10.0.0.1/32");
+        assert!(host.contains("10.0.0.1".parse().unwrap()));
+        assert!(!host.contains("10.0.0.2".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("1.2.3.4".parse().unwrap()));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fdab::1".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let policy: AccessPolicy = "
+            # Comment
+            deny-with illegal-data-address
+            allow read
+            allow write from 192.168.1.0/24,10.0.0.1 units 1,3-4 holding-registers 0-99 coils 5 functions 5,6,16 # Trailing comment
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(policy.denied_exception, ModbusException::IllegalDataAddress);
+        assert_eq!(policy.rules.len(), 2);
+
+        let rule = &policy.rules[1];
+        assert_eq!(rule.access, Access::Write);
+        assert_eq!(rule.sources.len(), 2);
+        assert_eq!(rule.unit_ids, [1, 3, 4]);
+        assert_eq!(rule.function_codes, [5, 6, 16]);
+        assert_eq!(
+            rule.addresses,
+            [(Table::HoldingRegisters, 0..=99), (Table::Coils, 5..=5)]
+        );
+
+        for (text, line) in [("allow", 1), ("\nallow read units", 2), ("allow read from 1.2.3", 1), ("deny", 1), ("allow read colis 1", 1)] {
+            match text.parse::<AccessPolicy>() {
+                Err(AccessPolicyError::Syntax { line: error_line, .. }) => assert_eq!(error_line, line, "{}", text),
+                other => panic!("Expected syntax error for {:?}, got {:?}", text, other),
+            }
+        }
+    }
+}
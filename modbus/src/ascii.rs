@@ -0,0 +1,149 @@
+//! Framing of Modbus ASCII messages: a colon, the hex encoded unit id, function code, body and LRC, and CR LF.
+
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{message::FrameError, rtu::SerialFrame};
+
+/// The maximum length of an ASCII frame, including the delimiters.
+const FRAME_MAX_LENGTH: usize = 513;
+/// The maximum length of a body, as in an RTU frame.
+const BODY_MAX_LENGTH: usize = 252;
+const START: u8 = b':';
+const END: &[u8] = b"\r\n";
+
+/// The Modbus LRC of the bytes, the two's complement of their sum.
+fn lrc(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg()
+}
+
+fn hex_value(char: u8) -> Result<u8, FrameError> {
+    match char {
+        b'0'..=b'9' => Ok(char - b'0'),
+        b'A'..=b'F' => Ok(char - b'A' + 10),
+        b'a'..=b'f' => Ok(char - b'a' + 10),
+        _ => Err(FrameError::InvalidHex),
+    }
+}
+
+/// Decodes the characters between the colon and CR LF, checking the LRC.
+fn decode_frame(chars: &[u8]) -> Result<SerialFrame, FrameError> {
+    if !chars.len().is_multiple_of(2) {
+        return Err(FrameError::InvalidHex);
+    }
+    let bytes = chars
+        .chunks(2)
+        .map(|pair| Ok(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+        .collect::<Result<Vec<u8>, FrameError>>()?;
+    // Unit id, function code and LRC.
+    if bytes.len() < 3 {
+        return Err(FrameError::LengthTooShort(bytes.len() as u16));
+    }
+    let (content, checksum) = bytes.split_at(bytes.len() - 1);
+    if lrc(content) != checksum[0] {
+        return Err(FrameError::LrcMismatch);
+    }
+    Ok(SerialFrame {
+        unit_id: content[0],
+        function_code: content[1].into(),
+        body: Bytes::copy_from_slice(&content[2..]),
+    })
+}
+
+/**
+ * Sans-IO encoder and decoder of Modbus ASCII frames on a byte stream.
+ *
+ * Frames are delimited by a colon and CR LF, so unlike RTU no timing is needed.
+ * Characters outside of a frame are discarded, and a colon within a frame starts a new frame.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct AsciiCodec;
+
+impl AsciiCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Decodes a frame from `buffer`, removing its bytes and any bytes before it. Returns `None` if more bytes are needed.
+    /// On error the invalid frame is discarded, so decoding again continues with the next frame.
+    pub fn decode(&mut self, buffer: &mut BytesMut) -> Result<Option<SerialFrame>, FrameError> {
+        let end = buffer.windows(END.len()).position(|window| window == END);
+        let search = &buffer[..end.unwrap_or(buffer.len())];
+        let Some(start) = search.iter().rposition(|&char| char == START) else {
+            // Keep a trailing CR, it may be followed by the LF of a frame's end.
+            let keep = end.map_or(buffer.ends_with(b"\r") as usize, |_| 0);
+            let discard = end.map_or(buffer.len() - keep, |end| end + END.len());
+            buffer.advance(discard);
+            return Ok(None);
+        };
+        buffer.advance(start);
+        let Some(end) = end.map(|end| end - start) else {
+            if buffer.len() >= FRAME_MAX_LENGTH {
+                let length = buffer.len();
+                buffer.advance(1);
+                return Err(FrameError::LengthTooLong(length.min(u16::MAX.into()) as u16));
+            }
+            return Ok(None);
+        };
+        let frame = buffer.split_to(end + END.len());
+        decode_frame(&frame[1..end]).map(Some)
+    }
+
+    /// Appends the encoded frame to `buffer`.
+    pub fn encode(&self, frame: &SerialFrame, buffer: &mut BytesMut) -> Result<(), FrameError> {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+        if frame.body.len() > BODY_MAX_LENGTH {
+            return Err(FrameError::LengthTooLong((frame.body.len() + 3).min(u16::MAX.into()) as u16));
+        }
+        let mut content = Vec::with_capacity(frame.body.len() + 3);
+        content.push(frame.unit_id);
+        content.push(frame.function_code.into());
+        content.extend_from_slice(&frame.body);
+        content.push(lrc(&content));
+
+        buffer.reserve(content.len() * 2 + 3);
+        buffer.put_u8(START);
+        for byte in content {
+            buffer.put_u8(DIGITS[(byte >> 4) as usize]);
+            buffer.put_u8(DIGITS[(byte & 0x0F) as usize]);
+        }
+        buffer.put_slice(END);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::function_code::FunctionCode;
+
+    use super::*;
+
+    #[test]
+    fn codec() {
+        // Read holding registers 0x006B-0x006D from unit 0x11.
+        let frame = SerialFrame {
+            unit_id: 0x11,
+            function_code: FunctionCode::ReadHoldingRegisters,
+            body: Bytes::from_static(&[0x00, 0x6B, 0x00, 0x03]),
+        };
+        let mut codec = AsciiCodec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(&frame, &mut buffer).unwrap();
+        assert_eq!(&buffer[..], b":1103006B00037E\r\n");
+        assert_eq!(codec.decode(&mut buffer), Ok(Some(frame.clone())));
+        assert!(buffer.is_empty());
+
+        // Noise is skipped, a colon restarts the frame and lowercase is accepted.
+        let mut buffer = BytesMut::from(&b"\x00\xFF:11:1103006b00037e\r"[..]);
+        assert_eq!(codec.decode(&mut buffer), Ok(None));
+        buffer.put_slice(b"\n");
+        assert_eq!(codec.decode(&mut buffer), Ok(Some(frame)));
+
+        let mut buffer = BytesMut::from(&b":1103006B00037F\r\n:11X3\r\n:11\r\n"[..]);
+        assert_eq!(codec.decode(&mut buffer), Err(FrameError::LrcMismatch));
+        assert_eq!(codec.decode(&mut buffer), Err(FrameError::InvalidHex));
+        assert_eq!(codec.decode(&mut buffer), Err(FrameError::LengthTooShort(1)));
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+//! An owned vector of packed bits, for coils and discrete inputs.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{fmt, ops::{Index, RangeBounds}};
+
+use crate::views::{BitIter, Bits};
+
+/**
+ * Coils or discrete inputs, packed eight to a byte in Modbus wire order: least significant bit first.
+ *
+ * Takes an eighth of the memory of a `Vec<bool>` and is encoded without conversion.
+ * Borrow it as [`Bits`] with [`BitBuf::as_bits`] or `From`, e.g. to write it with a client.
+ */
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitBuf {
+    /// The bits of the last byte past `len` are always cleared.
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitBuf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity.div_ceil(8)),
+            len: 0,
+        }
+    }
+
+    /// `len` bits, all set to `value`.
+    pub fn repeat(value: bool, len: usize) -> Self {
+        let mut bits = Self {
+            bytes: alloc::vec![if value { 0xFF } else { 0x00 }; len.div_ceil(8)],
+            len,
+        };
+        bits.clear_padding();
+        bits
+    }
+
+    /// The first `len` bits of packed bytes. Returns `None` if `bytes` holds fewer bits.
+    pub fn from_packed(mut bytes: Vec<u8>, len: usize) -> Option<Self> {
+        if len > bytes.len() * 8 {
+            return None;
+        }
+        bytes.truncate(len.div_ceil(8));
+        let mut bits = Self { bytes, len };
+        bits.clear_padding();
+        Some(bits)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        (index < self.len).then(|| self.bytes[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < self.len, "index {index} out of bounds for {} bits", self.len);
+        if value {
+            self.bytes[index / 8] |= 1 << (index % 8);
+        } else {
+            self.bytes[index / 8] &= !(1 << (index % 8));
+        }
+    }
+
+    pub fn push(&mut self, value: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, value);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+            self.bytes.truncate(len.div_ceil(8));
+            self.clear_padding();
+        }
+    }
+
+    pub fn as_bits(&self) -> Bits<'_> {
+        Bits::new(&self.bytes, self.len).unwrap()
+    }
+
+    /// A view of a range of the bits.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds, as when slicing a slice.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Bits<'_> {
+        self.as_bits().slice(range)
+    }
+
+    /// The packed bytes, with the bits of the last byte past the end cleared.
+    pub fn as_packed(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn iter(&self) -> BitIter<'_> {
+        self.as_bits().iter()
+    }
+
+    pub fn to_vec(&self) -> Vec<bool> {
+        self.iter().collect()
+    }
+
+    fn clear_padding(&mut self) {
+        if !self.len.is_multiple_of(8) {
+            *self.bytes.last_mut().unwrap() &= (1 << (self.len % 8)) - 1;
+        }
+    }
+}
+
+impl Index<usize> for BitBuf {
+    type Output = bool;
+
+    fn index(&self, index: usize) -> &bool {
+        match self.get(index) {
+            Some(true) => &true,
+            Some(false) => &false,
+            None => panic!("index {index} out of bounds for {} bits", self.len),
+        }
+    }
+}
+
+impl FromIterator<bool> for BitBuf {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut bits = Self::with_capacity(iter.size_hint().0);
+        bits.extend(iter);
+        bits
+    }
+}
+
+impl Extend<bool> for BitBuf {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a BitBuf {
+    type Item = bool;
+    type IntoIter = BitIter<'a>;
+
+    fn into_iter(self) -> BitIter<'a> {
+        self.iter()
+    }
+}
+
+impl From<&[bool]> for BitBuf {
+    fn from(values: &[bool]) -> Self {
+        values.iter().copied().collect()
+    }
+}
+
+impl<const N: usize> From<[bool; N]> for BitBuf {
+    fn from(values: [bool; N]) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl From<Vec<bool>> for BitBuf {
+    fn from(values: Vec<bool>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl From<Bits<'_>> for BitBuf {
+    fn from(bits: Bits<'_>) -> Self {
+        match bits.as_packed() {
+            Some(bytes) => Self::from_packed(bytes.to_vec(), bits.len()).unwrap(),
+            None => bits.iter().collect(),
+        }
+    }
+}
+
+impl From<BitBuf> for Vec<bool> {
+    fn from(bits: BitBuf) -> Self {
+        bits.to_vec()
+    }
+}
+
+impl<'a> From<&'a BitBuf> for Bits<'a> {
+    fn from(bits: &'a BitBuf) -> Self {
+        bits.as_bits()
+    }
+}
+
+impl From<BitBuf> for Cow<'_, BitBuf> {
+    fn from(bits: BitBuf) -> Self {
+        Cow::Owned(bits)
+    }
+}
+
+impl<'a> From<&'a BitBuf> for Cow<'a, BitBuf> {
+    fn from(bits: &'a BitBuf) -> Self {
+        Cow::Borrowed(bits)
+    }
+}
+
+impl PartialEq<[bool]> for BitBuf {
+    fn eq(&self, other: &[bool]) -> bool {
+        self.len == other.len() && self.iter().eq(other.iter().copied())
+    }
+}
+
+impl<const N: usize> PartialEq<[bool; N]> for BitBuf {
+    fn eq(&self, other: &[bool; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+impl PartialEq<Vec<bool>> for BitBuf {
+    fn eq(&self, other: &Vec<bool>) -> bool {
+        *self == other[..]
+    }
+}
+
+impl fmt::Debug for BitBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_bits(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn bit_buf() {
+        let mut bits = BitBuf::from([true, false, true, true, false, false, true, true, true]);
+        assert_eq!(bits.len(), 9);
+        assert_eq!(bits.as_packed(), [0b1100_1101, 0b0000_0001]);
+        assert!(bits[0] && !bits[1]);
+        assert_eq!(bits.get(9), None);
+
+        bits.set(1, true);
+        bits.push(true);
+        assert_eq!(bits, [true, true, true, true, false, false, true, true, true, true]);
+        assert_eq!(bits.slice(2..5).to_vec(), [true, true, false]);
+
+        bits.truncate(3);
+        assert_eq!(bits.as_packed(), [0b0000_0111]);
+        assert_eq!(Vec::from(bits.clone()), vec![true, true, true]);
+        assert_eq!(BitBuf::from(bits.as_bits()), bits);
+
+        // Padding is cleared, so equal bits compare equal.
+        assert_eq!(BitBuf::from_packed(vec![0xFF, 0xFF], 3), Some(BitBuf::repeat(true, 3)));
+        assert_eq!(BitBuf::from_packed(vec![0xFF], 9), None);
+        assert_eq!(BitBuf::from(Bits::new(&[0xFF], 8).unwrap().slice(5..)), BitBuf::repeat(true, 3));
+    }
+}
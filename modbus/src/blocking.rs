@@ -0,0 +1,124 @@
+//! A blocking client for use in synchronous code.
+//!
+//! The client runs the async [`crate::ModbusTCPClient`] on a private single threaded runtime.
+//! It must not be used from within an async runtime.
+
+use std::{
+    future::Future,
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{runtime::Runtime, time};
+
+use crate::{bit_buf::BitBuf, client::ModbusError, modbus_encapsulated_interface::DeviceIdentification, telemetry, views::Bits};
+
+/// A blocking Modbus TCP client.
+/// Every request fails with [`ModbusError::Timeout`] if no response is received within the configured timeout.
+pub struct ModbusTCPClient {
+    // Declared before the runtime so the receive task is aborted before the runtime shuts down.
+    client: crate::ModbusTCPClient,
+    runtime: Runtime,
+    timeout: Duration,
+}
+
+impl ModbusTCPClient {
+    /// Connects to the server, trying each resolved address in turn.
+    pub fn connect<A: ToSocketAddrs>(addr: A, timeout: Duration) -> Result<Self, ModbusError> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs().map_err(io_error)? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Self::new(stream, timeout),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(match last_error {
+            Some(error) => io_error(error),
+            None => ModbusError::ArgumentsOutOfRange("Address didn't resolve to any socket address"),
+        })
+    }
+
+    /// Creates a client from an already connected stream.
+    pub fn new(stream: TcpStream, timeout: Duration) -> Result<Self, ModbusError> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(io_error)?;
+
+        stream.set_nonblocking(true).map_err(io_error)?;
+        let client = {
+            let _guard = runtime.enter();
+            let stream = tokio::net::TcpStream::from_std(stream).map_err(io_error)?;
+            crate::ModbusTCPClient::new(stream).0
+        };
+
+        Ok(Self { client, runtime, timeout })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        self.block_on(self.client.read_coils(unit_id, address, length))
+    }
+
+    pub fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        self.block_on(self.client.read_discrete_inputs(unit_id, address, length))
+    }
+
+    pub fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        self.block_on(self.client.read_input_registers(unit_id, address, length))
+    }
+
+    pub fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        self.block_on(self.client.read_holding_registers(unit_id, address, length))
+    }
+
+    pub fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        self.block_on(self.client.write_single_coils(unit_id, address, value))
+    }
+
+    pub fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        self.block_on(self.client.write_single_holding_register(unit_id, address, value))
+    }
+
+    pub fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusError> {
+        self.block_on(self.client.write_multiple_coils(unit_id, address, values.into()))
+    }
+
+    pub fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        self.block_on(self.client.write_multiple_holding_registers(unit_id, address, values))
+    }
+
+    pub fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        self.block_on(self.client.mask_write_holding_registers(unit_id, address, and_mask, or_mask))
+    }
+
+    pub fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        self.block_on(self.client.modbus_encapsulated_interface(unit_id, interface_type, data))
+    }
+
+    /// The timeout applies to the whole operation, which may consist of multiple requests.
+    pub fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        self.block_on(self.client.read_device_identification(unit_id))
+    }
+
+    fn block_on<F, T>(&self, future: F) -> Result<T, ModbusError>
+    where
+        F: Future<Output = Result<T, ModbusError>>,
+    {
+        self.runtime
+            .block_on(async { time::timeout(self.timeout, future).await })
+            .unwrap_or_else(|_| {
+                telemetry::client_timeout();
+                Err(ModbusError::Timeout)
+            })
+    }
+}
+
+fn io_error(error: std::io::Error) -> ModbusError {
+    ModbusError::IO(Arc::new(error))
+}
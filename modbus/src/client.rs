@@ -1,26 +1,37 @@
 use std::{
-    borrow::Cow,
     collections::HashMap,
     sync::{
         atomic::{AtomicU16, Ordering},
-        Arc,
+        Arc, Mutex, Weak,
     },
+    time::Duration,
 };
 
+use bytes::Bytes;
 use thiserror::Error;
 use tokio::{net::TcpStream, task::AbortHandle};
-use tokio::{
-    sync::{oneshot, Mutex},
-    task::JoinHandle,
-};
+use tokio::{sync::oneshot, task::JoinHandle};
 
 use crate::{
-    connection::*, consts::*, encoding::*, function_code::FunctionCode, message::Message, messages::*, modbus_encapsulated_interface::*,
+    bit_buf::BitBuf,
+    client_ops::{self, response_body, validate_input, Transport},
+    codec::CodecError,
+    connection::*,
+    encoding::*,
+    function_code::FunctionCode,
+    message::{Frame, FrameError},
+    modbus_device::ModbusDevice,
+    modbus_encapsulated_interface::*,
     modbus_exception::ModbusException,
+    subscription::{self, Subscription, SubscriptionMap},
+    table::Table,
+    telemetry::{self, Transaction},
+    views::Bits,
 };
 
 /// Errors returned by the [`ModbusTCPClient`].
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ModbusError {
     /// Represent an IO error.
     #[error(transparent)]
@@ -43,6 +54,10 @@ pub enum ModbusError {
     /// Exception code reported by the server.
     #[error(transparent)]
     ModbusException(ModbusException),
+
+    /// No response was received within the configured timeout.
+    #[error("Timeout")]
+    Timeout,
 }
 
 impl From<DecodeError> for ModbusError {
@@ -54,244 +69,191 @@ impl From<DecodeError> for ModbusError {
     }
 }
 
-impl From<ReadError> for ModbusError {
-    fn from(value: ReadError) -> Self {
+impl From<FrameError> for ModbusError {
+    fn from(value: FrameError) -> Self {
+        Self::InvalidResponse(match value {
+            FrameError::InvalidProtocolId(_) => "Invalid protocol id",
+            FrameError::LengthTooShort(_) => "Frame too short",
+            FrameError::LengthTooLong(_) => "Frame too long",
+            FrameError::CrcMismatch => "CRC mismatch",
+            FrameError::LrcMismatch => "LRC mismatch",
+            FrameError::InvalidHex => "Invalid hex encoding",
+            FrameError::Incomplete => "Incomplete frame",
+        })
+    }
+}
+
+impl From<CodecError> for ModbusError {
+    fn from(value: CodecError) -> Self {
         match value {
-            ReadError::IO(err) => Self::IO(err.into()),
-            ReadError::Decode(err) => err.into(),
+            CodecError::IO(err) => Self::IO(err.into()),
+            CodecError::Frame(err) => err.into(),
         }
     }
 }
 
-type ResponseResult = Result<Message, ModbusError>;
-type ResponseMap = Arc<Mutex<HashMap<u16, oneshot::Sender<ResponseResult>>>>;
+type ResponseResult = Result<Frame, ModbusError>;
+/// `None` once the connection is closed.
+type ResponseMap = Arc<Mutex<Option<HashMap<u16, oneshot::Sender<ResponseResult>>>>>;
 
+/// Removes a request from the response map once its receiver is dropped without a response, e.g. when it timed out.
+struct PendingRequest<'a> {
+    response_map: &'a ResponseMap,
+    transaction_id: u16,
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        if let Some(map) = self.response_map.lock().unwrap().as_mut() {
+            // A newer request may have reused the transaction id, its receiver is still waiting.
+            if map.get(&self.transaction_id).is_some_and(|sender| sender.is_closed()) {
+                map.remove(&self.transaction_id);
+            }
+        }
+    }
+}
+
+/// A Modbus TCP client.
+/// Cloning the client is cheap, all clones share the same connection.
+#[derive(Clone)]
 pub struct ModbusTCPClient {
+    inner: Arc<ClientInner>,
+}
+
+pub(crate) struct ClientInner {
     connection: Arc<Connection>,
     transaction_id: AtomicU16,
     response_map: ResponseMap,
+    subscriptions: SubscriptionMap,
     abort_handle: AbortHandle,
 }
 
 impl ModbusTCPClient {
     pub fn new(stream: TcpStream) -> (Self, JoinHandle<Result<(), ModbusError>>) {
-        let connection = Arc::new(Connection::new(stream));
-        let response_map = Arc::new(Mutex::new(HashMap::new()));
+        let connection = Arc::new(Connection::new(stream, false));
+        let response_map = Arc::new(Mutex::new(Some(HashMap::new())));
 
         let join_handle = tokio::spawn(Self::receive_response(connection.clone(), response_map.clone()));
 
-        let client = Self {
+        let inner = ClientInner {
             connection,
             transaction_id: AtomicU16::default(),
             response_map,
+            subscriptions: SubscriptionMap::default(),
             abort_handle: join_handle.abort_handle(),
         };
 
-        (client, join_handle)
+        (Self { inner: Arc::new(inner) }, join_handle)
+    }
+
+    /// Polls a range of addresses every `interval` and returns a stream of the values.
+    ///
+    /// Values are only emitted when they differ from the previously emitted values.
+    /// Communication errors are emitted as items and polling continues afterwards.
+    /// Subscriptions to the same unit, table and interval share their requests where the ranges overlap.
+    ///
+    /// The stream ends when all clones of the client are dropped.
+    pub fn subscribe(
+        &self,
+        unit_id: u8,
+        table: Table,
+        address: u16,
+        length: u16,
+        interval: Duration,
+    ) -> Result<Subscription, ModbusError> {
+        validate_input(address, length as usize, table.max_read_length())?;
+        if interval.is_zero() {
+            return Err(ModbusError::ArgumentsOutOfRange("Interval must not be zero"));
+        }
+        Ok(subscription::subscribe(self, unit_id, table, address, length, interval))
+    }
+
+    /// Returns a handle to a single unit, so the unit id doesn't need to be passed to every call.
+    pub fn unit(&self, unit_id: u8) -> ModbusDevice {
+        ModbusDevice::new(self.clone(), unit_id)
+    }
+
+    pub(crate) fn downgrade(&self) -> Weak<ClientInner> {
+        Arc::downgrade(&self.inner)
+    }
+
+    pub(crate) fn upgrade(inner: &Weak<ClientInner>) -> Option<Self> {
+        inner.upgrade().map(|inner| Self { inner })
+    }
+
+    pub(crate) fn subscriptions(&self) -> &SubscriptionMap {
+        &self.inner.subscriptions
     }
 
-    pub async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<bool>, ModbusError> {
-        validate_input(address, length as usize, READ_COILS_MAX_LEN)?;
-        let req = ReadCoilsRequest { address, length };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::ReadCoils, req_body).await?;
-        let res = ReadCoilsResponse::decode_from_bytes(&result)?;
-        Ok(res.values.into())
+    pub async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        client_ops::read_coils(self, unit_id, address, length).await
     }
 
-    pub async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<bool>, ModbusError> {
-        validate_input(address, length as usize, READ_DISCRETE_INPUTS_MAX_LEN)?;
-        let req = ReadDiscreteInputsRequest { address, length };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::ReadDiscreteInputs, req_body).await?;
-        let res = ReadDiscreteInputsResponse::decode_from_bytes(&result)?;
-        Ok(res.values.into())
+    pub async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        client_ops::read_discrete_inputs(self, unit_id, address, length).await
     }
 
     pub async fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
-        validate_input(address, length as usize, READ_INPUT_REGISTERS_MAX_LEN)?;
-        let req = ReadInputRegistersRequest { address, length };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::ReadInputRegisters, req_body).await?;
-        let res = ReadInputRegistersResponse::decode_from_bytes(&result)?;
-        Ok(res.values.into())
+        client_ops::read_input_registers(self, unit_id, address, length).await
     }
 
     pub async fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
-        validate_input(address, length as usize, READ_HOLDING_REGISTERS_MAX_LEN)?;
-        let req = ReadHoldingRegistersRequest { address, length };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::ReadHoldingRegisters, req_body).await?;
-        let res =
-            ReadHoldingRegistersResponse::decode_from_bytes(&result)?;
-        Ok(res.values.into())
+        client_ops::read_holding_registers(self, unit_id, address, length).await
     }
 
     pub async fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
-        let req = WriteSingleCoilRequest { address, value };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::WriteSingleCoil, req_body).await?;
-        let res = WriteSingleCoilResponse::decode_from_bytes(&result)?;
-        if res.address == req.address && res.value == req.value {
-            Ok(())
-        } else {
-            Err(ModbusError::InvalidResponse("Address and value mismatch"))
-        }
+        client_ops::write_single_coils(self, unit_id, address, value).await
     }
 
     pub async fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
-        let req = WriteSingleHoldingRegisterRequest { address, value };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::WriteSingleHoldingRegister, req_body).await?;
-        let res = WriteSingleHoldingRegisterResponse::decode_from_bytes(&result)?;
-        if res.address == req.address && res.value == req.value {
-            Ok(())
-        } else {
-            Err(ModbusError::InvalidResponse("Address and value mismatch"))
-        }
+        client_ops::write_single_holding_register(self, unit_id, address, value).await
     }
 
-    pub async fn write_multiple_coils(&self, unit_id: u8, address: u16, values: &[bool]) -> Result<(), ModbusError> {
-        validate_input(address, values.len(), WRITE_MULTIPLE_COILS_MAX_LEN)?;
-        let req = WriteMultipleCoilsRequest {
-            address,
-            values: values.into(),
-        };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::WriteMultipleCoils, req_body).await?;
-        let res = WriteMultipleCoilsResponse::decode_from_bytes(&result)?;
-        if res.address == req.address && res.length as usize == req.values.len() {
-            Ok(())
-        } else {
-            Err(ModbusError::InvalidResponse("Address and length mismatch"))
-        }
+    pub async fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusError> {
+        client_ops::write_multiple_coils(self, unit_id, address, values.into()).await
     }
 
     pub async fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
-        validate_input(address, values.len(), WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN)?;
-        let req = WriteMultipleHoldingRegistersRequest {
-            address,
-            values: values.into(),
-        };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::WriteMultipleHoldingRegisters, req_body).await?;
-        let res = WriteMultipleHoldingRegistersResponse::decode_from_bytes(&result)?;
-        if res.address == req.address && res.length as usize == req.values.len() {
-            Ok(())
-        } else {
-            Err(ModbusError::InvalidResponse("Address and length mismatch"))
-        }
+        client_ops::write_multiple_holding_registers(self, unit_id, address, values).await
     }
 
     pub async fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
-        let req = MaskWriteHoldingRegisterRequest { address, and_mask, or_mask };
-        let req_body = req.encode_to_bytes().expect("Couldn't encode request");
-        let result = self.send_request(unit_id, FunctionCode::MaskWriteHoldingRegister, req_body).await?;
-        let res = MaskWriteHoldingRegisterResponse::decode_from_bytes(&result)?;
-        if res.address == req.address && res.and_mask == req.and_mask && res.or_mask == req.or_mask {
-            Ok(())
-        } else {
-            Err(ModbusError::InvalidResponse("Address and mask mismatch"))
-        }
+        client_ops::mask_write_holding_registers(self, unit_id, address, and_mask, or_mask).await
     }
 
     pub async fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
-        let req = ModbusEncapsulatedInterfaceRequest {
-            kind: ModbusEncapsulatedInterfaceType::Unknown(interface_type),
-            data: data.into(),
-        };
-
-        let res_body = self
-            .send_request(unit_id, FunctionCode::ModbusEncapsulatedInterface, req.encode_to_bytes().unwrap())
-            .await?;
-        let res = ModbusEncapsulatedInterfaceResponse::decode_from_bytes(&res_body)?;
-
-        if res.kind != req.kind {
-            return Err(ModbusError::InvalidResponse("Interface type mismatch"));
-        }
-
-        Ok(res.data.into())
+        client_ops::modbus_encapsulated_interface(self, unit_id, interface_type, data).await
     }
 
-    pub async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'_>, ModbusError> {
-        let mut more_follows = true;
-        let mut next_object_id = 0u8;
-
-        let mut result = DeviceIdentification {
-            vendor_name: "".into(),
-            product_code: "".into(),
-            major_minor_revision: "".into(),
-            model_name: None,
-            product_name: None,
-            user_application_name: None,
-            vendor_url: None,
-            objects: HashMap::new(),
-        };
-
-        for _ in 0..0xFFu8 {
-            if !more_follows {
-                break;
-            }
-
-            let req = ReadDeviceIdentificationRequest {
-                object_id: next_object_id,
-                device_id_code: ReadDeviceIdentificationIdCode::Extended,
-            };
-
-            let res_body = self
-                .modbus_encapsulated_interface(
-                    unit_id,
-                    ModbusEncapsulatedInterfaceType::ReadDeviceIdentification.into(),
-                    &req.encode_to_bytes().unwrap(),
-                )
-                .await?;
-            let res = ReadDeviceIdentificationResponse::decode_from_bytes(&res_body)?;
-
-            more_follows = res.more_follows;
-            next_object_id = res.next_object_id;
-
-            for (id, data) in res.objects {
-                let str_data = || -> Cow<str> { String::from_utf8_lossy(&data).to_string().into() };
-                match id {
-                    0 => result.vendor_name = str_data(),
-                    1 => result.product_code = str_data(),
-                    2 => result.major_minor_revision = str_data(),
-                    3 => result.vendor_url = Some(str_data()),
-                    4 => result.product_name = Some(str_data()),
-                    5 => result.model_name = Some(str_data()),
-                    6 => result.user_application_name = Some(str_data()),
-                    _ => {
-                        result.objects.insert(id, data);
-                    }
-                }
-            }
-        }
-
-        Ok(result)
+    pub async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        client_ops::read_device_identification(self, unit_id).await
     }
 
-    async fn send_request(&self, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> Result<Vec<u8>, ModbusError> {
-        let transaction_id = self.transaction_id.fetch_add(1, Ordering::Relaxed);
-
-        let msg = Message {
+    async fn exchange(&self, transaction_id: u16, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> Result<Bytes, ModbusError> {
+        let msg = Frame {
             protocol_id: 0,
             transaction_id,
             function_code,
             unit_id,
-            body,
+            body: body.into(),
         };
 
+        // Dropped after the receiver, when the request is answered, fails or is cancelled.
+        let _pending = PendingRequest {
+            response_map: &self.inner.response_map,
+            transaction_id,
+        };
         let (sender, receiver) = oneshot::channel::<ResponseResult>();
 
-        {
-            let mut map = self.response_map.lock().await;
-            map.insert(transaction_id, sender);
+        match self.inner.response_map.lock().unwrap().as_mut() {
+            Some(map) => _ = map.insert(transaction_id, sender),
+            None => return Err(connection_closed()),
         }
 
-        match self.connection.write_message(&msg).await {
+        match self.inner.connection.write_message(&msg).await {
             Ok(_) => {}
-            Err(WriteError::IO(e)) => return Err(ModbusError::IO(e.into())),
-            Err(WriteError::Encode(_)) => return Err(ModbusError::ArgumentsOutOfRange("Error encoding message")),
+            Err(CodecError::IO(e)) => return Err(ModbusError::IO(e.into())),
+            Err(CodecError::Frame(_)) => return Err(ModbusError::ArgumentsOutOfRange("Error encoding message")),
         }
 
         let res_msg = match receiver.await {
@@ -306,52 +268,102 @@ impl ModbusTCPClient {
         if res_msg.unit_id != msg.unit_id {
             return Err(ModbusError::InvalidResponse("Unit id mismatch"));
         }
-        if let FunctionCode::Error(_) = res_msg.function_code {
-            let ex_res = ExceptionMessage::decode_from_bytes(&res_msg.body)?;
-            return Err(ModbusError::ModbusException(ex_res.code));
-        }
-        if res_msg.function_code != msg.function_code {
-            return Err(ModbusError::InvalidResponse("Function code mismatch"));
-        }
-
-        Ok(res_msg.body)
+        response_body(msg.function_code, res_msg.function_code, res_msg.body)
     }
 
     async fn receive_response(connection: Arc<Connection>, response_map: ResponseMap) -> Result<(), ModbusError> {
-        loop {
+        let result: Result<(), ModbusError> = loop {
             let msg = match connection.read_message().await {
                 Ok(Some(msg)) => msg,
-                Ok(None) => return Ok(()),
-                Err(error) => {
-                    let error: ModbusError = error.into();
-                    let mut response_map = response_map.lock().await;
-                    for (_, sender) in response_map.drain() {
-                        _ = sender.send(Err(error.clone()));
-                    }
-                    return Err(error);
-                }
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(error.into()),
             };
 
-            let sender = response_map.lock().await.remove(&msg.transaction_id);
+            // Late responses to requests that timed out or were cancelled are expected, and don't fail the connection.
+            let sender = response_map.lock().unwrap().as_mut().and_then(|map| map.remove(&msg.transaction_id));
             match sender {
-                None => return Err(ModbusError::InvalidResponse("The server sent an unexpected response")),
+                None => telemetry::client_unexpected_response(msg.transaction_id),
                 Some(sender) => _ = sender.send(Ok(msg)),
             }
+        };
+
+        // Fail the pending requests and any requests made from now on.
+        let error = match &result {
+            Ok(()) => connection_closed(),
+            Err(error) => error.clone(),
+        };
+        for (_, sender) in response_map.lock().unwrap().take().into_iter().flatten() {
+            _ = sender.send(Err(error.clone()));
         }
+        result
+    }
+}
+
+impl Transport for ModbusTCPClient {
+    async fn send_request(&self, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> Result<Bytes, ModbusError> {
+        let transaction_id = self.inner.transaction_id.fetch_add(1, Ordering::Relaxed);
+
+        let transaction = Transaction::client(transaction_id, unit_id, function_code, &body);
+        let result = transaction
+            .instrument(self.exchange(transaction_id, unit_id, function_code, body))
+            .await;
+        transaction.finish((&result).into());
+        result
     }
 }
 
-impl Drop for ModbusTCPClient {
+impl Drop for ClientInner {
     fn drop(&mut self) {
         self.abort_handle.abort();
     }
 }
 
-fn validate_input(address: u16, length: usize, max_length: u16) -> Result<(), ModbusError> {
-    if length == 0 || length > max_length as usize {
-        return Err(ModbusError::ArgumentsOutOfRange("Length exceeds maximum allowed length"));
+fn connection_closed() -> ModbusError {
+    ModbusError::IO(Arc::new(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Connection closed")))
+}
+
+/// The exception reported to the downstream client when forwarding a request to an upstream device fails.
+pub(crate) fn gateway_exception(error: ModbusError) -> ModbusException {
+    match error {
+        ModbusError::ModbusException(exception) => exception,
+        ModbusError::Timeout | ModbusError::InvalidResponse(_) => ModbusException::GatewayTargetDeviceFailedToRespond,
+        ModbusError::IO(_) => ModbusException::GatewayPathUnavailable,
+        ModbusError::ArgumentsOutOfRange(_) => ModbusException::IllegalDataValue,
+        ModbusError::Internal(_) => ModbusException::ServerDeviceFailure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::messages::ReadHoldingRegistersResponse;
+
+    #[tokio::test]
+    async fn timeout_removes_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (client, _) = ModbusTCPClient::new(TcpStream::connect(listener.local_addr().unwrap()).await.unwrap());
+
+        // The server answers the first request late, and the others right away.
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio::spawn(async move {
+            let connection = Connection::new(stream, false);
+            let mut delay = Duration::from_millis(100);
+            while let Ok(Some(msg)) = connection.read_message().await {
+                tokio::time::sleep(delay).await;
+                delay = Duration::ZERO;
+                let body = ReadHoldingRegistersResponse { values: [7][..].into() }.encode_to_bytes().unwrap();
+                connection.write_message(&Frame { body: body.into(), ..msg }).await.unwrap();
+            }
+        });
+
+        let device = client.unit(1).with_timeout(Duration::from_millis(50));
+        assert!(matches!(device.read_holding_registers(0, 1).await, Err(ModbusError::Timeout)));
+        assert!(client.inner.response_map.lock().unwrap().as_ref().unwrap().is_empty());
+
+        // The late response is ignored, and the connection is still usable.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(client.read_holding_registers(1, 0, 1).await.unwrap(), [7]);
     }
-    u16::checked_add(address, (length - 1) as u16)
-        .ok_or(ModbusError::ArgumentsOutOfRange("Address + length exceeds device address space"))?;
-    Ok(())
 }
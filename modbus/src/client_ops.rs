@@ -0,0 +1,242 @@
+//! The client operations, shared by the clients for the different transports.
+
+use std::{borrow::Cow, collections::BTreeMap, future::Future};
+
+use bytes::Bytes;
+
+use crate::{
+    bit_buf::BitBuf,
+    client::ModbusError,
+    consts::*,
+    encoding::*,
+    function_code::FunctionCode,
+    messages::*,
+    modbus_encapsulated_interface::*,
+    telemetry,
+    views::{Bits, Registers},
+};
+
+/// Sends a request to a unit and returns the body of the response.
+pub(crate) trait Transport: Sync {
+    /// Implementations check the response with [`response_body`].
+    fn send_request(&self, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> impl Future<Output = Result<Bytes, ModbusError>> + Send;
+}
+
+pub(crate) async fn read_coils(transport: &impl Transport, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+    validate_input(address, length as usize, READ_COILS_MAX_LEN)?;
+    let req = ReadCoilsRequest { address, length };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::ReadCoils, req_body).await?;
+    let res: ReadCoilsResponse = decode(FunctionCode::ReadCoils, &result)?;
+    response_bits(res.values, length)
+}
+
+pub(crate) async fn read_discrete_inputs(transport: &impl Transport, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+    validate_input(address, length as usize, READ_DISCRETE_INPUTS_MAX_LEN)?;
+    let req = ReadDiscreteInputsRequest { address, length };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::ReadDiscreteInputs, req_body).await?;
+    let res: ReadDiscreteInputsResponse = decode(FunctionCode::ReadDiscreteInputs, &result)?;
+    response_bits(res.values, length)
+}
+
+pub(crate) async fn read_input_registers(transport: &impl Transport, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+    validate_input(address, length as usize, READ_INPUT_REGISTERS_MAX_LEN)?;
+    let req = ReadInputRegistersRequest { address, length };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::ReadInputRegisters, req_body).await?;
+    let res: ReadInputRegistersResponse = decode(FunctionCode::ReadInputRegisters, &result)?;
+    response_registers(res.values, length)
+}
+
+pub(crate) async fn read_holding_registers(transport: &impl Transport, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+    validate_input(address, length as usize, READ_HOLDING_REGISTERS_MAX_LEN)?;
+    let req = ReadHoldingRegistersRequest { address, length };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::ReadHoldingRegisters, req_body).await?;
+    let res: ReadHoldingRegistersResponse = decode(FunctionCode::ReadHoldingRegisters, &result)?;
+    response_registers(res.values, length)
+}
+
+pub(crate) async fn write_single_coils(transport: &impl Transport, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+    let req = WriteSingleCoilRequest { address, value };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::WriteSingleCoil, req_body).await?;
+    let res: WriteSingleCoilResponse = decode(FunctionCode::WriteSingleCoil, &result)?;
+    if res.address == req.address && res.value == req.value {
+        Ok(())
+    } else {
+        Err(ModbusError::InvalidResponse("Address and value mismatch"))
+    }
+}
+
+pub(crate) async fn write_single_holding_register(transport: &impl Transport, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+    let req = WriteSingleHoldingRegisterRequest { address, value };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::WriteSingleHoldingRegister, req_body).await?;
+    let res: WriteSingleHoldingRegisterResponse = decode(FunctionCode::WriteSingleHoldingRegister, &result)?;
+    if res.address == req.address && res.value == req.value {
+        Ok(())
+    } else {
+        Err(ModbusError::InvalidResponse("Address and value mismatch"))
+    }
+}
+
+pub(crate) async fn write_multiple_coils(transport: &impl Transport, unit_id: u8, address: u16, values: Bits<'_>) -> Result<(), ModbusError> {
+    validate_input(address, values.len(), WRITE_MULTIPLE_COILS_MAX_LEN)?;
+    let req = WriteMultipleCoilsRequest { address, values };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::WriteMultipleCoils, req_body).await?;
+    let res: WriteMultipleCoilsResponse = decode(FunctionCode::WriteMultipleCoils, &result)?;
+    if res.address == req.address && res.length as usize == req.values.len() {
+        Ok(())
+    } else {
+        Err(ModbusError::InvalidResponse("Address and length mismatch"))
+    }
+}
+
+pub(crate) async fn write_multiple_holding_registers(transport: &impl Transport, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+    validate_input(address, values.len(), WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN)?;
+    let req = WriteMultipleHoldingRegistersRequest {
+        address,
+        values: values.into(),
+    };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::WriteMultipleHoldingRegisters, req_body).await?;
+    let res: WriteMultipleHoldingRegistersResponse = decode(FunctionCode::WriteMultipleHoldingRegisters, &result)?;
+    if res.address == req.address && res.length as usize == req.values.len() {
+        Ok(())
+    } else {
+        Err(ModbusError::InvalidResponse("Address and length mismatch"))
+    }
+}
+
+pub(crate) async fn mask_write_holding_registers(transport: &impl Transport, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+    let req = MaskWriteHoldingRegisterRequest { address, and_mask, or_mask };
+    let req_body = req.encode_to_bytes().expect("Couldn't encode request");
+    let result = transport.send_request(unit_id, FunctionCode::MaskWriteHoldingRegister, req_body).await?;
+    let res: MaskWriteHoldingRegisterResponse = decode(FunctionCode::MaskWriteHoldingRegister, &result)?;
+    if res.address == req.address && res.and_mask == req.and_mask && res.or_mask == req.or_mask {
+        Ok(())
+    } else {
+        Err(ModbusError::InvalidResponse("Address and mask mismatch"))
+    }
+}
+
+pub(crate) async fn modbus_encapsulated_interface(transport: &impl Transport, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+    let req = ModbusEncapsulatedInterfaceRequest {
+        kind: ModbusEncapsulatedInterfaceType::Unknown(interface_type),
+        data: data.into(),
+    };
+
+    let res_body = transport
+        .send_request(unit_id, FunctionCode::ModbusEncapsulatedInterface, req.encode_to_bytes().unwrap())
+        .await?;
+    let res: ModbusEncapsulatedInterfaceResponse = decode(FunctionCode::ModbusEncapsulatedInterface, &res_body)?;
+
+    if res.kind != req.kind {
+        return Err(ModbusError::InvalidResponse("Interface type mismatch"));
+    }
+
+    Ok(res.data.into_owned())
+}
+
+pub(crate) async fn read_device_identification(transport: &impl Transport, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+    let mut more_follows = true;
+    let mut next_object_id = 0u8;
+
+    let mut result = DeviceIdentification {
+        vendor_name: "".into(),
+        product_code: "".into(),
+        major_minor_revision: "".into(),
+        model_name: None,
+        product_name: None,
+        user_application_name: None,
+        vendor_url: None,
+        objects: BTreeMap::new(),
+    };
+
+    for _ in 0..0xFFu8 {
+        if !more_follows {
+            break;
+        }
+
+        let req = ReadDeviceIdentificationRequest {
+            object_id: next_object_id,
+            device_id_code: ReadDeviceIdentificationIdCode::Extended,
+        };
+
+        let res_body = modbus_encapsulated_interface(
+            transport,
+            unit_id,
+            ModbusEncapsulatedInterfaceType::ReadDeviceIdentification.into(),
+            &req.encode_to_bytes().unwrap(),
+        )
+        .await?;
+        let res: ReadDeviceIdentificationResponse = decode(FunctionCode::ModbusEncapsulatedInterface, &res_body)?;
+
+        more_follows = res.more_follows;
+        next_object_id = res.next_object_id;
+
+        for (id, data) in res.objects {
+            let str_data = || -> Cow<str> { String::from_utf8_lossy(&data).to_string().into() };
+            match id {
+                0 => result.vendor_name = str_data(),
+                1 => result.product_code = str_data(),
+                2 => result.major_minor_revision = str_data(),
+                3 => result.vendor_url = Some(str_data()),
+                4 => result.product_name = Some(str_data()),
+                5 => result.model_name = Some(str_data()),
+                6 => result.user_application_name = Some(str_data()),
+                _ => {
+                    result.objects.insert(id, data.into_owned().into());
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// The body of a successful response, or the exception reported by the server.
+pub(crate) fn response_body(request_function_code: FunctionCode, function_code: FunctionCode, body: Bytes) -> Result<Bytes, ModbusError> {
+    if let FunctionCode::Error(_) = function_code {
+        let ex_res: ExceptionMessage = decode(function_code, &body)?;
+        return Err(ModbusError::ModbusException(ex_res.code));
+    }
+    if function_code != request_function_code {
+        return Err(ModbusError::InvalidResponse("Function code mismatch"));
+    }
+    Ok(body)
+}
+
+/// Bits are padded to whole bytes, the padding is removed.
+fn response_bits(values: Bits, length: u16) -> Result<BitBuf, ModbusError> {
+    if values.len() != (length as usize).div_ceil(8) * 8 {
+        return Err(ModbusError::InvalidResponse("Length mismatch"));
+    }
+    Ok(values.slice(..length as usize).into())
+}
+
+fn response_registers(values: Registers, length: u16) -> Result<Vec<u16>, ModbusError> {
+    if values.len() != length as usize {
+        return Err(ModbusError::InvalidResponse("Length mismatch"));
+    }
+    Ok(values.to_vec())
+}
+
+pub(crate) fn decode<'a, T: Decodable<'a>>(function_code: FunctionCode, bytes: &'a [u8]) -> Result<T, ModbusError> {
+    T::decode_from_bytes(bytes).map_err(|error| {
+        telemetry::client_decode_failed(function_code);
+        error.into()
+    })
+}
+
+pub(crate) fn validate_input(address: u16, length: usize, max_length: u16) -> Result<(), ModbusError> {
+    if length == 0 || length > max_length as usize {
+        return Err(ModbusError::ArgumentsOutOfRange("Length exceeds maximum allowed length"));
+    }
+    u16::checked_add(address, (length - 1) as u16)
+        .ok_or(ModbusError::ArgumentsOutOfRange("Address + length exceeds device address space"))?;
+    Ok(())
+}
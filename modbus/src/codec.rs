@@ -0,0 +1,177 @@
+//! [`tokio_util::codec`] implementations of the sans-IO frame codecs, for use with `Framed`, `FramedRead` and `FramedWrite`.
+
+use std::io;
+
+use bytes::BytesMut;
+use thiserror::Error;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    ascii::AsciiCodec,
+    message::{Frame, FrameError, MbapCodec},
+    rtu::{RtuCodec, SerialFrame},
+};
+
+/// An error reading or writing frames on a stream.
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Frame(#[from] FrameError),
+}
+
+/// A stream that ends within a frame is an error, as it would be with `read_exact`.
+fn unexpected_eof<T>(frame: Option<T>, buffer: &BytesMut) -> Result<Option<T>, CodecError> {
+    match frame {
+        None if !buffer.is_empty() => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        frame => Ok(frame),
+    }
+}
+
+impl Decoder for MbapCodec {
+    type Item = Frame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, CodecError> {
+        Ok(MbapCodec::decode(self, src)?)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, CodecError> {
+        let frame = MbapCodec::decode(self, src)?;
+        unexpected_eof(frame, src)
+    }
+}
+
+impl Encoder<Frame> for MbapCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), CodecError> {
+        Ok(MbapCodec::encode(self, &item, dst)?)
+    }
+}
+
+/// At the end of the stream the remaining bytes are decoded as a frame, as after silence on the line.
+impl Decoder for RtuCodec {
+    type Item = SerialFrame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<SerialFrame>, CodecError> {
+        Ok(RtuCodec::decode(self, src)?)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<SerialFrame>, CodecError> {
+        match RtuCodec::decode(self, src)? {
+            Some(frame) => Ok(Some(frame)),
+            None => Ok(self.decode_idle(src)?),
+        }
+    }
+}
+
+impl Encoder<SerialFrame> for RtuCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: SerialFrame, dst: &mut BytesMut) -> Result<(), CodecError> {
+        Ok(RtuCodec::encode(self, &item, dst)?)
+    }
+}
+
+impl Decoder for AsciiCodec {
+    type Item = SerialFrame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<SerialFrame>, CodecError> {
+        Ok(AsciiCodec::decode(self, src)?)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<SerialFrame>, CodecError> {
+        let frame = AsciiCodec::decode(self, src)?;
+        unexpected_eof(frame, src)
+    }
+}
+
+impl Encoder<SerialFrame> for AsciiCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: SerialFrame, dst: &mut BytesMut) -> Result<(), CodecError> {
+        Ok(AsciiCodec::encode(self, &item, dst)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bytes::Bytes;
+    use tokio_util::codec::FramedRead;
+
+    use crate::{function_code::FunctionCode, message::MSG_MAX_LENGTH};
+
+    use super::*;
+
+    async fn read_all<D: Decoder>(bytes: Vec<u8>, decoder: D) -> Vec<Result<D::Item, D::Error>> {
+        let mut reader = FramedRead::new(Cursor::new(bytes), decoder);
+        let mut frames = Vec::new();
+        while let Some(frame) = std::future::poll_fn(|cx| futures_core::Stream::poll_next(std::pin::Pin::new(&mut reader), cx)).await {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn mbap() {
+        let frame = Frame::new(1, 3, &[4, 5, 6, 7]).unwrap();
+        let mut bytes = BytesMut::new();
+        Encoder::encode(&mut MbapCodec::new(), frame.clone(), &mut bytes).unwrap();
+        Encoder::encode(&mut MbapCodec::new(), frame.clone(), &mut bytes).unwrap();
+        let frames = read_all(bytes.to_vec(), MbapCodec::new()).await;
+        assert!(matches!(&frames[..], [Ok(a), Ok(b)] if *a == frame && *b == frame));
+
+        // Ends within a frame.
+        let frames = read_all(bytes[..15].to_vec(), MbapCodec::new()).await;
+        assert!(matches!(&frames[..], [Ok(_), Err(CodecError::IO(e))] if e.kind() == io::ErrorKind::UnexpectedEof));
+
+        for (header, error) in [
+            ([0, 1, 0, 2, 0, 2, 1, 3], FrameError::InvalidProtocolId(2)),
+            ([0, 1, 0, 0, 0, 1, 1, 3], FrameError::LengthTooShort(1)),
+            ([0, 1, 0, 0, 0, 0, 1, 3], FrameError::LengthTooShort(0)),
+            ([0, 1, 0, 0, 1, 0, 1, 3], FrameError::LengthTooLong(256)),
+        ] {
+            let frames = read_all(header.to_vec(), MbapCodec::new()).await;
+            assert!(matches!(&frames[..], [Err(CodecError::Frame(e))] if *e == error), "Expected {:?}, got {:?}", error, frames);
+        }
+
+        // Resync across reads.
+        let mut garbage = vec![0xFF, 0x00, 0x12, 0x34, 0x00];
+        garbage.extend_from_slice(&bytes);
+        let frames = read_all(garbage, MbapCodec::new().with_resync(true)).await;
+        assert_eq!(frames.len(), 2);
+        let frames = read_all(vec![0xFF; MSG_MAX_LENGTH * 2], MbapCodec::new().with_resync(true)).await;
+        assert!(matches!(&frames[..], [Err(CodecError::Frame(FrameError::InvalidProtocolId(0xFFFF)))]));
+    }
+
+    #[tokio::test]
+    async fn serial() {
+        let frame = SerialFrame {
+            unit_id: 0x11,
+            function_code: FunctionCode::ReadHoldingRegisters,
+            body: Bytes::from_static(&[0x00, 0x6B, 0x00, 0x03]),
+        };
+
+        let mut bytes = BytesMut::new();
+        Encoder::encode(&mut AsciiCodec::new(), frame.clone(), &mut bytes).unwrap();
+        let frames = read_all(bytes.to_vec(), AsciiCodec::new()).await;
+        assert!(matches!(&frames[..], [Ok(a)] if *a == frame));
+
+        // A response whose length can't be told from its content ends with the stream.
+        let response = SerialFrame {
+            unit_id: 0x11,
+            function_code: FunctionCode::ModbusEncapsulatedInterface,
+            body: Bytes::from_static(&[0x0D, 0x01, 0x02]),
+        };
+        let mut bytes = BytesMut::new();
+        Encoder::encode(&mut RtuCodec::responses(), response.clone(), &mut bytes).unwrap();
+        let frames = read_all(bytes.to_vec(), RtuCodec::responses()).await;
+        assert!(matches!(&frames[..], [Ok(a)] if *a == response));
+    }
+}
@@ -1,6 +1,6 @@
-use std::ops::DerefMut;
+use std::{future, pin::Pin};
 
-use thiserror::Error;
+use futures_core::Stream;
 use tokio::{
     io::AsyncWriteExt,
     net::{
@@ -8,60 +8,45 @@ use tokio::{
     },
     sync::Mutex,
 };
+use tokio_util::codec::FramedRead;
 
-use crate::{encoding::*, message::Message};
+use crate::{
+    codec::CodecError,
+    message::{Frame, MbapCodec},
+};
 
 pub struct Connection {
-    reader: Mutex<OwnedReadHalf>,
+    reader: Mutex<FramedRead<OwnedReadHalf, MbapCodec>>,
     writer: Mutex<OwnedWriteHalf>,
 }
 
-#[derive(Error, Debug)]
-pub enum ReadError {
-    #[error(transparent)]
-    IO(#[from] tokio::io::Error),
-    #[error(transparent)]
-    Decode(#[from] DecodeError),
-}
-
-#[derive(Error, Debug)]
-pub enum WriteError {
-    #[error(transparent)]
-    IO(#[from] tokio::io::Error),
-    #[error(transparent)]
-    Encode(#[from] EncodeError),
-}
-
 impl Connection {
-    pub fn new(stream: TcpStream) -> Self {
+    /// With `resync`, invalid bytes between frames are skipped, see [`MbapCodec::with_resync`].
+    pub fn new(stream: TcpStream, resync: bool) -> Self {
         let (reader, writer) = stream.into_split();
         Self {
-            reader: Mutex::new(reader),
+            reader: Mutex::new(FramedRead::new(reader, MbapCodec::new().with_resync(resync))),
             writer: Mutex::new(writer),
         }
     }
 
-    pub async fn read_message(&self) -> Result<Option<Message>, ReadError> {
+    /// Returns `None` if the connection was closed by the peer between messages.
+    pub async fn read_message(&self) -> Result<Option<Frame>, CodecError> {
         let mut reader = self.reader.lock().await;
 
-        let mut t = [0];
-        let len = reader.peek(&mut t).await?;
-        if len == 0 { return Ok(None); }
-
-        Ok(Some(Message::read(reader.deref_mut()).await?))
+        future::poll_fn(|cx| Pin::new(&mut *reader).poll_next(cx)).await.transpose()
     }
 
-    pub async fn write_message(&self, msg: &Message) -> Result<(), WriteError> {
-        let bytes = msg.encode_to_bytes().map_err(WriteError::Encode)?;
+    pub async fn write_message(&self, msg: &Frame) -> Result<(), CodecError> {
+        let bytes = msg.encode()?;
 
         let mut writer = self.writer.lock().await;
 
-        writer.write_all(&bytes).await.map_err(WriteError::IO)?;
+        writer.write_all(&bytes).await?;
 
         Ok(())
     }
 
-    #[allow(unused)]
     pub async fn shutdown(&self) -> Result<(), std::io::Error> {
         self.writer.lock().await.shutdown().await
     }
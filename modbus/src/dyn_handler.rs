@@ -0,0 +1,140 @@
+//! A dyn compatible version of the request handlers of [`ModbusTCPServerHandler`],
+//! so handlers of different types can be stored together.
+
+use std::{borrow::Cow, future::Future, pin::Pin};
+
+use crate::{
+    bit_buf::BitBuf, modbus_encapsulated_interface::DeviceIdentification, modbus_exception::ModbusException, request_context::RequestContext,
+    server::ModbusTCPServerHandler, views::Bits,
+};
+
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Responses borrow from the handler (`'a`), the futures also borrow from the request (`'b`).
+pub(crate) trait DynHandler: Send + Sync {
+    fn read_coils<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, length: u16) -> BoxFuture<'b, Result<Cow<'a, BitBuf>, ModbusException>>;
+    fn read_discrete_inputs<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, BitBuf>, ModbusException>>;
+    fn read_input_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, [u16]>, ModbusException>>;
+    fn read_holding_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, [u16]>, ModbusException>>;
+    fn write_coils<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, values: Bits<'b>) -> BoxFuture<'b, Result<(), ModbusException>>;
+    fn write_holding_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        values: &'b [u16],
+    ) -> BoxFuture<'b, Result<(), ModbusException>>;
+    fn write_single_coil<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, value: bool) -> BoxFuture<'b, Result<(), ModbusException>>;
+    fn write_single_holding_register<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        value: u16,
+    ) -> BoxFuture<'b, Result<(), ModbusException>>;
+    fn mask_write_holding_register<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> BoxFuture<'b, Result<(), ModbusException>>;
+    fn read_device_identification<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+    ) -> BoxFuture<'b, Result<Cow<'a, DeviceIdentification<'a>>, ModbusException>>;
+    fn modbus_encapsulated_interface<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        interface_type: u8,
+        data: &'b [u8],
+    ) -> BoxFuture<'b, Result<Cow<'a, [u8]>, ModbusException>>;
+}
+
+impl<T: ModbusTCPServerHandler> DynHandler for T {
+    fn read_coils<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, length: u16) -> BoxFuture<'b, Result<Cow<'a, BitBuf>, ModbusException>> {
+        Box::pin(self.handle_read_coils(ctx, address, length))
+    }
+    fn read_discrete_inputs<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, BitBuf>, ModbusException>> {
+        Box::pin(self.handle_read_discrete_inputs(ctx, address, length))
+    }
+    fn read_input_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, [u16]>, ModbusException>> {
+        Box::pin(self.handle_read_input_registers(ctx, address, length))
+    }
+    fn read_holding_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        length: u16,
+    ) -> BoxFuture<'b, Result<Cow<'a, [u16]>, ModbusException>> {
+        Box::pin(self.handle_read_holding_registers(ctx, address, length))
+    }
+    fn write_coils<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, values: Bits<'b>) -> BoxFuture<'b, Result<(), ModbusException>> {
+        Box::pin(self.handle_write_coils(ctx, address, values))
+    }
+    fn write_holding_registers<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        values: &'b [u16],
+    ) -> BoxFuture<'b, Result<(), ModbusException>> {
+        Box::pin(self.handle_write_holding_registers(ctx, address, values))
+    }
+    fn write_single_coil<'a: 'b, 'b>(&'a self, ctx: &'b RequestContext, address: u16, value: bool) -> BoxFuture<'b, Result<(), ModbusException>> {
+        Box::pin(self.handle_write_single_coil(ctx, address, value))
+    }
+    fn write_single_holding_register<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        value: u16,
+    ) -> BoxFuture<'b, Result<(), ModbusException>> {
+        Box::pin(self.handle_write_single_holding_register(ctx, address, value))
+    }
+    fn mask_write_holding_register<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> BoxFuture<'b, Result<(), ModbusException>> {
+        Box::pin(self.handle_mask_write_holding_register(ctx, address, and_mask, or_mask))
+    }
+    fn read_device_identification<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+    ) -> BoxFuture<'b, Result<Cow<'a, DeviceIdentification<'a>>, ModbusException>> {
+        Box::pin(self.handle_read_device_identification(ctx))
+    }
+    fn modbus_encapsulated_interface<'a: 'b, 'b>(
+        &'a self,
+        ctx: &'b RequestContext,
+        interface_type: u8,
+        data: &'b [u8],
+    ) -> BoxFuture<'b, Result<Cow<'a, [u8]>, ModbusException>> {
+        Box::pin(self.handle_modbus_encapsulated_interface(ctx, interface_type, data))
+    }
+}
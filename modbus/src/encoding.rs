@@ -1,11 +1,11 @@
+use alloc::vec::Vec;
 use bytes::Buf;
+use core::num::TryFromIntError;
 use thiserror::Error;
-use std::{
-    io::{Cursor, Read},
-    num::TryFromIntError,
-};
 
-#[derive(Error, PartialEq, Debug)]
+use crate::views::{Bits, Registers};
+
+#[derive(Error, PartialEq, Eq, Debug, Clone, Copy)]
 pub enum EncodeError {
     #[error("Overflow")]
     Overflow,
@@ -22,6 +22,8 @@ pub type EncodeResult = Result<(), EncodeError>;
 pub trait Encodable {
     fn encode(&self, encoder: &mut Encoder) -> EncodeResult;
 
+    // Only used by the client and server.
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
     fn encode_to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
         Encoder::encode(self)
     }
@@ -51,14 +53,28 @@ impl Encoder {
         self.buffer.extend(value.to_be_bytes());
     }
 
-    pub fn write_bools(&mut self, values: &[bool]) {
+    /// A single coil value, encoded as 0xFF00 for on and 0x0000 for off.
+    pub fn write_coil(&mut self, value: bool) {
+        self.write_u16(if value { 0xFF00 } else { 0x0000 });
+    }
+
+    /// Bits packed into bytes, least significant bit first, with the padding of the last byte cleared.
+    pub fn write_bits(&mut self, values: Bits) {
         let byte_length = values.len().div_ceil(8);
         self.buffer.reserve(byte_length);
-        for i in 0..byte_length {
+        if let Some(bytes) = values.as_packed() {
+            self.buffer.extend_from_slice(bytes);
+            if !values.len().is_multiple_of(8) {
+                *self.buffer.last_mut().unwrap() &= (1 << (values.len() % 8)) - 1;
+            }
+            return;
+        }
+        let mut values = values.iter();
+        for _ in 0..byte_length {
             let mut byte = 0;
-            for i2 in 0..8 {
-                if values.get(i * 8 + i2).copied().unwrap_or_default() {
-                    byte |= 1 << i2;
+            for (i, value) in values.by_ref().take(8).enumerate() {
+                if value {
+                    byte |= 1 << i;
                 }
             }
             self.write_u8(byte);
@@ -69,8 +85,8 @@ impl Encoder {
         self.buffer.extend(value);
     }
 
-    pub fn write_registers(&mut self, value: &[u16]) {
-        self.buffer.extend(value.iter().flat_map(|v| v.to_be_bytes()));
+    pub fn write_registers(&mut self, values: Registers) {
+        self.buffer.extend(values.iter().flat_map(|v| v.to_be_bytes()));
     }
 
     pub fn write_type<T>(&mut self, value: &T) -> EncodeResult
@@ -84,6 +100,7 @@ impl Encoder {
         self.buffer
     }
 
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
     pub fn encode<T>(value: &T) -> Result<Vec<u8>, EncodeError>
     where
         T: Encodable + ?Sized,
@@ -94,7 +111,7 @@ impl Encoder {
     }
 }
 
-#[derive(Error, PartialEq, Debug)]
+#[derive(Error, PartialEq, Eq, Debug, Clone, Copy)]
 pub enum DecodeError {
     #[error("Missing data")]
     MissingData,
@@ -104,100 +121,97 @@ pub enum DecodeError {
 
 pub type DecodeResult<T> = Result<T, DecodeError>;
 
-pub trait Decodable<T> {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<T>;
+/// Decoded values may borrow from the buffer they are decoded from.
+pub trait Decodable<'a>: Sized {
+    fn decode(decoder: &mut Decoder<'a>) -> DecodeResult<Self>;
 
-    fn decode_from_bytes(buffer: &[u8]) -> DecodeResult<T>
-    where
-        T: Decodable<T>,
-    {
+    fn decode_from_bytes(buffer: &'a [u8]) -> DecodeResult<Self> {
         Decoder::decode(buffer)
     }
 }
 
 pub struct Decoder<'a> {
-    cursor: Cursor<&'a [u8]>,
+    buffer: &'a [u8],
+    length: usize,
 }
 
 impl<'a> Decoder<'a> {
     pub fn new(buffer: &'a [u8]) -> Self {
         Self {
-            cursor: Cursor::new(buffer),
+            buffer,
+            length: buffer.len(),
         }
     }
 
     #[allow(unused)]
     pub fn position(&self) -> usize {
-        self.cursor.position() as usize
+        self.length - self.buffer.len()
     }
 
     #[allow(unused)]
     pub fn remaining(&self) -> usize {
-        self.cursor.remaining()
+        self.buffer.remaining()
     }
 
     pub fn read_u8(&mut self) -> DecodeResult<u8> {
-        if self.cursor.remaining() < 1 {
+        if self.buffer.remaining() < 1 {
             return Err(DecodeError::MissingData);
         }
-        Ok(self.cursor.get_u8())
+        Ok(self.buffer.get_u8())
     }
 
     pub fn read_u16(&mut self) -> DecodeResult<u16> {
-        if self.cursor.remaining() < 2 {
+        if self.buffer.remaining() < 2 {
             return Err(DecodeError::MissingData);
         }
-        Ok(self.cursor.get_u16())
+        Ok(self.buffer.get_u16())
     }
 
-    pub fn read_bools(&mut self, length: usize) -> DecodeResult<Vec<bool>> {
-        let byte_length = length.div_ceil(8);
-        let mut values = Vec::with_capacity(length);
-        for _ in 0..byte_length {
-            let byte = self.read_u8()?;
-            for i2 in 0..8 {
-                if values.len() == length {
-                    break;
-                }
-                values.push((byte & (1 << i2)) > 0);
-            }
+    /// Bits packed into bytes, least significant bit first.
+    pub fn read_bits(&mut self, length: usize) -> DecodeResult<Bits<'a>> {
+        let bytes = self.read_bytes(length.div_ceil(8))?;
+        Ok(Bits::new(bytes, length).unwrap())
+    }
+
+    /// A single coil value, encoded as 0xFF00 for on and 0x0000 for off.
+    pub fn read_coil(&mut self) -> DecodeResult<bool> {
+        match self.read_u16()? {
+            0xFF00 => Ok(true),
+            0x0000 => Ok(false),
+            _ => Err(DecodeError::InvalidData("Invalid coil value")),
         }
-        Ok(values)
     }
 
-    pub fn read_bytes(&mut self, length: usize) -> DecodeResult<Vec<u8>> {
-        if self.cursor.remaining() < length {
+    pub fn read_bytes(&mut self, length: usize) -> DecodeResult<&'a [u8]> {
+        if self.buffer.remaining() < length {
             return Err(DecodeError::MissingData);
         }
-        let mut bytes = vec![0u8; length];
-        self.cursor.read_exact(&mut bytes).unwrap();
+        let (bytes, rest) = self.buffer.split_at(length);
+        self.buffer = rest;
         Ok(bytes)
     }
 
-    pub fn read_registers(&mut self, length: usize) -> DecodeResult<Vec<u16>> {
-        if self.cursor.remaining() < length * 2 {
-            return Err(DecodeError::MissingData);
-        }
-        let mut registers = Vec::with_capacity(length);
-        for _ in 0..length {
-            registers.push(self.cursor.get_u16());
-        }
-        Ok(registers)
+    pub fn read_registers(&mut self, length: usize) -> DecodeResult<Registers<'a>> {
+        let bytes = self.read_bytes(length * 2)?;
+        Ok(Registers::new(bytes).unwrap())
     }
 
     pub fn read_type<T>(&mut self) -> DecodeResult<T>
     where
-        T: Decodable<T>,
+        T: Decodable<'a>,
     {
         T::decode(self)
     }
 
     pub fn decode<T>(buffer: &'a [u8]) -> DecodeResult<T>
     where
-        T: Decodable<T>,
+        T: Decodable<'a>,
     {
         let mut decoder = Self::new(buffer);
         let value: T = decoder.read_type()?;
+        if decoder.remaining() > 0 {
+            return Err(DecodeError::InvalidData("Trailing data"));
+        }
         Ok(value)
     }
 }
@@ -212,25 +226,46 @@ mod tests {
         encoder.write_u8(0xAA);
         encoder.write_u16(0xBBCC);
         encoder.write_bytes(&[1, 2, 3]);
-        encoder.write_registers(&[300, 301, 302]);
+        encoder.write_registers((&[300, 301, 302][..]).into());
+        encoder.write_bits((&[true, false, true][..]).into());
+        encoder.write_bits(Bits::new(&[0xFF], 3).unwrap());
 
-        assert_eq!(encoder.position(), 12);
+        assert_eq!(encoder.position(), 14);
 
         let bytes = encoder.finish();
 
-        assert_eq!(bytes.len(), 12);
+        assert_eq!(bytes.len(), 14);
 
         let mut decoder = Decoder::new(&bytes);
 
         assert_eq!(decoder.position(), 0);
-        assert_eq!(decoder.remaining(), 12);
+        assert_eq!(decoder.remaining(), 14);
 
         assert_eq!(decoder.read_u8(), Ok(0xAA));
         assert_eq!(decoder.read_u16(), Ok(0xBBCC));
-        assert_eq!(decoder.read_bytes(3), Ok(vec![1, 2, 3]));
-        assert_eq!(decoder.read_registers(3), Ok(vec![300, 301, 302]));
+        assert_eq!(decoder.read_bytes(3), Ok(&[1, 2, 3][..]));
+        assert_eq!(decoder.read_registers(3).unwrap().to_vec(), [300, 301, 302]);
+        assert_eq!(decoder.read_bits(3).unwrap().to_vec(), [true, false, true]);
+        assert_eq!(decoder.read_u8(), Ok(0b111));
 
-        assert_eq!(decoder.position(), 12);
+        assert_eq!(decoder.position(), 14);
         assert_eq!(decoder.remaining(), 0);
     }
+
+    #[test]
+    fn strict() {
+        struct Coil(bool);
+
+        impl Decodable<'_> for Coil {
+            fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
+                Ok(Self(decoder.read_coil()?))
+            }
+        }
+
+        assert!(Coil::decode_from_bytes(&[0xFF, 0x00]).unwrap().0);
+        assert!(!Coil::decode_from_bytes(&[0x00, 0x00]).unwrap().0);
+        assert!(Coil::decode_from_bytes(&[0x00, 0x01]).is_err());
+        assert_eq!(Coil::decode_from_bytes(&[0xFF, 0x00, 0x00]).err(), Some(DecodeError::InvalidData("Trailing data")));
+        assert_eq!(Coil::decode_from_bytes(&[0xFF]).err(), Some(DecodeError::MissingData));
+    }
 }
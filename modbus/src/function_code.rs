@@ -1,5 +1,7 @@
+/// A Modbus function code. Serialized as the numeric code.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "u8", into = "u8"))]
 pub enum FunctionCode {
     ReadCoils = 1,
     ReadDiscreteInputs = 2,
@@ -16,6 +18,7 @@ pub enum FunctionCode {
 }
 
 impl FunctionCode {
+    /// The function code of an exception response to this function code.
     pub fn as_err(self) -> Self {
         FunctionCode::Error(u8::from(self) | 128u8)
     }
@@ -69,3 +72,5 @@ impl PartialEq for FunctionCode {
         u8::from(*self) == u8::from(*other)
     }
 }
+
+impl Eq for FunctionCode {}
@@ -0,0 +1,230 @@
+//! Entry points for the fuzz targets in `fuzz/`, also run by the property tests below.
+//!
+//! Each function panics if the input reveals a bug, e.g. a message that decodes but doesn't encode back to itself.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use bytes::BytesMut;
+
+use crate::{
+    ascii::AsciiCodec,
+    encoding::{Decodable, Encodable},
+    message::{Frame, FrameError, MbapCodec},
+    messages::*,
+    pdu::{Request, Response, PDU_MAX_LENGTH},
+    rtu::{RtuCodec, SerialFrame},
+};
+
+/// Decodes each message of `messages`, checking that what decodes is encoded to bytes that decode to the same message.
+macro_rules! round_trip {
+    ($data:expr, $($message:ident),* $(,)?) => {
+        $(
+            if let Ok(message) = $message::decode_from_bytes($data) {
+                let encoded = message.encode_to_bytes().expect(concat!(stringify!($message), " decodes but doesn't encode"));
+                assert_eq!($message::decode_from_bytes(&encoded), Ok(message));
+            }
+        )*
+    };
+}
+
+/// Decodes the data of a PDU, the bytes after the function code, as every message.
+pub fn decode_messages(data: &[u8]) {
+    round_trip!(
+        data,
+        ExceptionMessage,
+        MaskWriteHoldingRegisterRequest,
+        MaskWriteHoldingRegisterResponse,
+        ModbusEncapsulatedInterfaceRequest,
+        ModbusEncapsulatedInterfaceResponse,
+        ReadCoilsRequest,
+        ReadCoilsResponse,
+        ReadDeviceIdentificationRequest,
+        ReadDeviceIdentificationResponse,
+        ReadDiscreteInputsRequest,
+        ReadDiscreteInputsResponse,
+        ReadHoldingRegistersRequest,
+        ReadHoldingRegistersResponse,
+        ReadInputRegistersRequest,
+        ReadInputRegistersResponse,
+        WriteMultipleCoilsRequest,
+        WriteMultipleCoilsResponse,
+        WriteMultipleHoldingRegistersRequest,
+        WriteMultipleHoldingRegistersResponse,
+        WriteSingleCoilRequest,
+        WriteSingleCoilResponse,
+        WriteSingleHoldingRegisterRequest,
+        WriteSingleHoldingRegisterResponse,
+    );
+}
+
+/// Decodes a PDU as a request and as a response.
+pub fn decode_pdus(pdu: &[u8]) {
+    let request = Request::decode(pdu);
+    let response = Response::decode(pdu);
+    // Longer PDUs can be decoded, but don't fit in a frame and so aren't encoded.
+    if pdu.len() > PDU_MAX_LENGTH {
+        return;
+    }
+    if let Ok(request) = request {
+        let encoded = request.encode().expect("Request decodes but doesn't encode");
+        assert_eq!(Request::decode(&encoded), Ok(request));
+    }
+    if let Ok(response) = response {
+        let encoded = response.encode().expect("Response decodes but doesn't encode");
+        assert_eq!(Response::decode(&encoded), Ok(response));
+    }
+}
+
+/// Decodes a byte stream with each of the frame codecs.
+pub fn decode_frames(data: &[u8]) {
+    if let Ok((frame, rest)) = Frame::decode(data) {
+        assert!(rest.len() < data.len());
+        assert_eq!(Frame::decode(&frame.encode().unwrap()), Ok((frame, &[][..])));
+    }
+
+    for resync in [false, true] {
+        let mut codec = MbapCodec::new().with_resync(resync);
+        for frame in decode_stream(data, |buffer| codec.decode(buffer)) {
+            assert_eq!(Frame::decode(&frame.encode().unwrap()), Ok((frame, &[][..])));
+        }
+    }
+
+    for mut codec in [RtuCodec::requests(), RtuCodec::responses()] {
+        let mut buffer = BytesMut::from(data);
+        while let Ok(Some(frame)) = codec.decode(&mut buffer) {
+            assert_eq!(encode_and_decode_idle(&frame), frame);
+        }
+        // What remains is taken as a whole frame after silence on the line.
+        if let Ok(Some(frame)) = codec.decode_idle(&mut buffer) {
+            assert!(buffer.is_empty());
+            assert_eq!(encode_and_decode_idle(&frame), frame);
+        }
+    }
+
+    let mut codec = AsciiCodec::new();
+    for frame in decode_stream(data, |buffer| codec.decode(buffer)) {
+        let mut buffer = BytesMut::new();
+        codec.encode(&frame, &mut buffer).unwrap();
+        assert_eq!(codec.decode(&mut buffer), Ok(Some(frame)));
+    }
+}
+
+/// Decodes frames until more bytes are needed or an error occurs, checking that each frame consumes bytes.
+fn decode_stream<T: Debug>(data: &[u8], mut decode: impl FnMut(&mut BytesMut) -> Result<Option<T>, FrameError>) -> Vec<T> {
+    let mut buffer = BytesMut::from(data);
+    let mut frames = Vec::new();
+    while let Ok(Some(frame)) = decode(&mut buffer) {
+        assert!(buffer.len() < data.len(), "{frame:?} decoded without consuming bytes");
+        frames.push(frame);
+    }
+    frames
+}
+
+fn encode_and_decode_idle(frame: &SerialFrame) -> SerialFrame {
+    let mut buffer = BytesMut::new();
+    RtuCodec::requests().encode(frame, &mut buffer).unwrap();
+    RtuCodec::requests().decode_idle(&mut buffer).unwrap().unwrap()
+}
+
+cfg_tokio! {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use crate::{
+        memory_store::MemoryStore,
+        modbus_encapsulated_interface::DeviceIdentification,
+        request_context::RequestContext,
+        server::ModbusTCPServer,
+        server_handle::ConnectionId,
+    };
+
+    /// The unit with data in [`memory_store`].
+    pub const UNIT_ID: u8 = 1;
+
+    /// A handler for [`handle_request`], with the whole address space of [`UNIT_ID`] and a device identification.
+    pub fn memory_store() -> Arc<MemoryStore> {
+        let device_identification = DeviceIdentification {
+            vendor_name: "Vendor".into(),
+            product_code: "Product".into(),
+            major_minor_revision: "1.0".into(),
+            vendor_url: None,
+            product_name: None,
+            model_name: None,
+            user_application_name: None,
+            objects: BTreeMap::from([(0x80, vec![0xAB; 200].into()), (0x81, vec![0xCD; 200].into())]),
+        };
+        Arc::new(MemoryStore::new().with_unit(UNIT_ID).with_device_identification(device_identification))
+    }
+
+    /// Handles a request PDU as the server does after reading a frame, checking that the response fits in a frame.
+    pub async fn handle_request(handler: &Arc<MemoryStore>, unit_id: u8, pdu: &[u8]) {
+        let Ok(frame) = Frame::new(0, unit_id, pdu) else { return };
+        let ctx = RequestContext {
+            addr: ([127, 0, 0, 1], 502).into(),
+            connection_id: ConnectionId(0),
+            transaction_id: frame.transaction_id,
+            unit_id,
+            function_code: frame.function_code,
+            extensions: Arc::default(),
+        };
+        if let Ok(body) = ModbusTCPServer::handle_request(&frame, &ctx, handler).await {
+            let response = Frame { body: body.into(), ..frame };
+            response.encode().expect("Response doesn't fit in a frame");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use proptest::{collection, prelude::*};
+
+    use super::*;
+
+    /// Function codes the server handles, followed by an address and a length for the data to make sense more often.
+    fn pdu() -> impl Strategy<Value = Vec<u8>> {
+        let function_code = prop_oneof![
+            prop::sample::select(vec![1u8, 2, 3, 4, 5, 6, 15, 16, 22, 43]),
+            any::<u8>(),
+        ];
+        let length = prop_oneof![0..=4u16, 0..=2000u16, any::<u16>()];
+        (function_code, any::<u16>(), length, collection::vec(any::<u8>(), 0..250)).prop_map(|(function_code, address, length, data)| {
+            let mut pdu = vec![function_code];
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(&length.to_be_bytes());
+            pdu.extend_from_slice(&data);
+            pdu
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn messages(data in collection::vec(any::<u8>(), 0..260)) {
+            decode_messages(&data);
+        }
+
+        #[test]
+        fn pdus(pdu in prop_oneof![pdu(), collection::vec(any::<u8>(), 0..260)]) {
+            decode_pdus(&pdu);
+            decode_messages(&pdu[1.min(pdu.len())..]);
+        }
+
+        #[test]
+        fn frames(data in collection::vec(any::<u8>(), 0..600)) {
+            decode_frames(&data);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    proptest! {
+        #[test]
+        fn requests(unit_id in prop_oneof![Just(UNIT_ID), any::<u8>()], pdu in pdu()) {
+            thread_local! {
+                static HANDLER: Arc<MemoryStore> = memory_store();
+            }
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            HANDLER.with(|handler| runtime.block_on(handle_request(handler, unit_id, &pdu)));
+        }
+    }
+}
@@ -1,15 +1,89 @@
-mod client;
-mod connection;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+/// Items that need the Tokio runtime, and with it `std`.
+macro_rules! cfg_tokio {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "tokio")]
+            $item
+        )*
+    };
+}
+
+mod ascii;
+mod bit_buf;
 pub mod consts;
 mod encoding;
 mod function_code;
+#[cfg(any(test, fuzzing))]
+#[doc(hidden)]
+pub mod fuzzing;
 mod message;
 mod messages;
 mod modbus_encapsulated_interface;
 mod modbus_exception;
-mod server;
+mod pdu;
+mod rtu;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod table;
+mod views;
 
-pub use client::{ModbusError, ModbusTCPClient};
+pub use ascii::AsciiCodec;
+pub use bit_buf::BitBuf;
+pub use encoding::{DecodeError, EncodeError};
+pub use function_code::FunctionCode;
+pub use message::{Frame, FrameError, MbapCodec};
 pub use modbus_encapsulated_interface::DeviceIdentification;
 pub use modbus_exception::ModbusException;
-pub use server::{ModbusTCPServer, ModbusTCPServerHandler};
+pub use pdu::{PduError, Request, Response};
+pub use rtu::{RtuCodec, SerialFrame};
+pub use table::Table;
+pub use views::{BitIter, Bits, RegisterIter, Registers};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+cfg_tokio! {
+    mod access_policy;
+    mod client;
+    mod client_ops;
+    mod codec;
+    mod connection;
+    mod dyn_handler;
+    mod memory_store;
+    mod middleware;
+    mod mock_client;
+    mod modbus_client;
+    mod modbus_device;
+    mod proxy;
+    mod rate_limit;
+    mod request_context;
+    mod rtu_client;
+    mod rtu_gateway;
+    mod server;
+    mod server_handle;
+    mod subscription;
+    mod telemetry;
+    mod unit_router;
+
+    pub use access_policy::{Access, AccessPolicy, AccessPolicyError, AccessRule, Cidr};
+    pub use client::{ModbusError, ModbusTCPClient};
+    pub use codec::CodecError;
+    pub use memory_store::{MemoryStore, MemoryStoreChange};
+    pub use middleware::{Layered, LoggingLayer, Middleware, Next, ReadOnlyLayer};
+    pub use mock_client::{MockCall, MockClient, MockResponse};
+    pub use modbus_client::ModbusClient;
+    pub use modbus_device::ModbusDevice;
+    pub use proxy::ModbusProxy;
+    pub use rate_limit::{Overload, RateLimit};
+    pub use request_context::{Extensions, RequestContext};
+    pub use rtu_client::ModbusRTUClient;
+    pub use rtu_gateway::RtuGateway;
+    pub use server::{DisconnectReason, ModbusTCPServer, ModbusTCPServerHandler};
+    pub use server_handle::{ConnectionId, ConnectionInfo, ModbusTCPServerHandle};
+    pub use subscription::{Subscription, SubscriptionValues};
+    pub use unit_router::UnitRouter;
+}
@@ -0,0 +1,265 @@
+use std::{borrow::Cow, collections::HashMap, ops::Range, sync::RwLock};
+
+use tokio::sync::broadcast;
+
+use crate::{
+    bit_buf::BitBuf, modbus_encapsulated_interface::DeviceIdentification, modbus_exception::ModbusException, request_context::RequestContext,
+    server::ModbusTCPServerHandler, server_handle::ConnectionId, views::Bits,
+};
+
+/// Capacity of the change notification channel. Receivers that fall further behind miss changes.
+const CHANGES_CAPACITY: usize = 256;
+
+/// A write made by a client to a [`MemoryStore`].
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryStoreChange {
+    Coils {
+        connection_id: ConnectionId,
+        unit_id: u8,
+        address: u16,
+        values: BitBuf,
+    },
+    HoldingRegisters {
+        connection_id: ConnectionId,
+        unit_id: u8,
+        address: u16,
+        values: Vec<u16>,
+    },
+}
+
+/**
+ * A server handler that keeps the data of each unit in memory.
+ *
+ * Each unit has a bank of coils, discrete inputs, input registers and holding registers covering a configurable address range.
+ * Requests outside the configured ranges, or to units that aren't configured, are answered with [`ModbusException::IllegalDataAddress`].
+ *
+ * The host application reads and updates the data with the methods on the store,
+ * and is notified of writes made by clients through [`MemoryStore::subscribe`].
+ */
+pub struct MemoryStore {
+    units: HashMap<u8, Unit>,
+    device_identification: Option<DeviceIdentification<'static>>,
+    changes: broadcast::Sender<MemoryStoreChange>,
+}
+
+#[derive(Default)]
+struct Unit {
+    coils: RwLock<Bank<bool>>,
+    discrete_inputs: RwLock<Bank<bool>>,
+    input_registers: RwLock<Bank<u16>>,
+    holding_registers: RwLock<Bank<u16>>,
+}
+
+struct Bank<T> {
+    address: u16,
+    values: Vec<T>,
+}
+
+impl<T> Default for Bank<T> {
+    fn default() -> Self {
+        Self {
+            address: 0,
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T: Copy + Default> Bank<T> {
+    fn new(address: u16, length: usize) -> Self {
+        assert!(address as usize + length <= 0x10000, "Address + length exceeds the address space");
+        Self {
+            address,
+            values: vec![T::default(); length],
+        }
+    }
+
+    fn range(&self, address: u16, length: usize) -> Result<Range<usize>, ModbusException> {
+        let start = (address as usize).checked_sub(self.address as usize).ok_or(ModbusException::IllegalDataAddress)?;
+        if start + length > self.values.len() {
+            return Err(ModbusException::IllegalDataAddress);
+        }
+        Ok(start..start + length)
+    }
+
+    fn read<V: FromIterator<T>>(&self, address: u16, length: usize) -> Result<V, ModbusException> {
+        Ok(self.values[self.range(address, length)?].iter().copied().collect())
+    }
+
+    fn write(&mut self, address: u16, values: impl ExactSizeIterator<Item = T>) -> Result<(), ModbusException> {
+        let range = self.range(address, values.len())?;
+        for (slot, value) in self.values[range].iter_mut().zip(values) {
+            *slot = value;
+        }
+        Ok(())
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    /// Creates a store without any units.
+    pub fn new() -> Self {
+        Self {
+            units: HashMap::new(),
+            device_identification: None,
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+        }
+    }
+
+    /// Adds `length` coils starting at `address` to the unit, all off.
+    /// Panics if the range exceeds the address space.
+    pub fn with_coils(mut self, unit_id: u8, address: u16, length: usize) -> Self {
+        self.units.entry(unit_id).or_default().coils = Bank::new(address, length).into();
+        self
+    }
+
+    /// Adds `length` discrete inputs starting at `address` to the unit, all off.
+    /// Panics if the range exceeds the address space.
+    pub fn with_discrete_inputs(mut self, unit_id: u8, address: u16, length: usize) -> Self {
+        self.units.entry(unit_id).or_default().discrete_inputs = Bank::new(address, length).into();
+        self
+    }
+
+    /// Adds `length` input registers starting at `address` to the unit, all zero.
+    /// Panics if the range exceeds the address space.
+    pub fn with_input_registers(mut self, unit_id: u8, address: u16, length: usize) -> Self {
+        self.units.entry(unit_id).or_default().input_registers = Bank::new(address, length).into();
+        self
+    }
+
+    /// Adds `length` holding registers starting at `address` to the unit, all zero.
+    /// Panics if the range exceeds the address space.
+    pub fn with_holding_registers(mut self, unit_id: u8, address: u16, length: usize) -> Self {
+        self.units.entry(unit_id).or_default().holding_registers = Bank::new(address, length).into();
+        self
+    }
+
+    /// Adds the whole address space of all four banks to the unit.
+    pub fn with_unit(self, unit_id: u8) -> Self {
+        self.with_coils(unit_id, 0, 0x10000)
+            .with_discrete_inputs(unit_id, 0, 0x10000)
+            .with_input_registers(unit_id, 0, 0x10000)
+            .with_holding_registers(unit_id, 0, 0x10000)
+    }
+
+    /// Responds to Read Device Identification requests for all units.
+    pub fn with_device_identification(mut self, device_identification: DeviceIdentification<'static>) -> Self {
+        self.device_identification = Some(device_identification);
+        self
+    }
+
+    /// Receives the writes made by clients. Writes made through the methods on the store aren't included.
+    pub fn subscribe(&self) -> broadcast::Receiver<MemoryStoreChange> {
+        self.changes.subscribe()
+    }
+
+    pub fn coils(&self, unit_id: u8, address: u16, length: usize) -> Result<BitBuf, ModbusException> {
+        self.unit(unit_id)?.coils.read().unwrap().read(address, length)
+    }
+
+    pub fn discrete_inputs(&self, unit_id: u8, address: u16, length: usize) -> Result<BitBuf, ModbusException> {
+        self.unit(unit_id)?.discrete_inputs.read().unwrap().read(address, length)
+    }
+
+    pub fn input_registers(&self, unit_id: u8, address: u16, length: usize) -> Result<Vec<u16>, ModbusException> {
+        self.unit(unit_id)?.input_registers.read().unwrap().read(address, length)
+    }
+
+    pub fn holding_registers(&self, unit_id: u8, address: u16, length: usize) -> Result<Vec<u16>, ModbusException> {
+        self.unit(unit_id)?.holding_registers.read().unwrap().read(address, length)
+    }
+
+    pub fn set_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusException> {
+        self.unit(unit_id)?.coils.write().unwrap().write(address, values.into().iter())
+    }
+
+    pub fn set_discrete_inputs<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusException> {
+        self.unit(unit_id)?.discrete_inputs.write().unwrap().write(address, values.into().iter())
+    }
+
+    pub fn set_input_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        self.unit(unit_id)?.input_registers.write().unwrap().write(address, values.iter().copied())
+    }
+
+    pub fn set_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        self.unit(unit_id)?.holding_registers.write().unwrap().write(address, values.iter().copied())
+    }
+
+    fn unit(&self, unit_id: u8) -> Result<&Unit, ModbusException> {
+        self.units.get(&unit_id).ok_or(ModbusException::IllegalDataAddress)
+    }
+
+    /// Called with the bank locked, so changes are received in the order they were applied.
+    fn notify(&self, change: MemoryStoreChange) {
+        // Fails only when there are no receivers.
+        _ = self.changes.send(change);
+    }
+}
+
+impl ModbusTCPServerHandler for MemoryStore {
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        Ok(self.coils(ctx.unit_id, address, length as usize)?.into())
+    }
+
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        Ok(self.discrete_inputs(ctx.unit_id, address, length as usize)?.into())
+    }
+
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        Ok(self.input_registers(ctx.unit_id, address, length as usize)?.into())
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        Ok(self.holding_registers(ctx.unit_id, address, length as usize)?.into())
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
+        let mut bank = self.unit(ctx.unit_id)?.coils.write().unwrap();
+        bank.write(address, values.iter())?;
+        self.notify(MemoryStoreChange::Coils {
+            connection_id: ctx.connection_id,
+            unit_id: ctx.unit_id,
+            address,
+            values: values.into(),
+        });
+        Ok(())
+    }
+
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        let mut bank = self.unit(ctx.unit_id)?.holding_registers.write().unwrap();
+        bank.write(address, values.iter().copied())?;
+        self.notify(MemoryStoreChange::HoldingRegisters {
+            connection_id: ctx.connection_id,
+            unit_id: ctx.unit_id,
+            address,
+            values: values.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        let mut bank = self.unit(ctx.unit_id)?.holding_registers.write().unwrap();
+        let index = bank.range(address, 1)?.start;
+        let value = (bank.values[index] & and_mask) | (or_mask & !and_mask);
+        bank.values[index] = value;
+        self.notify(MemoryStoreChange::HoldingRegisters {
+            connection_id: ctx.connection_id,
+            unit_id: ctx.unit_id,
+            address,
+            values: vec![value],
+        });
+        Ok(())
+    }
+
+    async fn handle_read_device_identification(&self, _ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        match &self.device_identification {
+            Some(device_identification) => Ok(Cow::Borrowed(device_identification)),
+            None => Err(ModbusException::IllegalFunction),
+        }
+    }
+}
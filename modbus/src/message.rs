@@ -1,94 +1,219 @@
-use tokio::io::{AsyncRead, AsyncReadExt};
+//! Framing of Modbus TCP messages: the MBAP header followed by the unit id, function code and body.
 
-use crate::connection::ReadError;
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use thiserror::Error;
 use crate::function_code::FunctionCode;
 
-use super::encoding::*;
-
 pub const MSG_MAX_LENGTH: usize = 260;
 
-#[derive(PartialEq, Debug)]
-pub struct Message {
+/// The MBAP header, the unit id and the function code.
+const HEADER_LENGTH: usize = 8;
+/// The length field counts the unit id, the function code and the body.
+const MIN_LENGTH: u16 = 2;
+const MAX_LENGTH: u16 = MSG_MAX_LENGTH as u16 - 6;
+
+/// Bytes that can't be decoded as a frame.
+#[derive(Error, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FrameError {
+    #[error("Invalid protocol id: {0}")]
+    InvalidProtocolId(u16),
+    #[error("Length too short: {0}")]
+    LengthTooShort(u16),
+    #[error("Length too long: {0}")]
+    LengthTooLong(u16),
+    #[error("CRC mismatch")]
+    CrcMismatch,
+    #[error("LRC mismatch")]
+    LrcMismatch,
+    #[error("Invalid hex encoding")]
+    InvalidHex,
+    #[error("Incomplete frame")]
+    Incomplete,
+}
+
+/// A Modbus TCP frame: the MBAP header and a PDU, split into function code and body.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Frame {
     pub transaction_id: u16,
     pub protocol_id: u16,
     pub unit_id: u8,
     pub function_code: FunctionCode,
-    pub body: Vec<u8>,
+    pub body: Bytes,
 }
 
-impl Encodable for Message {
-    fn encode(&self, encoder: &mut Encoder) -> EncodeResult {
-        encoder.write_u16(self.transaction_id);
-        encoder.write_u16(self.protocol_id);
-        encoder.write_u16((self.body.len() + 2).try_into()?);
-        encoder.write_u8(self.unit_id);
-        encoder.write_u8(self.function_code.into());
-        encoder.write_bytes(&self.body);
-        Ok(())
+impl Frame {
+    /// A frame carrying a PDU, as encoded by [`crate::Request::encode`] and [`crate::Response::encode`].
+    pub fn new(transaction_id: u16, unit_id: u8, pdu: &[u8]) -> Result<Self, FrameError> {
+        let Some((&function_code, body)) = pdu.split_first() else {
+            return Err(FrameError::LengthTooShort(1));
+        };
+        Ok(Self {
+            transaction_id,
+            protocol_id: 0,
+            unit_id,
+            function_code: function_code.into(),
+            body: Bytes::copy_from_slice(body),
+        })
     }
-}
 
-impl Message {
-    pub async fn read<R>(reader: &mut R) -> Result<Message, ReadError> 
-    where
-        R: AsyncRead + Unpin + ?Sized
-    {
-        let mut header = [0; 8];
+    /// The PDU, to be decoded with [`crate::Request::decode`] or [`crate::Response::decode`].
+    pub fn pdu(&self) -> Vec<u8> {
+        let mut pdu = Vec::with_capacity(self.body.len() + 1);
+        pdu.push(self.function_code.into());
+        pdu.extend_from_slice(&self.body);
+        pdu
+    }
 
-        reader.read_exact(&mut header).await?;
+    pub fn encode(&self) -> Result<Vec<u8>, FrameError> {
+        let mut buffer = BytesMut::new();
+        MbapCodec::new().encode(self, &mut buffer)?;
+        Ok(buffer.into())
+    }
 
-        let mut decoder = Decoder::new(&header);
+    /// Decodes the frame at the start of `bytes`, returning it with the bytes after it, e.g. the next frame of a TCP segment.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), FrameError> {
+        let mut buffer = BytesMut::from(bytes);
+        let frame = MbapCodec::new().decode(&mut buffer)?.ok_or(FrameError::Incomplete)?;
+        Ok((frame, &bytes[bytes.len() - buffer.len()..]))
+    }
+}
 
-        let transaction_id = decoder.read_u16()?;
-        let protocol_id = decoder.read_u16()?;
-        let byte_length = decoder.read_u16()?;
+/// Returns the length field of the header.
+fn validate_header(header: &[u8]) -> Result<u16, FrameError> {
+    let protocol_id = u16::from_be_bytes([header[2], header[3]]);
+    let byte_length = u16::from_be_bytes([header[4], header[5]]);
+    if protocol_id != 0 {
+        return Err(FrameError::InvalidProtocolId(protocol_id));
+    }
+    if byte_length < MIN_LENGTH {
+        return Err(FrameError::LengthTooShort(byte_length));
+    }
+    if byte_length > MAX_LENGTH {
+        return Err(FrameError::LengthTooLong(byte_length));
+    }
+    Ok(byte_length)
+}
 
-        if byte_length as usize > MSG_MAX_LENGTH - 6 {
-            return Err(DecodeError::InvalidData("Byte length to large").into());
-        }
+/// Sans-IO encoder and decoder of [`Frame`]s on a byte stream.
+#[derive(Debug, Clone, Default)]
+pub struct MbapCodec {
+    resync: bool,
+    skipped: usize,
+}
 
-        let unit_id = decoder.read_u8()?;
-        let function_code = decoder.read_u8()?.into();
+impl MbapCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let mut body = vec![0; (byte_length - 2).into()];
-        reader.read_exact(&mut body).await?;
+    /// After an invalid header, skip bytes until a valid header is found instead of failing,
+    /// giving up after skipping the length of the longest message. Default is false.
+    pub fn with_resync(mut self, resync: bool) -> Self {
+        self.resync = resync;
+        self
+    }
 
-        Ok(Message {
-            transaction_id,
-            protocol_id,
-            unit_id,
-            function_code,
-            body,
-        })
+    /// Decodes a message from the start of `buffer`, removing its bytes. Returns `None` if more bytes are needed.
+    pub fn decode(&mut self, buffer: &mut BytesMut) -> Result<Option<Frame>, FrameError> {
+        while buffer.len() >= HEADER_LENGTH {
+            let byte_length = match validate_header(buffer) {
+                Ok(byte_length) => byte_length,
+                Err(_) if self.resync && self.skipped < MSG_MAX_LENGTH => {
+                    buffer.advance(1);
+                    self.skipped += 1;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            let length = 6 + byte_length as usize;
+            if buffer.len() < length {
+                buffer.reserve(length - buffer.len());
+                return Ok(None);
+            }
+            self.skipped = 0;
+            let mut body = buffer.split_to(length);
+            let header = body.split_to(HEADER_LENGTH);
+            return Ok(Some(Frame {
+                transaction_id: u16::from_be_bytes([header[0], header[1]]),
+                protocol_id: u16::from_be_bytes([header[2], header[3]]),
+                unit_id: header[6],
+                function_code: header[7].into(),
+                body: body.freeze(),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Appends the encoded message to `buffer`.
+    pub fn encode(&self, message: &Frame, buffer: &mut BytesMut) -> Result<(), FrameError> {
+        let byte_length = message.body.len() + MIN_LENGTH as usize;
+        if byte_length > MAX_LENGTH as usize {
+            return Err(FrameError::LengthTooLong(byte_length.min(u16::MAX.into()) as u16));
+        }
+        buffer.reserve(6 + byte_length);
+        buffer.put_u16(message.transaction_id);
+        buffer.put_u16(message.protocol_id);
+        buffer.put_u16(byte_length as u16);
+        buffer.put_u8(message.unit_id);
+        buffer.put_u8(message.function_code.into());
+        buffer.put_slice(&message.body);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
-
     use crate::function_code::FunctionCode;
 
     use super::*;
 
-    #[tokio::test]
-    async fn encode_decode() {
-        let msg = Message {
+    #[test]
+    fn codec() {
+        let msg = Frame {
             transaction_id: 1,
-            protocol_id: 2,
+            protocol_id: 0,
             unit_id: 3,
             function_code: FunctionCode::ReadInputRegisters,
-            body: vec![5, 6, 7],
+            body: Bytes::from_static(&[5, 6, 7]),
         };
 
-        let bytes = msg.encode_to_bytes().unwrap();
-
-        assert_eq!(bytes.len(), 11);
-
-        let mut cur = Cursor::new(bytes);
-        
-        let decoded_msg = Message::read(&mut cur).await.unwrap();
+        let mut codec = MbapCodec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(&msg, &mut buffer).unwrap();
+        assert_eq!(&buffer[..], [0, 1, 0, 0, 0, 5, 3, 4, 5, 6, 7]);
+
+        let mut partial = buffer.split_to(5);
+        assert_eq!(codec.decode(&mut partial), Ok(None));
+        partial.unsplit(buffer);
+        assert_eq!(codec.decode(&mut partial), Ok(Some(msg.clone())));
+        assert!(partial.is_empty());
+
+        // Garbage in front of a message.
+        let mut buffer = BytesMut::from(&[0xFF, 0x00, 0x12, 0x34, 0x00][..]);
+        codec.encode(&msg, &mut buffer).unwrap();
+        assert_eq!(codec.clone().decode(&mut buffer.clone()), Err(FrameError::InvalidProtocolId(0x1234)));
+        let mut codec = MbapCodec::new().with_resync(true);
+        assert_eq!(codec.decode(&mut buffer), Ok(Some(msg)));
+
+        // Gives up eventually.
+        let mut buffer = BytesMut::from(&[0xFF; MSG_MAX_LENGTH * 2][..]);
+        assert_eq!(codec.decode(&mut buffer), Err(FrameError::InvalidProtocolId(0xFFFF)));
+    }
 
-        assert_eq!(msg, decoded_msg);
+    #[test]
+    fn frame() {
+        let frame = Frame::new(1, 3, &[4, 5, 6, 7]).unwrap();
+        assert_eq!(frame.function_code, FunctionCode::ReadInputRegisters);
+        assert_eq!(frame.pdu(), [4, 5, 6, 7]);
+
+        // Two frames in a row.
+        let mut bytes = frame.encode().unwrap();
+        bytes.extend(frame.encode().unwrap());
+        let (decoded, rest) = Frame::decode(&bytes).unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(Frame::decode(rest), Ok((frame, &[][..])));
+        assert_eq!(Frame::decode(&rest[..10]), Err(FrameError::Incomplete));
+        assert_eq!(Frame::new(1, 3, &[]), Err(FrameError::LengthTooShort(1)));
     }
 }
@@ -19,8 +19,8 @@ impl Encodable for ExceptionMessage {
     }
 }
 
-impl Decodable<Self> for ExceptionMessage {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for ExceptionMessage {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             code: decoder.read_u8()?.into(),
         })
@@ -16,8 +16,8 @@ impl Encodable for MaskWriteHoldingRegisterRequest {
     }
 }
 
-impl Decodable<Self> for MaskWriteHoldingRegisterRequest {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for MaskWriteHoldingRegisterRequest {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
             and_mask: decoder.read_u16()?,
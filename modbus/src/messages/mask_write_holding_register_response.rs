@@ -16,8 +16,8 @@ impl Encodable for MaskWriteHoldingRegisterResponse {
     }
 }
 
-impl Decodable<Self> for MaskWriteHoldingRegisterResponse {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for MaskWriteHoldingRegisterResponse {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
             and_mask: decoder.read_u16()?,
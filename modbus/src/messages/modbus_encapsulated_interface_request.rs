@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 use crate::{encoding::*, modbus_encapsulated_interface::ModbusEncapsulatedInterfaceType};
 
@@ -16,8 +16,8 @@ impl<'a> Encodable for ModbusEncapsulatedInterfaceRequest<'a> {
     }
 }
 
-impl<'a> Decodable<Self> for ModbusEncapsulatedInterfaceRequest<'a> {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl<'a> Decodable<'a> for ModbusEncapsulatedInterfaceRequest<'a> {
+    fn decode(decoder: &mut Decoder<'a>) -> DecodeResult<Self> {
         let kind = decoder.read_u8()?.into();
         let data = decoder.read_bytes(decoder.remaining())?.into();
 
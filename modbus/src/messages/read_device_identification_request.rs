@@ -14,8 +14,8 @@ impl Encodable for ReadDeviceIdentificationRequest {
     }
 }
 
-impl Decodable<Self> for ReadDeviceIdentificationRequest {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for ReadDeviceIdentificationRequest {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             device_id_code: decoder.read_u8()?.into(),
             object_id: decoder.read_u8()?,
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, collections::HashMap};
+use alloc::{borrow::Cow, collections::BTreeMap};
 
 use crate::{encoding::*, modbus_encapsulated_interface::*};
 
@@ -8,7 +8,7 @@ pub struct ReadDeviceIdentificationResponse<'a> {
     pub conformity_level: ReadDeviceIdentificationConformityLevel,
     pub more_follows: bool,
     pub next_object_id: u8,
-    pub objects: HashMap<u8, Cow<'a, [u8]>>,
+    pub objects: BTreeMap<u8, Cow<'a, [u8]>>,
 }
 
 impl<'a> Encodable for ReadDeviceIdentificationResponse<'a> {
@@ -27,14 +27,18 @@ impl<'a> Encodable for ReadDeviceIdentificationResponse<'a> {
     }
 }
 
-impl<'a> Decodable<Self> for ReadDeviceIdentificationResponse<'a> {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl<'a> Decodable<'a> for ReadDeviceIdentificationResponse<'a> {
+    fn decode(decoder: &mut Decoder<'a>) -> DecodeResult<Self> {
         let device_id_code = decoder.read_u8()?.into();
         let conformity_level = decoder.read_u8()?.into();
-        let more_follows = decoder.read_u8()? != 0;
+        let more_follows = match decoder.read_u8()? {
+            0x00 => false,
+            0xFF => true,
+            _ => return Err(DecodeError::InvalidData("Invalid more follows value")),
+        };
         let next_object_id = decoder.read_u8()?;
         let length = decoder.read_u8()?;
-        let mut objects = HashMap::with_capacity(length.into());
+        let mut objects = BTreeMap::new();
         for _ in 0..length {
             let id = decoder.read_u8()?;
             let length = decoder.read_u8()?;
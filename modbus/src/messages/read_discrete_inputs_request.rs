@@ -14,8 +14,8 @@ impl Encodable for ReadDiscreteInputsRequest {
     }
 }
 
-impl Decodable<Self> for ReadDiscreteInputsRequest {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for ReadDiscreteInputsRequest {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
             length: decoder.read_u16()?,
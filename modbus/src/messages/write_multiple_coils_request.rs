@@ -1,11 +1,9 @@
-use std::borrow::Cow;
-
-use crate::encoding::*;
+use crate::{encoding::*, views::Bits};
 
 #[derive(PartialEq, Debug)]
 pub struct WriteMultipleCoilsRequest<'a> {
     pub address: u16,
-    pub values: Cow<'a, [bool]>,
+    pub values: Bits<'a>,
 }
 
 impl<'a> Encodable for WriteMultipleCoilsRequest<'a> {
@@ -15,13 +13,13 @@ impl<'a> Encodable for WriteMultipleCoilsRequest<'a> {
         encoder.write_u16(self.address);
         encoder.write_u16(length);
         encoder.write_u8(byte_length);
-        encoder.write_bools(&self.values);
+        encoder.write_bits(self.values);
         Ok(())
     }
 }
 
-impl<'a> Decodable<Self> for WriteMultipleCoilsRequest<'a> {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl<'a> Decodable<'a> for WriteMultipleCoilsRequest<'a> {
+    fn decode(decoder: &mut Decoder<'a>) -> DecodeResult<Self> {
         let address = decoder.read_u16()?;
         let length = decoder.read_u16()?;
         let byte_length = decoder.read_u8()?;
@@ -32,7 +30,7 @@ impl<'a> Decodable<Self> for WriteMultipleCoilsRequest<'a> {
 
         Ok(Self {
             address,
-            values: decoder.read_bools(length as usize)?.into(),
+            values: decoder.read_bits(length as usize)?,
         })
     }
 }
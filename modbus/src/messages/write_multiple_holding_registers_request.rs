@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 use crate::encoding::*;
 
@@ -13,13 +13,13 @@ impl<'a> Encodable for WriteMultipleHoldingRegistersRequest<'a> {
         encoder.write_u16(self.address);
         encoder.write_u16(self.values.len().try_into()?);
         encoder.write_u8((self.values.len() * 2).try_into()?);
-        encoder.write_registers(&self.values);
+        encoder.write_registers((&*self.values).into());
         Ok(())
     }
 }
 
-impl<'a> Decodable<Self> for WriteMultipleHoldingRegistersRequest<'a> {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl<'a> Decodable<'a> for WriteMultipleHoldingRegistersRequest<'a> {
+    fn decode(decoder: &mut Decoder<'a>) -> DecodeResult<Self> {
         let address = decoder.read_u16()?;
         let length = decoder.read_u16()?;
         let byte_length = decoder.read_u8()?;
@@ -28,7 +28,7 @@ impl<'a> Decodable<Self> for WriteMultipleHoldingRegistersRequest<'a> {
         }
         Ok(Self {
             address,
-            values: decoder.read_registers(length as usize)?.into(),
+            values: decoder.read_registers(length as usize)?.to_vec().into(),
         })
     }
 }
@@ -14,8 +14,8 @@ impl Encodable for WriteMultipleHoldingRegistersResponse {
     }
 }
 
-impl Decodable<Self> for WriteMultipleHoldingRegistersResponse {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for WriteMultipleHoldingRegistersResponse {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
             length: decoder.read_u16()?,
@@ -9,16 +9,16 @@ pub struct WriteSingleCoilResponse {
 impl Encodable for WriteSingleCoilResponse {
     fn encode(&self, encoder: &mut Encoder) -> EncodeResult {
         encoder.write_u16(self.address);
-        encoder.write_u16(if self.value { 0xFF00 } else { 0 });
+        encoder.write_coil(self.value);
         Ok(())
     }
 }
 
-impl Decodable<Self> for WriteSingleCoilResponse {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for WriteSingleCoilResponse {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
-            value: decoder.read_u16()? != 0,
+            value: decoder.read_coil()?,
         })
     }
 }
@@ -14,8 +14,8 @@ impl Encodable for WriteSingleHoldingRegisterResponse {
     }
 }
 
-impl Decodable<Self> for WriteSingleHoldingRegisterResponse {
-    fn decode(decoder: &mut Decoder) -> DecodeResult<Self> {
+impl Decodable<'_> for WriteSingleHoldingRegisterResponse {
+    fn decode(decoder: &mut Decoder<'_>) -> DecodeResult<Self> {
         Ok(Self {
             address: decoder.read_u16()?,
             value: decoder.read_u16()?,
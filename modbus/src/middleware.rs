@@ -0,0 +1,290 @@
+use std::{borrow::Cow, future::Future, net::SocketAddr, sync::Arc, time::Duration};
+
+use crate::{
+    bit_buf::BitBuf,
+    modbus_encapsulated_interface::DeviceIdentification,
+    modbus_exception::ModbusException,
+    pdu::{Request, Response},
+    request_context::{Extensions, RequestContext},
+    server::{DisconnectReason, ModbusTCPServerHandler},
+    views::Bits,
+};
+
+/**
+ * Cross-cutting request processing, applied to a handler with [`Layered`].
+ *
+ * [`Self::call`] sees every request after it has been decoded and validated by the server.
+ * It can inspect or modify the request before passing it on with [`Next::run`], short-circuit with an exception,
+ * and observe or modify the response. The response must be of the same kind as the request.
+ */
+pub trait Middleware: Send + Sync + 'static {
+    /// Whether to accept a new connection, asked before the handler is. Default is to always accept.
+    #[allow(unused_variables)]
+    fn accept_connection(&self, addr: SocketAddr, extensions: &mut Extensions) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
+    #[allow(unused_variables)]
+    fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+    /// Processes a request. Default is to pass it on unchanged.
+    fn call<H: ModbusTCPServerHandler>(
+        &self,
+        ctx: &RequestContext,
+        request: Request,
+        next: Next<'_, H>,
+    ) -> impl Future<Output = Result<Response, ModbusException>> + Send {
+        next.run(ctx, request)
+    }
+}
+
+/// The rest of the stack, i.e. the inner layers and the handler.
+pub struct Next<'a, H> {
+    handler: &'a H,
+}
+
+impl<H: ModbusTCPServerHandler> Next<'_, H> {
+    pub async fn run(self, ctx: &RequestContext, request: Request) -> Result<Response, ModbusException> {
+        let handler = self.handler;
+        let response = match request {
+            Request::ReadCoils { address, length } => Response::ReadCoils(handler.handle_read_coils(ctx, address, length).await?.into_owned()),
+            Request::ReadDiscreteInputs { address, length } => {
+                Response::ReadDiscreteInputs(handler.handle_read_discrete_inputs(ctx, address, length).await?.into_owned())
+            }
+            Request::ReadInputRegisters { address, length } => {
+                Response::ReadInputRegisters(handler.handle_read_input_registers(ctx, address, length).await?.into_owned())
+            }
+            Request::ReadHoldingRegisters { address, length } => {
+                Response::ReadHoldingRegisters(handler.handle_read_holding_registers(ctx, address, length).await?.into_owned())
+            }
+            Request::WriteSingleCoil { address, value } => {
+                handler.handle_write_single_coil(ctx, address, value).await?;
+                Response::WriteSingleCoil { address, value }
+            }
+            Request::WriteSingleHoldingRegister { address, value } => {
+                handler.handle_write_single_holding_register(ctx, address, value).await?;
+                Response::WriteSingleHoldingRegister { address, value }
+            }
+            Request::WriteMultipleCoils { address, values } => {
+                handler.handle_write_coils(ctx, address, values.as_bits()).await?;
+                Response::WriteMultipleCoils {
+                    address,
+                    length: values.len() as u16,
+                }
+            }
+            Request::WriteMultipleHoldingRegisters { address, values } => {
+                handler.handle_write_holding_registers(ctx, address, &values).await?;
+                Response::WriteMultipleHoldingRegisters {
+                    address,
+                    length: values.len() as u16,
+                }
+            }
+            Request::MaskWriteHoldingRegister { address, and_mask, or_mask } => {
+                handler.handle_mask_write_holding_register(ctx, address, and_mask, or_mask).await?;
+                Response::MaskWriteHoldingRegister { address, and_mask, or_mask }
+            }
+            Request::ReadDeviceIdentification => {
+                Response::ReadDeviceIdentification(handler.handle_read_device_identification(ctx).await?.into_owned().into_owned())
+            }
+            Request::ModbusEncapsulatedInterface { interface_type, data } => {
+                let data = handler.handle_modbus_encapsulated_interface(ctx, interface_type, &data).await?.into_owned();
+                Response::ModbusEncapsulatedInterface { interface_type, data }
+            }
+        };
+        Ok(response)
+    }
+}
+
+/**
+ * A handler with a middleware applied to it. Layers can be stacked, the outermost layer sees requests first.
+ *
+ * Connection settings such as [`ModbusTCPServerHandler::max_concurrent_connections`] are taken from the handler.
+ */
+pub struct Layered<M, H> {
+    middleware: M,
+    handler: H,
+}
+
+impl<M: Middleware, H: ModbusTCPServerHandler> Layered<M, H> {
+    pub fn new(handler: H, middleware: M) -> Self {
+        Self { middleware, handler }
+    }
+
+    /// Applies another middleware on top of this one.
+    pub fn layer<M2: Middleware>(self, middleware: M2) -> Layered<M2, Self> {
+        Layered::new(self, middleware)
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    async fn call(&self, ctx: &RequestContext, request: Request) -> Result<Response, ModbusException> {
+        self.middleware.call(ctx, request, Next { handler: &self.handler }).await
+    }
+}
+
+/// The middleware responded with another kind of response than requested.
+const MISMATCH: ModbusException = ModbusException::ServerDeviceFailure;
+
+impl<M: Middleware, H: ModbusTCPServerHandler> ModbusTCPServerHandler for Layered<M, H> {
+    async fn accept_connection(&self, addr: SocketAddr, extensions: &mut Extensions) -> bool {
+        self.middleware.accept_connection(addr, extensions).await && self.handler.accept_connection(addr, extensions).await
+    }
+
+    fn max_concurrent_connections(&self) -> usize {
+        self.handler.max_concurrent_connections()
+    }
+
+    fn max_concurrent_requests(&self) -> usize {
+        self.handler.max_concurrent_requests()
+    }
+
+    fn idle_timeout(&self) -> Option<Duration> {
+        self.handler.idle_timeout()
+    }
+
+    fn request_timeout(&self) -> Option<Duration> {
+        self.handler.request_timeout()
+    }
+
+    fn evict_idle_connection_when_full(&self) -> bool {
+        self.handler.evict_idle_connection_when_full()
+    }
+
+    fn resync_frames(&self) -> bool {
+        self.handler.resync_frames()
+    }
+
+    async fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) {
+        self.middleware.disconnected(addr, reason).await;
+        self.handler.disconnected(addr, reason).await;
+    }
+
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        match self.call(ctx, Request::ReadCoils { address, length }).await? {
+            Response::ReadCoils(values) => Ok(values.into()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        match self.call(ctx, Request::ReadDiscreteInputs { address, length }).await? {
+            Response::ReadDiscreteInputs(values) => Ok(values.into()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        match self.call(ctx, Request::ReadInputRegisters { address, length }).await? {
+            Response::ReadInputRegisters(values) => Ok(values.into()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        match self.call(ctx, Request::ReadHoldingRegisters { address, length }).await? {
+            Response::ReadHoldingRegisters(values) => Ok(values.into()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
+        let values = values.into();
+        match self.call(ctx, Request::WriteMultipleCoils { address, values }).await? {
+            Response::WriteMultipleCoils { .. } => Ok(()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        let values = values.to_vec();
+        match self.call(ctx, Request::WriteMultipleHoldingRegisters { address, values }).await? {
+            Response::WriteMultipleHoldingRegisters { .. } => Ok(()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> Result<(), ModbusException> {
+        match self.call(ctx, Request::WriteSingleCoil { address, value }).await? {
+            Response::WriteSingleCoil { .. } => Ok(()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_write_single_holding_register(&self, ctx: &RequestContext, address: u16, value: u16) -> Result<(), ModbusException> {
+        match self.call(ctx, Request::WriteSingleHoldingRegister { address, value }).await? {
+            Response::WriteSingleHoldingRegister { .. } => Ok(()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        match self.call(ctx, Request::MaskWriteHoldingRegister { address, and_mask, or_mask }).await? {
+            Response::MaskWriteHoldingRegister { .. } => Ok(()),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_read_device_identification(&self, ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        match self.call(ctx, Request::ReadDeviceIdentification).await? {
+            Response::ReadDeviceIdentification(device_identification) => Ok(Cow::Owned(device_identification)),
+            _ => Err(MISMATCH),
+        }
+    }
+
+    async fn handle_modbus_encapsulated_interface(&self, ctx: &RequestContext, interface_type: u8, data: &[u8]) -> Result<Cow<'_, [u8]>, ModbusException> {
+        let data = data.to_vec();
+        match self.call(ctx, Request::ModbusEncapsulatedInterface { interface_type, data }).await? {
+            Response::ModbusEncapsulatedInterface { data, .. } => Ok(data.into()),
+            _ => Err(MISMATCH),
+        }
+    }
+}
+
+/// Logs connections, and every request with its response or exception, as lines of text.
+#[derive(Clone)]
+pub struct LoggingLayer {
+    log: Arc<dyn Fn(&str) + Send + Sync>,
+}
+
+impl LoggingLayer {
+    /// Passes each line to `log`, e.g. `|line| println!("{line}")`.
+    pub fn new(log: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self { log: Arc::new(log) }
+    }
+}
+
+impl Middleware for LoggingLayer {
+    async fn accept_connection(&self, addr: SocketAddr, _extensions: &mut Extensions) -> bool {
+        (self.log)(&format!("[{}] Connected", addr));
+        true
+    }
+
+    async fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) {
+        (self.log)(&format!("[{}] Disconnected: {}", addr, reason));
+    }
+
+    async fn call<H: ModbusTCPServerHandler>(&self, ctx: &RequestContext, request: Request, next: Next<'_, H>) -> Result<Response, ModbusException> {
+        let line = format!("[{}] Unit {}: {:?}", ctx.addr, ctx.unit_id, request);
+        let result = next.run(ctx, request).await;
+        match &result {
+            Ok(response) => (self.log)(&format!("{} -> {:?}", line, response)),
+            Err(exception) => (self.log)(&format!("{} -> {:?}", line, exception)),
+        }
+        result
+    }
+}
+
+/// Rejects every write with [`ModbusException::IllegalFunction`].
+#[derive(Clone, Copy, Default)]
+pub struct ReadOnlyLayer;
+
+impl Middleware for ReadOnlyLayer {
+    async fn call<H: ModbusTCPServerHandler>(&self, ctx: &RequestContext, request: Request, next: Next<'_, H>) -> Result<Response, ModbusException> {
+        if request.is_write() {
+            return Err(ModbusException::IllegalFunction);
+        }
+        next.run(ctx, request).await
+    }
+}
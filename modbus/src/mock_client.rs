@@ -0,0 +1,416 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    bit_buf::BitBuf,
+    client::ModbusError,
+    client_ops::validate_input,
+    consts::*,
+    modbus_client::ModbusClient,
+    modbus_encapsulated_interface::DeviceIdentification,
+    modbus_exception::ModbusException,
+    views::Bits,
+};
+
+/// A call made to a [`MockClient`].
+#[derive(PartialEq, Debug, Clone)]
+pub enum MockCall {
+    ReadCoils { unit_id: u8, address: u16, length: u16 },
+    ReadDiscreteInputs { unit_id: u8, address: u16, length: u16 },
+    ReadInputRegisters { unit_id: u8, address: u16, length: u16 },
+    ReadHoldingRegisters { unit_id: u8, address: u16, length: u16 },
+    WriteSingleCoil { unit_id: u8, address: u16, value: bool },
+    WriteSingleHoldingRegister { unit_id: u8, address: u16, value: u16 },
+    WriteMultipleCoils { unit_id: u8, address: u16, values: BitBuf },
+    WriteMultipleHoldingRegisters { unit_id: u8, address: u16, values: Vec<u16> },
+    MaskWriteHoldingRegister { unit_id: u8, address: u16, and_mask: u16, or_mask: u16 },
+    ModbusEncapsulatedInterface { unit_id: u8, interface_type: u8, data: Vec<u8> },
+    ReadDeviceIdentification { unit_id: u8 },
+}
+
+/// A scripted response returned by a [`MockClient`].
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// Response to reading coils or discrete inputs.
+    Bits(BitBuf),
+    /// Response to reading input or holding registers.
+    Registers(Vec<u16>),
+    /// Response to a Modbus encapsulated interface request.
+    Bytes(Vec<u8>),
+    /// Response to reading the device identification.
+    DeviceIdentification(DeviceIdentification<'static>),
+    /// Response to a write.
+    Done,
+    /// Fails the request with the error.
+    Error(ModbusError),
+}
+
+/**
+ * An in-memory [`ModbusClient`] for tests.
+ *
+ * Requests are answered by the first scripted response if there is one.
+ * Otherwise reads and writes operate on in-memory data, where unset addresses read as zero.
+ * Every call is recorded and can be inspected with [`MockClient::calls`].
+ *
+ * Cloning the mock is cheap, all clones share the same state.
+ */
+#[derive(Clone, Default)]
+pub struct MockClient {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    coils: HashMap<(u8, u16), bool>,
+    discrete_inputs: HashMap<(u8, u16), bool>,
+    input_registers: HashMap<(u8, u16), u16>,
+    holding_registers: HashMap<(u8, u16), u16>,
+    device_identification: HashMap<u8, DeviceIdentification<'static>>,
+    exceptions: HashMap<u8, ModbusException>,
+    responses: VecDeque<MockResponse>,
+    calls: Vec<MockCall>,
+}
+
+impl MockClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) {
+        set(&mut self.state.lock().unwrap().coils, unit_id, address, values.into());
+    }
+
+    pub fn set_discrete_inputs<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) {
+        set(&mut self.state.lock().unwrap().discrete_inputs, unit_id, address, values.into());
+    }
+
+    pub fn set_input_registers(&self, unit_id: u8, address: u16, values: &[u16]) {
+        set(&mut self.state.lock().unwrap().input_registers, unit_id, address, values.iter().copied());
+    }
+
+    pub fn set_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) {
+        set(&mut self.state.lock().unwrap().holding_registers, unit_id, address, values.iter().copied());
+    }
+
+    pub fn coils(&self, unit_id: u8, address: u16, length: u16) -> BitBuf {
+        get(&self.state.lock().unwrap().coils, unit_id, address, length)
+    }
+
+    pub fn holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Vec<u16> {
+        get(&self.state.lock().unwrap().holding_registers, unit_id, address, length)
+    }
+
+    pub fn set_device_identification(&self, unit_id: u8, device_identification: DeviceIdentification<'static>) {
+        self.state.lock().unwrap().device_identification.insert(unit_id, device_identification);
+    }
+
+    /// Fails every request to the unit with the exception, until cleared with `None`.
+    /// Scripted responses take precedence.
+    pub fn set_exception(&self, unit_id: u8, exception: Option<ModbusException>) {
+        let mut state = self.state.lock().unwrap();
+        match exception {
+            Some(exception) => state.exceptions.insert(unit_id, exception),
+            None => state.exceptions.remove(&unit_id),
+        };
+    }
+
+    /// Queues a response for the next request.
+    pub fn push_response(&self, response: MockResponse) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    /// Queues an exception for the next request.
+    pub fn push_exception(&self, exception: ModbusException) {
+        self.push_response(MockResponse::Error(ModbusError::ModbusException(exception)));
+    }
+
+    /// All calls made so far, oldest first.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Returns all calls made so far and clears the history.
+    pub fn take_calls(&self) -> Vec<MockCall> {
+        std::mem::take(&mut self.state.lock().unwrap().calls)
+    }
+
+    /// Records the call and returns the scripted response or injected exception, if any.
+    fn begin(&self, call: MockCall) -> (std::sync::MutexGuard<'_, MockState>, Option<MockResponse>) {
+        let unit_id = call.unit_id();
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        let response = match state.responses.pop_front() {
+            Some(response) => Some(response),
+            None => state
+                .exceptions
+                .get(&unit_id)
+                .map(|ex| MockResponse::Error(ModbusError::ModbusException(*ex))),
+        };
+        (state, response)
+    }
+
+    /// Like [`Self::begin`], but fails calls with invalid arguments without taking their response, as the client doesn't send them.
+    fn begin_valid(
+        &self,
+        call: MockCall,
+        length: usize,
+        max_length: u16,
+    ) -> Result<(std::sync::MutexGuard<'_, MockState>, Option<MockResponse>), ModbusError> {
+        let (_, address, _) = call.range();
+        if let Err(error) = validate_input(address, length, max_length) {
+            self.state.lock().unwrap().calls.push(call);
+            return Err(error);
+        }
+        Ok(self.begin(call))
+    }
+
+    fn read_bits(&self, call: MockCall, max_length: u16, bank: fn(&MockState) -> &HashMap<(u8, u16), bool>) -> Result<BitBuf, ModbusError> {
+        let (unit_id, address, length) = call.range();
+        let (state, response) = self.begin_valid(call, length as usize, max_length)?;
+        match response {
+            None => Ok(get(bank(&state), unit_id, address, length)),
+            Some(MockResponse::Bits(values)) => Ok(values),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+
+    fn read_registers(&self, call: MockCall, max_length: u16, bank: fn(&MockState) -> &HashMap<(u8, u16), u16>) -> Result<Vec<u16>, ModbusError> {
+        let (unit_id, address, length) = call.range();
+        let (state, response) = self.begin_valid(call, length as usize, max_length)?;
+        match response {
+            None => Ok(get(bank(&state), unit_id, address, length)),
+            Some(MockResponse::Registers(values)) => Ok(values),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+
+    fn write<T: Copy>(
+        &self,
+        call: MockCall,
+        values: impl ExactSizeIterator<Item = T>,
+        max_length: u16,
+        bank: fn(&mut MockState) -> &mut HashMap<(u8, u16), T>,
+    ) -> Result<(), ModbusError> {
+        let (unit_id, address, _) = call.range();
+        let (mut state, response) = self.begin_valid(call, values.len(), max_length)?;
+        match response {
+            None => {
+                set(bank(&mut state), unit_id, address, values);
+                Ok(())
+            }
+            Some(MockResponse::Done) => Ok(()),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+}
+
+impl MockCall {
+    fn unit_id(&self) -> u8 {
+        match self {
+            MockCall::ReadCoils { unit_id, .. }
+            | MockCall::ReadDiscreteInputs { unit_id, .. }
+            | MockCall::ReadInputRegisters { unit_id, .. }
+            | MockCall::ReadHoldingRegisters { unit_id, .. }
+            | MockCall::WriteSingleCoil { unit_id, .. }
+            | MockCall::WriteSingleHoldingRegister { unit_id, .. }
+            | MockCall::WriteMultipleCoils { unit_id, .. }
+            | MockCall::WriteMultipleHoldingRegisters { unit_id, .. }
+            | MockCall::MaskWriteHoldingRegister { unit_id, .. }
+            | MockCall::ModbusEncapsulatedInterface { unit_id, .. }
+            | MockCall::ReadDeviceIdentification { unit_id } => *unit_id,
+        }
+    }
+
+    fn range(&self) -> (u8, u16, u16) {
+        match self {
+            MockCall::ReadCoils { unit_id, address, length }
+            | MockCall::ReadDiscreteInputs { unit_id, address, length }
+            | MockCall::ReadInputRegisters { unit_id, address, length }
+            | MockCall::ReadHoldingRegisters { unit_id, address, length } => (*unit_id, *address, *length),
+            MockCall::WriteSingleCoil { unit_id, address, .. }
+            | MockCall::WriteSingleHoldingRegister { unit_id, address, .. }
+            | MockCall::MaskWriteHoldingRegister { unit_id, address, .. } => (*unit_id, *address, 1),
+            MockCall::WriteMultipleCoils { unit_id, address, values } => (*unit_id, *address, values.len() as u16),
+            MockCall::WriteMultipleHoldingRegisters { unit_id, address, values } => (*unit_id, *address, values.len() as u16),
+            MockCall::ModbusEncapsulatedInterface { unit_id, .. } | MockCall::ReadDeviceIdentification { unit_id } => (*unit_id, 0, 0),
+        }
+    }
+}
+
+impl ModbusClient for MockClient {
+    async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        self.read_bits(MockCall::ReadCoils { unit_id, address, length }, READ_COILS_MAX_LEN, |s| &s.coils)
+    }
+
+    async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        let call = MockCall::ReadDiscreteInputs { unit_id, address, length };
+        self.read_bits(call, READ_DISCRETE_INPUTS_MAX_LEN, |s| &s.discrete_inputs)
+    }
+
+    async fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        let call = MockCall::ReadInputRegisters { unit_id, address, length };
+        self.read_registers(call, READ_INPUT_REGISTERS_MAX_LEN, |s| &s.input_registers)
+    }
+
+    async fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        let call = MockCall::ReadHoldingRegisters { unit_id, address, length };
+        self.read_registers(call, READ_HOLDING_REGISTERS_MAX_LEN, |s| &s.holding_registers)
+    }
+
+    async fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        let call = MockCall::WriteSingleCoil { unit_id, address, value };
+        self.write(call, [value].into_iter(), 1, |s| &mut s.coils)
+    }
+
+    async fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        let call = MockCall::WriteSingleHoldingRegister { unit_id, address, value };
+        self.write(call, [value].into_iter(), 1, |s| &mut s.holding_registers)
+    }
+
+    async fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>> + Send) -> Result<(), ModbusError> {
+        let values = values.into();
+        let call = MockCall::WriteMultipleCoils {
+            unit_id,
+            address,
+            values: values.into(),
+        };
+        self.write(call, values.iter(), WRITE_MULTIPLE_COILS_MAX_LEN, |s| &mut s.coils)
+    }
+
+    async fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        let call = MockCall::WriteMultipleHoldingRegisters {
+            unit_id,
+            address,
+            values: values.to_vec(),
+        };
+        self.write(call, values.iter().copied(), WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN, |s| &mut s.holding_registers)
+    }
+
+    async fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        let call = MockCall::MaskWriteHoldingRegister {
+            unit_id,
+            address,
+            and_mask,
+            or_mask,
+        };
+        let (mut state, response) = self.begin(call);
+        match response {
+            None => {
+                let value = state.holding_registers.entry((unit_id, address)).or_default();
+                *value = (*value & and_mask) | (or_mask & !and_mask);
+                Ok(())
+            }
+            Some(MockResponse::Done) => Ok(()),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+
+    async fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        let call = MockCall::ModbusEncapsulatedInterface {
+            unit_id,
+            interface_type,
+            data: data.to_vec(),
+        };
+        match self.begin(call).1 {
+            None => Err(ModbusError::ModbusException(ModbusException::IllegalFunction)),
+            Some(MockResponse::Bytes(data)) => Ok(data),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+
+    async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        let (state, response) = self.begin(MockCall::ReadDeviceIdentification { unit_id });
+        match response {
+            None => state
+                .device_identification
+                .get(&unit_id)
+                .cloned()
+                .ok_or(ModbusError::ModbusException(ModbusException::IllegalFunction)),
+            Some(MockResponse::DeviceIdentification(device_identification)) => Ok(device_identification),
+            Some(response) => Err(unexpected(response)),
+        }
+    }
+}
+
+fn unexpected(response: MockResponse) -> ModbusError {
+    match response {
+        MockResponse::Error(error) => error,
+        _ => ModbusError::InvalidResponse("Scripted response doesn't match the request"),
+    }
+}
+
+fn get<T: Copy + Default, V: FromIterator<T>>(bank: &HashMap<(u8, u16), T>, unit_id: u8, address: u16, length: u16) -> V {
+    (0..length)
+        .map(|i| bank.get(&(unit_id, address.wrapping_add(i))).copied().unwrap_or_default())
+        .collect()
+}
+
+fn set<T>(bank: &mut HashMap<(u8, u16), T>, unit_id: u8, address: u16, values: impl IntoIterator<Item = T>) {
+    for (i, value) in values.into_iter().enumerate() {
+        bank.insert((unit_id, address.wrapping_add(i as u16)), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn toggle<C: ModbusClient>(client: &C, unit_id: u8, address: u16) -> Result<bool, ModbusError> {
+        let value = !client.read_coils(unit_id, address, 1).await?[0];
+        client.write_single_coils(unit_id, address, value).await?;
+        Ok(value)
+    }
+
+    #[tokio::test]
+    async fn mock() {
+        let client = MockClient::new();
+        client.set_coils(1, 10, &[true]);
+
+        assert!(matches!(toggle(&client, 1, 10).await, Ok(false)));
+        assert_eq!(client.coils(1, 10, 1), vec![false]);
+        assert_eq!(
+            client.take_calls(),
+            vec![
+                MockCall::ReadCoils {
+                    unit_id: 1,
+                    address: 10,
+                    length: 1
+                },
+                MockCall::WriteSingleCoil {
+                    unit_id: 1,
+                    address: 10,
+                    value: false
+                },
+            ]
+        );
+
+        client.push_response(MockResponse::Bits([false].into()));
+        client.push_exception(ModbusException::ServerDeviceBusy);
+        assert!(matches!(
+            toggle(&client, 1, 10).await,
+            Err(ModbusError::ModbusException(ModbusException::ServerDeviceBusy))
+        ));
+
+        client.set_exception(1, Some(ModbusException::GatewayPathUnavailable));
+        assert!(matches!(
+            toggle(&client, 1, 10).await,
+            Err(ModbusError::ModbusException(ModbusException::GatewayPathUnavailable))
+        ));
+        assert_eq!(client.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments_keep_responses() {
+        let client = MockClient::new();
+        client.push_response(MockResponse::Registers(vec![1]));
+        client.push_response(MockResponse::Registers(vec![2]));
+
+        assert_eq!(client.read_holding_registers(1, 0, 1).await.unwrap(), [1]);
+        assert!(matches!(client.read_holding_registers(1, 0, 0).await, Err(ModbusError::ArgumentsOutOfRange(_))));
+        assert!(matches!(client.write_multiple_holding_registers(1, 0, &[]).await, Err(ModbusError::ArgumentsOutOfRange(_))));
+        assert_eq!(client.read_holding_registers(1, 0, 1).await.unwrap(), [2]);
+        assert_eq!(client.calls().len(), 4);
+    }
+}
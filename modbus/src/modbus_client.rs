@@ -0,0 +1,151 @@
+use std::future::Future;
+
+use crate::{
+    bit_buf::BitBuf, client::ModbusError, modbus_device::ModbusDevice, modbus_encapsulated_interface::DeviceIdentification, views::Bits,
+    ModbusRTUClient, ModbusTCPClient,
+};
+
+/**
+ * Operations supported by every client.
+ * Application code can be generic over this trait to support multiple transports,
+ * or to be tested with [`crate::MockClient`] instead of a live server.
+ */
+pub trait ModbusClient: Send + Sync {
+    fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> impl Future<Output = Result<BitBuf, ModbusError>> + Send;
+
+    fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> impl Future<Output = Result<BitBuf, ModbusError>> + Send;
+
+    fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> impl Future<Output = Result<Vec<u16>, ModbusError>> + Send;
+
+    fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> impl Future<Output = Result<Vec<u16>, ModbusError>> + Send;
+
+    fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> impl Future<Output = Result<(), ModbusError>> + Send;
+
+    fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> impl Future<Output = Result<(), ModbusError>> + Send;
+
+    fn write_multiple_coils<'a>(
+        &self,
+        unit_id: u8,
+        address: u16,
+        values: impl Into<Bits<'a>> + Send,
+    ) -> impl Future<Output = Result<(), ModbusError>> + Send;
+
+    fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> impl Future<Output = Result<(), ModbusError>> + Send;
+
+    fn mask_write_holding_registers(
+        &self,
+        unit_id: u8,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> impl Future<Output = Result<(), ModbusError>> + Send;
+
+    fn modbus_encapsulated_interface(
+        &self,
+        unit_id: u8,
+        interface_type: u8,
+        data: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, ModbusError>> + Send;
+
+    fn read_device_identification(&self, unit_id: u8) -> impl Future<Output = Result<DeviceIdentification<'static>, ModbusError>> + Send;
+
+    /// Returns a handle to a single unit, so the unit id doesn't need to be passed to every call.
+    fn unit(&self, unit_id: u8) -> ModbusDevice<Self>
+    where
+        Self: Clone + Sized,
+    {
+        ModbusDevice::new(self.clone(), unit_id)
+    }
+}
+
+impl ModbusClient for ModbusTCPClient {
+    async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        ModbusTCPClient::read_coils(self, unit_id, address, length).await
+    }
+
+    async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        ModbusTCPClient::read_discrete_inputs(self, unit_id, address, length).await
+    }
+
+    async fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        ModbusTCPClient::read_input_registers(self, unit_id, address, length).await
+    }
+
+    async fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        ModbusTCPClient::read_holding_registers(self, unit_id, address, length).await
+    }
+
+    async fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        ModbusTCPClient::write_single_coils(self, unit_id, address, value).await
+    }
+
+    async fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        ModbusTCPClient::write_single_holding_register(self, unit_id, address, value).await
+    }
+
+    async fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>> + Send) -> Result<(), ModbusError> {
+        ModbusTCPClient::write_multiple_coils(self, unit_id, address, values).await
+    }
+
+    async fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        ModbusTCPClient::write_multiple_holding_registers(self, unit_id, address, values).await
+    }
+
+    async fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        ModbusTCPClient::mask_write_holding_registers(self, unit_id, address, and_mask, or_mask).await
+    }
+
+    async fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        ModbusTCPClient::modbus_encapsulated_interface(self, unit_id, interface_type, data).await
+    }
+
+    async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        ModbusTCPClient::read_device_identification(self, unit_id).await
+    }
+}
+
+impl ModbusClient for ModbusRTUClient {
+    async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        ModbusRTUClient::read_coils(self, unit_id, address, length).await
+    }
+
+    async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        ModbusRTUClient::read_discrete_inputs(self, unit_id, address, length).await
+    }
+
+    async fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        ModbusRTUClient::read_input_registers(self, unit_id, address, length).await
+    }
+
+    async fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        ModbusRTUClient::read_holding_registers(self, unit_id, address, length).await
+    }
+
+    async fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        ModbusRTUClient::write_single_coils(self, unit_id, address, value).await
+    }
+
+    async fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        ModbusRTUClient::write_single_holding_register(self, unit_id, address, value).await
+    }
+
+    async fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>> + Send) -> Result<(), ModbusError> {
+        ModbusRTUClient::write_multiple_coils(self, unit_id, address, values).await
+    }
+
+    async fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        ModbusRTUClient::write_multiple_holding_registers(self, unit_id, address, values).await
+    }
+
+    async fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        ModbusRTUClient::mask_write_holding_registers(self, unit_id, address, and_mask, or_mask).await
+    }
+
+    async fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        ModbusRTUClient::modbus_encapsulated_interface(self, unit_id, interface_type, data).await
+    }
+
+    async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        ModbusRTUClient::read_device_identification(self, unit_id).await
+    }
+}
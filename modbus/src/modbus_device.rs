@@ -0,0 +1,268 @@
+use std::{future::Future, ops::Range, time::Duration};
+
+use tokio::time;
+
+use crate::{
+    bit_buf::BitBuf, client::ModbusError, consts::*, modbus_client::ModbusClient, modbus_encapsulated_interface::DeviceIdentification,
+    telemetry, views::Bits, ModbusTCPClient,
+};
+
+/**
+ * A handle to a single unit behind a client, created with [`ModbusClient::unit`].
+ *
+ * Reads and writes longer than the configured maximum request sizes are split into multiple requests.
+ * Such operations are not atomic, and a failed write may have been partially applied.
+ *
+ * Cloning the handle is cheap if the client is cheap to clone.
+ */
+#[derive(Clone, Debug)]
+pub struct ModbusDevice<C = ModbusTCPClient> {
+    client: C,
+    unit_id: u8,
+    timeout: Option<Duration>,
+    max_read_bits: u16,
+    max_read_registers: u16,
+    max_write_bits: u16,
+    max_write_registers: u16,
+}
+
+impl<C> ModbusDevice<C>
+where
+    C: ModbusClient,
+{
+    pub fn new(client: C, unit_id: u8) -> Self {
+        Self {
+            client,
+            unit_id,
+            timeout: None,
+            max_read_bits: READ_COILS_MAX_LEN,
+            max_read_registers: READ_HOLDING_REGISTERS_MAX_LEN,
+            max_write_bits: WRITE_MULTIPLE_COILS_MAX_LEN,
+            max_write_registers: WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN,
+        }
+    }
+
+    pub fn unit_id(&self) -> u8 {
+        self.unit_id
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Fails each request with [`ModbusError::Timeout`] if no response is received in time.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of coils or discrete inputs read per request. Clamped to the protocol maximum.
+    pub fn with_max_read_bits(mut self, max: u16) -> Self {
+        self.max_read_bits = max.clamp(1, READ_COILS_MAX_LEN);
+        self
+    }
+
+    /// Maximum number of input or holding registers read per request. Clamped to the protocol maximum.
+    pub fn with_max_read_registers(mut self, max: u16) -> Self {
+        self.max_read_registers = max.clamp(1, READ_HOLDING_REGISTERS_MAX_LEN);
+        self
+    }
+
+    /// Maximum number of coils written per request. Clamped to the protocol maximum.
+    pub fn with_max_write_bits(mut self, max: u16) -> Self {
+        self.max_write_bits = max.clamp(1, WRITE_MULTIPLE_COILS_MAX_LEN);
+        self
+    }
+
+    /// Maximum number of holding registers written per request. Clamped to the protocol maximum.
+    pub fn with_max_write_registers(mut self, max: u16) -> Self {
+        self.max_write_registers = max.clamp(1, WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN);
+        self
+    }
+
+    pub async fn read_coils(&self, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        self.read_chunked(address, length, self.max_read_bits, |address, length| {
+            self.client.read_coils(self.unit_id, address, length)
+        })
+        .await
+    }
+
+    pub async fn read_discrete_inputs(&self, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        self.read_chunked(address, length, self.max_read_bits, |address, length| {
+            self.client.read_discrete_inputs(self.unit_id, address, length)
+        })
+        .await
+    }
+
+    pub async fn read_input_registers(&self, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        self.read_chunked(address, length, self.max_read_registers, |address, length| {
+            self.client.read_input_registers(self.unit_id, address, length)
+        })
+        .await
+    }
+
+    pub async fn read_holding_registers(&self, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        self.read_chunked(address, length, self.max_read_registers, |address, length| {
+            self.client.read_holding_registers(self.unit_id, address, length)
+        })
+        .await
+    }
+
+    pub async fn write_single_coils(&self, address: u16, value: bool) -> Result<(), ModbusError> {
+        self.with_timeout_of(self.client.write_single_coils(self.unit_id, address, value)).await
+    }
+
+    pub async fn write_single_holding_register(&self, address: u16, value: u16) -> Result<(), ModbusError> {
+        self.with_timeout_of(self.client.write_single_holding_register(self.unit_id, address, value)).await
+    }
+
+    pub async fn write_multiple_coils<'a>(&self, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusError> {
+        let values = values.into();
+        self.write_chunked(address, values.len(), self.max_write_bits, |address, range| {
+            self.client.write_multiple_coils(self.unit_id, address, values.slice(range))
+        })
+        .await
+    }
+
+    pub async fn write_multiple_holding_registers(&self, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        self.write_chunked(address, values.len(), self.max_write_registers, |address, range| {
+            self.client.write_multiple_holding_registers(self.unit_id, address, &values[range])
+        })
+        .await
+    }
+
+    pub async fn mask_write_holding_registers(&self, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        self.with_timeout_of(self.client.mask_write_holding_registers(self.unit_id, address, and_mask, or_mask))
+            .await
+    }
+
+    pub async fn modbus_encapsulated_interface(&self, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        self.with_timeout_of(self.client.modbus_encapsulated_interface(self.unit_id, interface_type, data))
+            .await
+    }
+
+    /// The timeout applies to the whole operation, which may consist of multiple requests.
+    pub async fn read_device_identification(&self) -> Result<DeviceIdentification<'static>, ModbusError> {
+        self.with_timeout_of(self.client.read_device_identification(self.unit_id)).await
+    }
+
+    async fn with_timeout_of<T>(&self, future: impl Future<Output = Result<T, ModbusError>>) -> Result<T, ModbusError> {
+        match self.timeout {
+            None => future.await,
+            Some(timeout) => time::timeout(timeout, future).await.unwrap_or_else(|_| {
+                telemetry::client_timeout();
+                Err(ModbusError::Timeout)
+            }),
+        }
+    }
+
+    async fn read_chunked<'a, V, F, Fut>(&'a self, address: u16, length: u16, max_length: u16, read: F) -> Result<V, ModbusError>
+    where
+        V: Chunk,
+        F: Fn(u16, u16) -> Fut,
+        Fut: Future<Output = Result<V, ModbusError>> + 'a,
+    {
+        validate_range(address, length as usize)?;
+        let mut values = V::default();
+        let mut offset = 0;
+        while offset < length {
+            let chunk = (length - offset).min(max_length);
+            let chunk_values = self.with_timeout_of(read(address + offset, chunk)).await?;
+            if chunk_values.len() != chunk as usize {
+                return Err(ModbusError::InvalidResponse("Length mismatch"));
+            }
+            values.append(chunk_values);
+            offset += chunk;
+        }
+        Ok(values)
+    }
+
+    /// `write` is called with the address and the range of the values of each chunk.
+    async fn write_chunked<'a, F, Fut>(&'a self, address: u16, length: usize, max_length: u16, write: F) -> Result<(), ModbusError>
+    where
+        F: Fn(u16, Range<usize>) -> Fut,
+        Fut: Future<Output = Result<(), ModbusError>> + 'a,
+    {
+        validate_range(address, length)?;
+        for start in (0..length).step_by(max_length as usize) {
+            let end = (start + max_length as usize).min(length);
+            self.with_timeout_of(write(address + start as u16, start..end)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The values read in one request, joined into the result of a chunked read.
+trait Chunk: Default {
+    fn len(&self) -> usize;
+    fn append(&mut self, chunk: Self);
+}
+
+impl Chunk for Vec<u16> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn append(&mut self, chunk: Self) {
+        self.extend(chunk);
+    }
+}
+
+impl Chunk for BitBuf {
+    fn len(&self) -> usize {
+        BitBuf::len(self)
+    }
+
+    fn append(&mut self, chunk: Self) {
+        self.extend(&chunk);
+    }
+}
+
+fn validate_range(address: u16, length: usize) -> Result<(), ModbusError> {
+    if length == 0 || address as usize + length - 1 > u16::MAX as usize {
+        return Err(ModbusError::ArgumentsOutOfRange("Address + length exceeds device address space"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockCall, MockClient};
+
+    #[tokio::test]
+    async fn chunked() {
+        let client = MockClient::new();
+        let device = client.unit(3).with_max_read_registers(10).with_max_write_registers(4);
+
+        device.write_multiple_holding_registers(100, &[1, 2, 3, 4, 5, 6]).await.unwrap();
+        assert_eq!(device.read_holding_registers(95, 15).await.unwrap()[5..11], [1, 2, 3, 4, 5, 6]);
+        assert!(device.read_holding_registers(0xFFFF, 2).await.is_err());
+
+        assert_eq!(
+            client.calls(),
+            vec![
+                MockCall::WriteMultipleHoldingRegisters {
+                    unit_id: 3,
+                    address: 100,
+                    values: vec![1, 2, 3, 4]
+                },
+                MockCall::WriteMultipleHoldingRegisters {
+                    unit_id: 3,
+                    address: 104,
+                    values: vec![5, 6]
+                },
+                MockCall::ReadHoldingRegisters {
+                    unit_id: 3,
+                    address: 95,
+                    length: 10
+                },
+                MockCall::ReadHoldingRegisters {
+                    unit_id: 3,
+                    address: 105,
+                    length: 5
+                },
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, collections::HashMap};
+use alloc::{borrow::Cow, collections::BTreeMap};
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
@@ -119,6 +119,7 @@ impl PartialEq for ReadDeviceIdentificationConformityLevel {
 
 /// Data structure used when reading identification and additional information from a device.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceIdentification<'a> {
     pub vendor_name: Cow<'a, str>,
     pub product_code: Cow<'a, str>,
@@ -129,5 +130,38 @@ pub struct DeviceIdentification<'a> {
     pub user_application_name: Option<Cow<'a, str>>,
     /// Private objects may be optionally defined.
     /// The range [0x80 – 0xFF] is product dependant.
-    pub objects: HashMap<u8, Cow<'a, [u8]>>,
+    /// Serialized as hex strings by human-readable formats and as bytes by binary formats.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_impls::objects"))]
+    pub objects: BTreeMap<u8, Cow<'a, [u8]>>,
+}
+
+impl DeviceIdentification<'_> {
+    /// The value of an object, either one of the standard objects 0x00 – 0x06 or one of [`Self::objects`].
+    pub(crate) fn object(&self, id: u8) -> Option<&[u8]> {
+        match id {
+            0 => Some(self.vendor_name.as_bytes()),
+            1 => Some(self.product_code.as_bytes()),
+            2 => Some(self.major_minor_revision.as_bytes()),
+            3 => Some(self.vendor_url.as_ref()?.as_bytes()),
+            4 => Some(self.product_name.as_ref()?.as_bytes()),
+            5 => Some(self.model_name.as_ref()?.as_bytes()),
+            6 => Some(self.user_application_name.as_ref()?.as_bytes()),
+            _ => Some(self.objects.get(&id)?),
+        }
+    }
+
+    /// Copies any borrowed strings and objects, so the identification no longer borrows.
+    pub fn into_owned(self) -> DeviceIdentification<'static> {
+        let owned = |value: Cow<str>| -> Cow<'static, str> { value.into_owned().into() };
+        DeviceIdentification {
+            vendor_name: owned(self.vendor_name),
+            product_code: owned(self.product_code),
+            major_minor_revision: owned(self.major_minor_revision),
+            vendor_url: self.vendor_url.map(owned),
+            product_name: self.product_name.map(owned),
+            model_name: self.model_name.map(owned),
+            user_application_name: self.user_application_name.map(owned),
+            objects: self.objects.into_iter().map(|(id, data)| (id, data.into_owned().into())).collect(),
+        }
+    }
 }
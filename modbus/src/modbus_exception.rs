@@ -3,9 +3,12 @@ use thiserror::Error;
 /**
  * Exception codes as defined by the protocol.
  * See the [MODBUS Application Protocol Specification](https://www.modbus.org/docs/Modbus_Application_Protocol_V1_1b3.pdf) for more details.
+ *
+ * Serialized as the exception code.
  */
 #[repr(u8)]
 #[derive(Error, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "u8", into = "u8"))]
 pub enum ModbusException {
     /// The function code received in the query is not an allowable action for the server.
     #[error("Illegal function")]
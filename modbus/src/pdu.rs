@@ -0,0 +1,443 @@
+//! Decoded requests and responses, as seen by server middleware, and their encoding as PDUs:
+//! a function code followed by the function's data.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "tokio")]
+use core::ops::RangeInclusive;
+
+use thiserror::Error;
+
+#[cfg(feature = "tokio")]
+use crate::table::Table;
+use crate::{
+    bit_buf::BitBuf,
+    encoding::{DecodeError, Decodable, EncodeError, Encoder},
+    function_code::FunctionCode,
+    messages::*,
+    modbus_encapsulated_interface::*,
+    modbus_exception::ModbusException,
+};
+
+/// The longest PDU that fits in a frame.
+pub(crate) const PDU_MAX_LENGTH: usize = 253;
+
+/// An error encoding or decoding a PDU.
+#[derive(Error, PartialEq, Debug, Clone, Copy)]
+pub enum PduError {
+    #[error("Unsupported function code: {0}")]
+    UnsupportedFunctionCode(u8),
+    /// The PDU is an exception response.
+    #[error("Exception: {0}")]
+    Exception(ModbusException),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+/// Starts a PDU with the function code.
+fn encoder(function_code: FunctionCode) -> Encoder {
+    let mut encoder = Encoder::new();
+    encoder.write_u8(function_code.into());
+    encoder
+}
+
+fn finish(encoder: Encoder) -> Result<Vec<u8>, PduError> {
+    let pdu = encoder.finish();
+    if pdu.len() > PDU_MAX_LENGTH {
+        return Err(EncodeError::Overflow.into());
+    }
+    Ok(pdu)
+}
+
+/// Splits a PDU into function code and data.
+fn split(pdu: &[u8]) -> Result<(FunctionCode, &[u8]), PduError> {
+    let (&function_code, data) = pdu.split_first().ok_or(DecodeError::MissingData)?;
+    Ok((function_code.into(), data))
+}
+
+/// A decoded request.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Request {
+    ReadCoils { address: u16, length: u16 },
+    ReadDiscreteInputs { address: u16, length: u16 },
+    ReadInputRegisters { address: u16, length: u16 },
+    ReadHoldingRegisters { address: u16, length: u16 },
+    WriteSingleCoil { address: u16, value: bool },
+    WriteSingleHoldingRegister { address: u16, value: u16 },
+    WriteMultipleCoils { address: u16, values: BitBuf },
+    WriteMultipleHoldingRegisters { address: u16, values: Vec<u16> },
+    MaskWriteHoldingRegister { address: u16, and_mask: u16, or_mask: u16 },
+    ReadDeviceIdentification,
+    ModbusEncapsulatedInterface { interface_type: u8, data: Vec<u8> },
+}
+
+impl Request {
+    pub fn function_code(&self) -> FunctionCode {
+        match self {
+            Request::ReadCoils { .. } => FunctionCode::ReadCoils,
+            Request::ReadDiscreteInputs { .. } => FunctionCode::ReadDiscreteInputs,
+            Request::ReadInputRegisters { .. } => FunctionCode::ReadInputRegisters,
+            Request::ReadHoldingRegisters { .. } => FunctionCode::ReadHoldingRegisters,
+            Request::WriteSingleCoil { .. } => FunctionCode::WriteSingleCoil,
+            Request::WriteSingleHoldingRegister { .. } => FunctionCode::WriteSingleHoldingRegister,
+            Request::WriteMultipleCoils { .. } => FunctionCode::WriteMultipleCoils,
+            Request::WriteMultipleHoldingRegisters { .. } => FunctionCode::WriteMultipleHoldingRegisters,
+            Request::MaskWriteHoldingRegister { .. } => FunctionCode::MaskWriteHoldingRegister,
+            Request::ReadDeviceIdentification | Request::ModbusEncapsulatedInterface { .. } => FunctionCode::ModbusEncapsulatedInterface,
+        }
+    }
+
+    /// Whether the request writes coils or holding registers.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Request::WriteSingleCoil { .. }
+                | Request::WriteSingleHoldingRegister { .. }
+                | Request::WriteMultipleCoils { .. }
+                | Request::WriteMultipleHoldingRegisters { .. }
+                | Request::MaskWriteHoldingRegister { .. }
+        )
+    }
+
+    /**
+     * Encodes the request as a PDU.
+     *
+     * [`Request::ReadDeviceIdentification`] is encoded as the first request of a stream of all objects,
+     * further requests are needed to read the objects that don't fit in the first response.
+     */
+    pub fn encode(&self) -> Result<Vec<u8>, PduError> {
+        let mut encoder = encoder(self.function_code());
+        match self {
+            Request::ReadCoils { address, length } => encoder.write_type(&ReadCoilsRequest { address: *address, length: *length }),
+            Request::ReadDiscreteInputs { address, length } => encoder.write_type(&ReadDiscreteInputsRequest { address: *address, length: *length }),
+            Request::ReadInputRegisters { address, length } => encoder.write_type(&ReadInputRegistersRequest { address: *address, length: *length }),
+            Request::ReadHoldingRegisters { address, length } => encoder.write_type(&ReadHoldingRegistersRequest { address: *address, length: *length }),
+            Request::WriteSingleCoil { address, value } => encoder.write_type(&WriteSingleCoilRequest { address: *address, value: *value }),
+            Request::WriteSingleHoldingRegister { address, value } => {
+                encoder.write_type(&WriteSingleHoldingRegisterRequest { address: *address, value: *value })
+            }
+            Request::WriteMultipleCoils { address, values } => encoder.write_type(&WriteMultipleCoilsRequest {
+                address: *address,
+                values: values.as_bits(),
+            }),
+            Request::WriteMultipleHoldingRegisters { address, values } => encoder.write_type(&WriteMultipleHoldingRegistersRequest {
+                address: *address,
+                values: values.into(),
+            }),
+            Request::MaskWriteHoldingRegister { address, and_mask, or_mask } => encoder.write_type(&MaskWriteHoldingRegisterRequest {
+                address: *address,
+                and_mask: *and_mask,
+                or_mask: *or_mask,
+            }),
+            Request::ReadDeviceIdentification => {
+                encoder.write_u8(ModbusEncapsulatedInterfaceType::ReadDeviceIdentification.into());
+                encoder.write_type(&ReadDeviceIdentificationRequest {
+                    device_id_code: ReadDeviceIdentificationIdCode::Extended,
+                    object_id: 0,
+                })
+            }
+            Request::ModbusEncapsulatedInterface { interface_type, data } => encoder.write_type(&ModbusEncapsulatedInterfaceRequest {
+                kind: (*interface_type).into(),
+                data: data.into(),
+            }),
+        }?;
+        finish(encoder)
+    }
+
+    /**
+     * Decodes a request PDU.
+     *
+     * All Modbus Encapsulated Interface requests, including Read Device Identification, are decoded as
+     * [`Request::ModbusEncapsulatedInterface`] to keep their data.
+     */
+    pub fn decode(pdu: &[u8]) -> Result<Self, PduError> {
+        let (function_code, data) = split(pdu)?;
+        Ok(match function_code {
+            FunctionCode::ReadCoils => {
+                let req = ReadCoilsRequest::decode_from_bytes(data)?;
+                Request::ReadCoils { address: req.address, length: req.length }
+            }
+            FunctionCode::ReadDiscreteInputs => {
+                let req = ReadDiscreteInputsRequest::decode_from_bytes(data)?;
+                Request::ReadDiscreteInputs { address: req.address, length: req.length }
+            }
+            FunctionCode::ReadInputRegisters => {
+                let req = ReadInputRegistersRequest::decode_from_bytes(data)?;
+                Request::ReadInputRegisters { address: req.address, length: req.length }
+            }
+            FunctionCode::ReadHoldingRegisters => {
+                let req = ReadHoldingRegistersRequest::decode_from_bytes(data)?;
+                Request::ReadHoldingRegisters { address: req.address, length: req.length }
+            }
+            FunctionCode::WriteSingleCoil => {
+                let req = WriteSingleCoilRequest::decode_from_bytes(data)?;
+                Request::WriteSingleCoil { address: req.address, value: req.value }
+            }
+            FunctionCode::WriteSingleHoldingRegister => {
+                let req = WriteSingleHoldingRegisterRequest::decode_from_bytes(data)?;
+                Request::WriteSingleHoldingRegister { address: req.address, value: req.value }
+            }
+            FunctionCode::WriteMultipleCoils => {
+                let req = WriteMultipleCoilsRequest::decode_from_bytes(data)?;
+                Request::WriteMultipleCoils { address: req.address, values: req.values.into() }
+            }
+            FunctionCode::WriteMultipleHoldingRegisters => {
+                let req = WriteMultipleHoldingRegistersRequest::decode_from_bytes(data)?;
+                Request::WriteMultipleHoldingRegisters { address: req.address, values: req.values.into_owned() }
+            }
+            FunctionCode::MaskWriteHoldingRegister => {
+                let req = MaskWriteHoldingRegisterRequest::decode_from_bytes(data)?;
+                Request::MaskWriteHoldingRegister {
+                    address: req.address,
+                    and_mask: req.and_mask,
+                    or_mask: req.or_mask,
+                }
+            }
+            FunctionCode::ModbusEncapsulatedInterface => {
+                let req = ModbusEncapsulatedInterfaceRequest::decode_from_bytes(data)?;
+                Request::ModbusEncapsulatedInterface {
+                    interface_type: req.kind.into(),
+                    data: req.data.into_owned(),
+                }
+            }
+            FunctionCode::Error(code) | FunctionCode::Unknown(code) => return Err(PduError::UnsupportedFunctionCode(code)),
+        })
+    }
+
+    /// The table and addresses accessed by the request, if any.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn addresses(&self) -> Option<(Table, RangeInclusive<u16>)> {
+        let range = |address: u16, length: usize| address..=address.saturating_add((length as u16).saturating_sub(1));
+        Some(match self {
+            Request::ReadCoils { address, length } => (Table::Coils, range(*address, *length as usize)),
+            Request::ReadDiscreteInputs { address, length } => (Table::DiscreteInputs, range(*address, *length as usize)),
+            Request::ReadInputRegisters { address, length } => (Table::InputRegisters, range(*address, *length as usize)),
+            Request::ReadHoldingRegisters { address, length } => (Table::HoldingRegisters, range(*address, *length as usize)),
+            Request::WriteSingleCoil { address, .. } => (Table::Coils, range(*address, 1)),
+            Request::WriteSingleHoldingRegister { address, .. } => (Table::HoldingRegisters, range(*address, 1)),
+            Request::WriteMultipleCoils { address, values } => (Table::Coils, range(*address, values.len())),
+            Request::WriteMultipleHoldingRegisters { address, values } => (Table::HoldingRegisters, range(*address, values.len())),
+            Request::MaskWriteHoldingRegister { address, .. } => (Table::HoldingRegisters, range(*address, 1)),
+            Request::ReadDeviceIdentification | Request::ModbusEncapsulatedInterface { .. } => return None,
+        })
+    }
+}
+
+/// A successful response. Every [`Request`] has a response of the same name.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Response {
+    ReadCoils(BitBuf),
+    ReadDiscreteInputs(BitBuf),
+    ReadInputRegisters(Vec<u16>),
+    ReadHoldingRegisters(Vec<u16>),
+    WriteSingleCoil { address: u16, value: bool },
+    WriteSingleHoldingRegister { address: u16, value: u16 },
+    WriteMultipleCoils { address: u16, length: u16 },
+    WriteMultipleHoldingRegisters { address: u16, length: u16 },
+    MaskWriteHoldingRegister { address: u16, and_mask: u16, or_mask: u16 },
+    ReadDeviceIdentification(DeviceIdentification<'static>),
+    ModbusEncapsulatedInterface { interface_type: u8, data: Vec<u8> },
+}
+
+impl Response {
+    pub fn function_code(&self) -> FunctionCode {
+        match self {
+            Response::ReadCoils(_) => FunctionCode::ReadCoils,
+            Response::ReadDiscreteInputs(_) => FunctionCode::ReadDiscreteInputs,
+            Response::ReadInputRegisters(_) => FunctionCode::ReadInputRegisters,
+            Response::ReadHoldingRegisters(_) => FunctionCode::ReadHoldingRegisters,
+            Response::WriteSingleCoil { .. } => FunctionCode::WriteSingleCoil,
+            Response::WriteSingleHoldingRegister { .. } => FunctionCode::WriteSingleHoldingRegister,
+            Response::WriteMultipleCoils { .. } => FunctionCode::WriteMultipleCoils,
+            Response::WriteMultipleHoldingRegisters { .. } => FunctionCode::WriteMultipleHoldingRegisters,
+            Response::MaskWriteHoldingRegister { .. } => FunctionCode::MaskWriteHoldingRegister,
+            Response::ReadDeviceIdentification(_) | Response::ModbusEncapsulatedInterface { .. } => FunctionCode::ModbusEncapsulatedInterface,
+        }
+    }
+
+    /**
+     * Encodes the response as a PDU.
+     *
+     * [`Response::ReadDeviceIdentification`] is encoded as a single response with all objects,
+     * failing if they don't fit.
+     */
+    pub fn encode(&self) -> Result<Vec<u8>, PduError> {
+        let mut encoder = encoder(self.function_code());
+        match self {
+            Response::ReadCoils(values) => encoder.write_type(&ReadCoilsResponse { values: values.as_bits() }),
+            Response::ReadDiscreteInputs(values) => encoder.write_type(&ReadDiscreteInputsResponse { values: values.as_bits() }),
+            Response::ReadInputRegisters(values) => encoder.write_type(&ReadInputRegistersResponse { values: values[..].into() }),
+            Response::ReadHoldingRegisters(values) => encoder.write_type(&ReadHoldingRegistersResponse { values: values[..].into() }),
+            Response::WriteSingleCoil { address, value } => encoder.write_type(&WriteSingleCoilResponse { address: *address, value: *value }),
+            Response::WriteSingleHoldingRegister { address, value } => {
+                encoder.write_type(&WriteSingleHoldingRegisterResponse { address: *address, value: *value })
+            }
+            Response::WriteMultipleCoils { address, length } => encoder.write_type(&WriteMultipleCoilsResponse { address: *address, length: *length }),
+            Response::WriteMultipleHoldingRegisters { address, length } => {
+                encoder.write_type(&WriteMultipleHoldingRegistersResponse { address: *address, length: *length })
+            }
+            Response::MaskWriteHoldingRegister { address, and_mask, or_mask } => encoder.write_type(&MaskWriteHoldingRegisterResponse {
+                address: *address,
+                and_mask: *and_mask,
+                or_mask: *or_mask,
+            }),
+            Response::ReadDeviceIdentification(device_identification) => {
+                encoder.write_u8(ModbusEncapsulatedInterfaceType::ReadDeviceIdentification.into());
+                encoder.write_type(&ReadDeviceIdentificationResponse {
+                    device_id_code: ReadDeviceIdentificationIdCode::Extended,
+                    conformity_level: ReadDeviceIdentificationConformityLevel::ExtendedStreamAndIndividual,
+                    more_follows: false,
+                    next_object_id: 0,
+                    objects: (0..=0xFF).filter_map(|id| Some((id, device_identification.object(id)?.into()))).collect::<BTreeMap<_, _>>(),
+                })
+            }
+            Response::ModbusEncapsulatedInterface { interface_type, data } => encoder.write_type(&ModbusEncapsulatedInterfaceResponse {
+                kind: (*interface_type).into(),
+                data: data.into(),
+            }),
+        }?;
+        finish(encoder)
+    }
+
+    /**
+     * Decodes a response PDU. Exception responses are returned as [`PduError::Exception`].
+     *
+     * The number of coils or discrete inputs read isn't part of the response, so their values are padded with `false`
+     * to a multiple of 8. All Modbus Encapsulated Interface responses, including Read Device Identification, are decoded as
+     * [`Response::ModbusEncapsulatedInterface`].
+     */
+    pub fn decode(pdu: &[u8]) -> Result<Self, PduError> {
+        let (function_code, data) = split(pdu)?;
+        Ok(match function_code {
+            FunctionCode::ReadCoils => {
+                let res = ReadCoilsResponse::decode_from_bytes(data)?;
+                Response::ReadCoils(res.values.into())
+            }
+            FunctionCode::ReadDiscreteInputs => {
+                let res = ReadDiscreteInputsResponse::decode_from_bytes(data)?;
+                Response::ReadDiscreteInputs(res.values.into())
+            }
+            FunctionCode::ReadInputRegisters => {
+                let res = ReadInputRegistersResponse::decode_from_bytes(data)?;
+                Response::ReadInputRegisters(res.values.to_vec())
+            }
+            FunctionCode::ReadHoldingRegisters => {
+                let res = ReadHoldingRegistersResponse::decode_from_bytes(data)?;
+                Response::ReadHoldingRegisters(res.values.to_vec())
+            }
+            FunctionCode::WriteSingleCoil => {
+                let res = WriteSingleCoilResponse::decode_from_bytes(data)?;
+                Response::WriteSingleCoil { address: res.address, value: res.value }
+            }
+            FunctionCode::WriteSingleHoldingRegister => {
+                let res = WriteSingleHoldingRegisterResponse::decode_from_bytes(data)?;
+                Response::WriteSingleHoldingRegister { address: res.address, value: res.value }
+            }
+            FunctionCode::WriteMultipleCoils => {
+                let res = WriteMultipleCoilsResponse::decode_from_bytes(data)?;
+                Response::WriteMultipleCoils { address: res.address, length: res.length }
+            }
+            FunctionCode::WriteMultipleHoldingRegisters => {
+                let res = WriteMultipleHoldingRegistersResponse::decode_from_bytes(data)?;
+                Response::WriteMultipleHoldingRegisters { address: res.address, length: res.length }
+            }
+            FunctionCode::MaskWriteHoldingRegister => {
+                let res = MaskWriteHoldingRegisterResponse::decode_from_bytes(data)?;
+                Response::MaskWriteHoldingRegister {
+                    address: res.address,
+                    and_mask: res.and_mask,
+                    or_mask: res.or_mask,
+                }
+            }
+            FunctionCode::ModbusEncapsulatedInterface => {
+                let res = ModbusEncapsulatedInterfaceResponse::decode_from_bytes(data)?;
+                Response::ModbusEncapsulatedInterface {
+                    interface_type: res.kind.into(),
+                    data: res.data.into_owned(),
+                }
+            }
+            FunctionCode::Error(_) => {
+                let res = ExceptionMessage::decode_from_bytes(data)?;
+                return Err(PduError::Exception(res.code));
+            }
+            FunctionCode::Unknown(code) => return Err(PduError::UnsupportedFunctionCode(code)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn requests() {
+        for request in [
+            Request::ReadCoils { address: 1, length: 2 },
+            Request::ReadDiscreteInputs { address: 1, length: 2 },
+            Request::ReadInputRegisters { address: 1, length: 2 },
+            Request::ReadHoldingRegisters { address: 1, length: 2 },
+            Request::WriteSingleCoil { address: 1, value: true },
+            Request::WriteSingleHoldingRegister { address: 1, value: 2 },
+            Request::WriteMultipleCoils { address: 1, values: [true, false, true].into() },
+            Request::WriteMultipleHoldingRegisters { address: 1, values: vec![2, 3] },
+            Request::MaskWriteHoldingRegister { address: 1, and_mask: 2, or_mask: 3 },
+            Request::ModbusEncapsulatedInterface { interface_type: 13, data: vec![1, 2] },
+        ] {
+            assert_eq!(Request::decode(&request.encode().unwrap()), Ok(request));
+        }
+
+        // Read holding registers 0x006B-0x006D, from the specification.
+        assert_eq!(
+            Request::decode(&[0x03, 0x00, 0x6B, 0x00, 0x03]),
+            Ok(Request::ReadHoldingRegisters { address: 0x6B, length: 3 })
+        );
+        assert_eq!(
+            Request::decode(&Request::ReadDeviceIdentification.encode().unwrap()),
+            Ok(Request::ModbusEncapsulatedInterface { interface_type: 14, data: vec![3, 0] })
+        );
+        assert_eq!(Request::decode(&[0x41]), Err(PduError::UnsupportedFunctionCode(0x41)));
+        assert_eq!(Request::decode(&[]), Err(PduError::Decode(DecodeError::MissingData)));
+        assert_eq!(Request::decode(&[0x03, 0x00, 0x6B]), Err(PduError::Decode(DecodeError::MissingData)));
+        assert!(Request::WriteMultipleHoldingRegisters { address: 0, values: vec![0; 200] }.encode().is_err());
+    }
+
+    #[test]
+    fn responses() {
+        for response in [
+            Response::ReadCoils([true, false, true, false, false, false, false, true].into()),
+            Response::ReadDiscreteInputs(BitBuf::repeat(false, 16)),
+            Response::ReadInputRegisters(vec![1, 2]),
+            Response::ReadHoldingRegisters(vec![1, 2]),
+            Response::WriteSingleCoil { address: 1, value: false },
+            Response::WriteSingleHoldingRegister { address: 1, value: 2 },
+            Response::WriteMultipleCoils { address: 1, length: 2 },
+            Response::WriteMultipleHoldingRegisters { address: 1, length: 2 },
+            Response::MaskWriteHoldingRegister { address: 1, and_mask: 2, or_mask: 3 },
+            Response::ModbusEncapsulatedInterface { interface_type: 13, data: vec![1, 2] },
+        ] {
+            assert_eq!(Response::decode(&response.encode().unwrap()), Ok(response));
+        }
+
+        // Coils are padded to whole bytes.
+        assert_eq!(Response::decode(&[0x01, 0x01, 0b101]), Ok(Response::ReadCoils([true, false, true, false, false, false, false, false].into())));
+        assert_eq!(Response::decode(&[0x83, 0x02]), Err(PduError::Exception(ModbusException::IllegalDataAddress)));
+
+        let device_identification = DeviceIdentification {
+            vendor_name: "a".into(),
+            product_code: "b".into(),
+            major_minor_revision: "c".into(),
+            vendor_url: None,
+            product_name: None,
+            model_name: None,
+            user_application_name: None,
+            objects: BTreeMap::from([(0x80, vec![1].into())]),
+        };
+        assert_eq!(
+            Response::ReadDeviceIdentification(device_identification).encode().unwrap(),
+            [0x2B, 0x0E, 0x03, 0x83, 0x00, 0x00, 0x04, 0x00, 0x01, b'a', 0x01, 0x01, b'b', 0x02, 0x01, b'c', 0x80, 0x01, 0x01]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::OnceCell;
+
+use crate::{
+    bit_buf::BitBuf,
+    client::{gateway_exception, ModbusError},
+    modbus_client::ModbusClient,
+    modbus_encapsulated_interface::DeviceIdentification,
+    modbus_exception::ModbusException,
+    request_context::RequestContext,
+    server::ModbusTCPServerHandler,
+    table::Table,
+    views::Bits,
+};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct ReadKey {
+    unit_id: u8,
+    table: Table,
+    address: u16,
+    length: u16,
+}
+
+impl ReadKey {
+    fn addresses(&self) -> RangeInclusive<u16> {
+        self.address..=self.address.saturating_add(self.length.saturating_sub(1))
+    }
+}
+
+/// A read sent upstream, shared by identical reads while in flight.
+type InFlight<V> = Arc<OnceCell<Result<V, ModbusException>>>;
+
+/// Cached reads and reads in flight of either bits or registers.
+struct Cache<V> {
+    state: Mutex<CacheState<V>>,
+    /// Keyed by the generation of the table too, so reads after a write don't share a read sent before it.
+    in_flight: Mutex<HashMap<(ReadKey, u64), InFlight<V>>>,
+}
+
+struct CacheState<V> {
+    entries: HashMap<ReadKey, (Instant, V)>,
+    /// Incremented by every successful write to a table of a unit. Reads sent before the write aren't cached.
+    generations: HashMap<(u8, Table), u64>,
+}
+
+impl<V> CacheState<V> {
+    fn generation(&self, key: &ReadKey) -> u64 {
+        self.generations.get(&(key.unit_id, key.table)).copied().unwrap_or_default()
+    }
+}
+
+impl<V> Default for Cache<V> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                generations: HashMap::new(),
+            }),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+struct CacheRule {
+    unit_id: u8,
+    table: Table,
+    addresses: RangeInclusive<u16>,
+    ttl: Duration,
+}
+
+struct WriteRule {
+    unit_id: u8,
+    table: Table,
+    addresses: RangeInclusive<u16>,
+    allow: bool,
+}
+
+/**
+ * A server handler that forwards requests to an upstream device, so many clients can share the connections of one client.
+ *
+ * Reads can be cached for a time per address range, and identical reads in flight at the same time are sent upstream only once.
+ * Writes can be restricted by unit and address range, denied writes are answered with [`ModbusException::IllegalDataAddress`].
+ * A successful write removes the cached reads it overlaps, and reads sent before it completed aren't cached.
+ *
+ * Exceptions from the device are passed through. Requests the device doesn't answer in time are answered with
+ * [`ModbusException::GatewayTargetDeviceFailedToRespond`], and requests that can't be sent with [`ModbusException::GatewayPathUnavailable`].
+ *
+ * The unit id of the request is used upstream as is. Use a [`crate::UnitRouter`] with a proxy per device to proxy several devices.
+ */
+pub struct ModbusProxy<C> {
+    client: C,
+    cache_rules: Vec<CacheRule>,
+    write_rules: Vec<WriteRule>,
+    deduplicate: bool,
+    bits: Cache<BitBuf>,
+    registers: Cache<Vec<u16>>,
+}
+
+impl<C: ModbusClient> ModbusProxy<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            cache_rules: Vec::new(),
+            write_rules: Vec::new(),
+            deduplicate: true,
+            bits: Cache::default(),
+            registers: Cache::default(),
+        }
+    }
+
+    /// Caches reads within `addresses` of the unit for `ttl`. Reads are only cached if they are entirely within a cached range.
+    pub fn with_cache(mut self, unit_id: u8, table: Table, addresses: RangeInclusive<u16>, ttl: Duration) -> Self {
+        self.cache_rules.push(CacheRule { unit_id, table, addresses, ttl });
+        self
+    }
+
+    /**
+     * Allows writes within `addresses` of the unit. `table` is either [`Table::Coils`] or [`Table::HoldingRegisters`].
+     *
+     * Once any range of a table of the unit is allowed, writes to the table must be entirely within an allowed range.
+     * Without allowed ranges every write is allowed.
+     */
+    pub fn with_allowed_writes(mut self, unit_id: u8, table: Table, addresses: RangeInclusive<u16>) -> Self {
+        self.write_rules.push(WriteRule { unit_id, table, addresses, allow: true });
+        self
+    }
+
+    /// Denies writes touching `addresses` of the unit, even if they are allowed.
+    pub fn with_denied_writes(mut self, unit_id: u8, table: Table, addresses: RangeInclusive<u16>) -> Self {
+        self.write_rules.push(WriteRule { unit_id, table, addresses, allow: false });
+        self
+    }
+
+    /// Whether identical reads in flight at the same time are sent upstream only once. Default is true.
+    pub fn with_deduplication(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// The upstream client.
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    async fn read<V: Clone>(
+        &self,
+        cache: &Cache<V>,
+        key: ReadKey,
+        fetch: impl Future<Output = Result<V, ModbusError>>,
+    ) -> Result<V, ModbusException> {
+        let ttl = self
+            .cache_rules
+            .iter()
+            .find(|rule| rule.unit_id == key.unit_id && rule.table == key.table && contains(&rule.addresses, &key.addresses()))
+            .map(|rule| rule.ttl);
+
+        let generation = {
+            let mut state = cache.state.lock().unwrap();
+            if ttl.is_some() {
+                match state.entries.get(&key) {
+                    Some((expires, values)) if *expires > Instant::now() => return Ok(values.clone()),
+                    Some(_) => _ = state.entries.remove(&key),
+                    None => {}
+                }
+            }
+            state.generation(&key)
+        };
+
+        if !self.deduplicate {
+            return Self::fetch(cache, key, generation, ttl, fetch).await;
+        }
+
+        let cell = cache.in_flight.lock().unwrap().entry((key, generation)).or_default().clone();
+        let result = cell.get_or_init(|| Self::fetch(cache, key, generation, ttl, fetch)).await.clone();
+
+        let mut in_flight = cache.in_flight.lock().unwrap();
+        if in_flight.get(&(key, generation)).is_some_and(|other| Arc::ptr_eq(other, &cell)) {
+            in_flight.remove(&(key, generation));
+        }
+        result
+    }
+
+    async fn fetch<V: Clone>(
+        cache: &Cache<V>,
+        key: ReadKey,
+        generation: u64,
+        ttl: Option<Duration>,
+        fetch: impl Future<Output = Result<V, ModbusError>>,
+    ) -> Result<V, ModbusException> {
+        let values = fetch.await.map_err(gateway_exception)?;
+
+        let mut state = cache.state.lock().unwrap();
+        if let Some(ttl) = ttl.filter(|_| state.generation(&key) == generation) {
+            let now = Instant::now();
+            state.entries.retain(|_, (expires, _)| *expires > now);
+            state.entries.insert(key, (now + ttl, values.clone()));
+        }
+        Ok(values)
+    }
+
+    /// Checks the write against the rules, sends it and then removes the cached reads it overlaps.
+    async fn write<V>(
+        &self,
+        cache: &Cache<V>,
+        unit_id: u8,
+        table: Table,
+        address: u16,
+        length: usize,
+        write: impl Future<Output = Result<(), ModbusError>>,
+    ) -> Result<(), ModbusException> {
+        let addresses = address..=address.saturating_add((length as u16).saturating_sub(1));
+        let rules = self.write_rules.iter().filter(|rule| rule.unit_id == unit_id && rule.table == table);
+
+        let denied = rules.clone().any(|rule| !rule.allow && overlaps(&rule.addresses, &addresses));
+        let allowed = rules.clone().any(|rule| rule.allow && contains(&rule.addresses, &addresses));
+        let allow_list = rules.clone().any(|rule| rule.allow);
+        if denied || (allow_list && !allowed) {
+            return Err(ModbusException::IllegalDataAddress);
+        }
+
+        write.await.map_err(gateway_exception)?;
+
+        let mut state = cache.state.lock().unwrap();
+        *state.generations.entry((unit_id, table)).or_default() += 1;
+        state
+            .entries
+            .retain(|key, _| !(key.unit_id == unit_id && key.table == table && overlaps(&key.addresses(), &addresses)));
+        Ok(())
+    }
+}
+
+fn contains(outer: &RangeInclusive<u16>, inner: &RangeInclusive<u16>) -> bool {
+    outer.start() <= inner.start() && inner.end() <= outer.end()
+}
+
+fn overlaps(a: &RangeInclusive<u16>, b: &RangeInclusive<u16>) -> bool {
+    a.start() <= b.end() && b.start() <= a.end()
+}
+
+impl<C: ModbusClient + 'static> ModbusTCPServerHandler for ModbusProxy<C> {
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        let key = ReadKey { unit_id: ctx.unit_id, table: Table::Coils, address, length };
+        let values = self.read(&self.bits, key, self.client.read_coils(ctx.unit_id, address, length)).await?;
+        Ok(Cow::Owned(values))
+    }
+
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        let key = ReadKey { unit_id: ctx.unit_id, table: Table::DiscreteInputs, address, length };
+        let values = self.read(&self.bits, key, self.client.read_discrete_inputs(ctx.unit_id, address, length)).await?;
+        Ok(Cow::Owned(values))
+    }
+
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        let key = ReadKey { unit_id: ctx.unit_id, table: Table::InputRegisters, address, length };
+        let values = self.read(&self.registers, key, self.client.read_input_registers(ctx.unit_id, address, length)).await?;
+        Ok(Cow::Owned(values))
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        let key = ReadKey { unit_id: ctx.unit_id, table: Table::HoldingRegisters, address, length };
+        let values = self.read(&self.registers, key, self.client.read_holding_registers(ctx.unit_id, address, length)).await?;
+        Ok(Cow::Owned(values))
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
+        let length = values.len();
+        let write = self.client.write_multiple_coils(ctx.unit_id, address, values);
+        self.write(&self.bits, ctx.unit_id, Table::Coils, address, length, write).await
+    }
+
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        let write = self.client.write_multiple_holding_registers(ctx.unit_id, address, values);
+        self.write(&self.registers, ctx.unit_id, Table::HoldingRegisters, address, values.len(), write).await
+    }
+
+    async fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> Result<(), ModbusException> {
+        let write = self.client.write_single_coils(ctx.unit_id, address, value);
+        self.write(&self.bits, ctx.unit_id, Table::Coils, address, 1, write).await
+    }
+
+    async fn handle_write_single_holding_register(&self, ctx: &RequestContext, address: u16, value: u16) -> Result<(), ModbusException> {
+        let write = self.client.write_single_holding_register(ctx.unit_id, address, value);
+        self.write(&self.registers, ctx.unit_id, Table::HoldingRegisters, address, 1, write).await
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        let write = self.client.mask_write_holding_registers(ctx.unit_id, address, and_mask, or_mask);
+        self.write(&self.registers, ctx.unit_id, Table::HoldingRegisters, address, 1, write).await
+    }
+
+    async fn handle_read_device_identification(&self, ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        let device_identification = self.client.read_device_identification(ctx.unit_id).await.map_err(gateway_exception)?;
+        Ok(Cow::Owned(device_identification))
+    }
+
+    async fn handle_modbus_encapsulated_interface(&self, ctx: &RequestContext, interface_type: u8, data: &[u8]) -> Result<Cow<'_, [u8]>, ModbusException> {
+        let data = self
+            .client
+            .modbus_encapsulated_interface(ctx.unit_id, interface_type, data)
+            .await
+            .map_err(gateway_exception)?;
+        Ok(data.into())
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::Semaphore,
+    time::{self, Instant},
+};
+
+use crate::{
+    middleware::{Middleware, Next},
+    modbus_exception::ModbusException,
+    pdu::{Request, Response},
+    request_context::{Extensions, RequestContext},
+    server::ModbusTCPServerHandler,
+    telemetry,
+};
+
+/// What to do with requests exceeding a [`RateLimit`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Overload {
+    /// Respond with [`ModbusException::ServerDeviceBusy`].
+    #[default]
+    Busy,
+    /// Wait until the request is within the limits. Combine with [`ModbusTCPServerHandler::request_timeout`] to bound the wait.
+    Queue,
+}
+
+#[derive(Clone, Copy)]
+struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+/// A token bucket, starting full.
+struct Bucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate.per_second).min(self.rate.burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate.per_second))
+        }
+    }
+
+    /// Returns a token taken for a request that was rejected by another limit.
+    fn give_back(&mut self) {
+        self.tokens = (self.tokens + 1.0).min(self.rate.burst);
+    }
+}
+
+/// The buckets of a connection, kept in the connection's extensions and keyed by [`RateLimit::id`], so layers don't share buckets.
+#[derive(Default)]
+struct ConnectionBuckets(HashMap<u64, Mutex<Bucket>>);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/**
+ * Middleware limiting the request rate per connection and across all connections, and the number of concurrent requests
+ * across all connections. Applied to a handler with [`crate::Layered`].
+ *
+ * Rates are enforced with token buckets, allowing bursts of requests up to the bucket size.
+ * Requests exceeding a limit are handled as set with [`Self::with_overload`]. Requests rejected by one limit don't count against the others.
+ */
+pub struct RateLimit {
+    id: u64,
+    connection_rate: Option<Rate>,
+    global_bucket: Option<Mutex<Bucket>>,
+    concurrency: Option<Arc<Semaphore>>,
+    overload: Overload,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimit {
+    /// No limits.
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            connection_rate: None,
+            global_bucket: None,
+            concurrency: None,
+            overload: Overload::Busy,
+        }
+    }
+
+    /// Limits each connection to `per_second` requests per second, with bursts of up to `burst` requests.
+    pub fn with_connection_rate(mut self, per_second: f64, burst: u32) -> Self {
+        self.connection_rate = Some(rate(per_second, burst));
+        self
+    }
+
+    /// Limits all connections together to `per_second` requests per second, with bursts of up to `burst` requests.
+    pub fn with_global_rate(mut self, per_second: f64, burst: u32) -> Self {
+        self.global_bucket = Some(Mutex::new(Bucket::new(rate(per_second, burst))));
+        self
+    }
+
+    /// Limits the number of requests handled at the same time across all connections.
+    pub fn with_max_concurrent_requests(mut self, max: usize) -> Self {
+        self.concurrency = Some(Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// What to do with requests exceeding a limit. Default is [`Overload::Busy`].
+    pub fn with_overload(mut self, overload: Overload) -> Self {
+        self.overload = overload;
+        self
+    }
+
+    async fn take(&self, bucket: &Mutex<Bucket>, addr: SocketAddr) -> Result<(), ModbusException> {
+        loop {
+            let wait = match bucket.lock().unwrap().take() {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+            match self.overload {
+                Overload::Busy => {
+                    telemetry::server_rate_limited(addr);
+                    return Err(ModbusException::ServerDeviceBusy);
+                }
+                Overload::Queue => time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+/// Panics if the rate isn't positive, as no request could ever be handled.
+fn rate(per_second: f64, burst: u32) -> Rate {
+    assert!(per_second > 0.0, "Rate must be positive");
+    Rate {
+        per_second,
+        burst: burst.max(1) as f64,
+    }
+}
+
+impl Middleware for RateLimit {
+    async fn accept_connection(&self, _addr: SocketAddr, extensions: &mut Extensions) -> bool {
+        if let Some(rate) = self.connection_rate {
+            if !extensions.contains::<ConnectionBuckets>() {
+                extensions.insert(ConnectionBuckets::default());
+            }
+            let ConnectionBuckets(buckets) = extensions.get_mut().unwrap();
+            buckets.insert(self.id, Mutex::new(Bucket::new(rate)));
+        }
+        true
+    }
+
+    async fn call<H: ModbusTCPServerHandler>(&self, ctx: &RequestContext, request: Request, next: Next<'_, H>) -> Result<Response, ModbusException> {
+        let connection_bucket = ctx.extensions.get::<ConnectionBuckets>().and_then(|ConnectionBuckets(buckets)| buckets.get(&self.id));
+        let buckets = [connection_bucket, self.global_bucket.as_ref()];
+        let give_back = |taken: &[Option<&Mutex<Bucket>>]| {
+            for bucket in taken.iter().flatten() {
+                bucket.lock().unwrap().give_back();
+            }
+        };
+
+        for (i, bucket) in buckets.iter().enumerate() {
+            if let Some(bucket) = bucket {
+                if let Err(exception) = self.take(bucket, ctx.addr).await {
+                    give_back(&buckets[..i]);
+                    return Err(exception);
+                }
+            }
+        }
+
+        let _permit = match &self.concurrency {
+            Some(semaphore) => Some(match self.overload {
+                Overload::Busy => semaphore.clone().try_acquire_owned().map_err(|_| {
+                    give_back(&buckets);
+                    telemetry::server_rate_limited(ctx.addr);
+                    ModbusException::ServerDeviceBusy
+                })?,
+                Overload::Queue => semaphore.clone().acquire_owned().await.expect("Semaphore is never closed"),
+            }),
+            None => None,
+        };
+        next.run(ctx, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket() {
+        let mut bucket = Bucket::new(rate(10.0, 2));
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().unwrap_err() <= Duration::from_millis(100));
+
+        // Half a token was added while waiting.
+        bucket.updated -= Duration::from_millis(50);
+        assert!(bucket.take().unwrap_err() <= Duration::from_millis(50));
+        bucket.updated -= Duration::from_millis(60);
+        assert!(bucket.take().is_ok());
+
+        // Refills up to the burst size.
+        bucket.updated -= Duration::from_secs(10);
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().is_err());
+
+        // Given back tokens are available again, up to the burst size.
+        bucket.give_back();
+        assert!(bucket.take().is_ok());
+        bucket.updated -= Duration::from_secs(10);
+        bucket.give_back();
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().is_ok());
+        assert!(bucket.take().is_err());
+    }
+}
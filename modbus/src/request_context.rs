@@ -0,0 +1,98 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use crate::{function_code::FunctionCode, server_handle::ConnectionId};
+
+/**
+ * A map of values keyed by their type.
+ *
+ * Populated by [`crate::ModbusTCPServerHandler::accept_connection`] and shared by all requests on the connection,
+ * typically with the authenticated identity of the peer or other per-connection state.
+ * Values that need to change after the connection is accepted must use interior mutability.
+ */
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map.remove(&TypeId::of::<T>())?.downcast().ok().map(|value| *value)
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.map.len()).finish()
+    }
+}
+
+/// Information about the request being handled, passed to every request handler.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub addr: SocketAddr,
+    pub connection_id: ConnectionId,
+    pub transaction_id: u16,
+    pub unit_id: u8,
+    /// The function code of the request, e.g. to distinguish single from multiple writes.
+    pub function_code: FunctionCode,
+    /// Values inserted when the connection was accepted.
+    pub extensions: Arc<Extensions>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        #[derive(Debug, PartialEq)]
+        struct User(&'static str);
+
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.insert(User("a")), None);
+        assert_eq!(extensions.insert(5u32), None);
+        assert_eq!(extensions.insert(User("b")), Some(User("a")));
+        assert_eq!(extensions.get::<User>(), Some(&User("b")));
+        *extensions.get_mut::<u32>().unwrap() += 1;
+        assert_eq!(extensions.remove::<u32>(), Some(6));
+        assert!(!extensions.contains::<u32>());
+        assert_eq!(extensions.len(), 1);
+    }
+}
@@ -0,0 +1,305 @@
+//! Framing of Modbus RTU messages: unit id, function code, body and a CRC.
+
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{function_code::FunctionCode, message::FrameError};
+
+/// The maximum length of an RTU frame, including the CRC.
+pub(crate) const FRAME_MAX_LENGTH: usize = 256;
+/// The unit id, the function code and the CRC.
+const FRAME_MIN_LENGTH: usize = 4;
+
+/// A Modbus RTU or ASCII frame, without the checksum.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SerialFrame {
+    pub unit_id: u8,
+    pub function_code: FunctionCode,
+    pub body: Bytes,
+}
+
+/// The length of a frame, as far as it can be told from the start of the frame.
+#[derive(PartialEq, Debug)]
+pub(crate) enum FrameLength {
+    /// More bytes are needed to tell the length.
+    Incomplete,
+    /// The total length of the frame, including the CRC.
+    Known(usize),
+    /// The length can't be told from the content, the end of the frame must be detected by silence on the line.
+    Unknown,
+}
+
+/// The Modbus CRC-16 of the bytes.
+pub(crate) fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in bytes {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+pub(crate) fn encode_frame(unit_id: u8, function_code: FunctionCode, body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(body.len() + 4);
+    frame.push(unit_id);
+    frame.push(function_code.into());
+    frame.extend_from_slice(body);
+    // The CRC is the only little endian value in the protocol.
+    frame.extend(crc16(&frame).to_le_bytes());
+    frame
+}
+
+fn check_frame(frame: &[u8]) -> Result<(), FrameError> {
+    if frame.len() < FRAME_MIN_LENGTH {
+        return Err(FrameError::LengthTooShort(frame.len() as u16));
+    }
+    let (content, crc) = frame.split_at(frame.len() - 2);
+    if crc16(content) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(FrameError::CrcMismatch);
+    }
+    Ok(())
+}
+
+/// Splits a checked frame into unit id, function code and body, without copying the body.
+fn split_frame(mut frame: BytesMut) -> SerialFrame {
+    frame.truncate(frame.len() - 2);
+    let header = frame.split_to(2);
+    SerialFrame {
+        unit_id: header[0],
+        function_code: header[1].into(),
+        body: frame.freeze(),
+    }
+}
+
+/// Checks the CRC and splits the frame into unit id, function code and body.
+pub(crate) fn decode_frame(frame: BytesMut) -> Result<SerialFrame, FrameError> {
+    if frame.len() > FRAME_MAX_LENGTH {
+        return Err(FrameError::LengthTooLong(frame.len().min(u16::MAX.into()) as u16));
+    }
+    check_frame(&frame)?;
+    Ok(split_frame(frame))
+}
+
+/// The length of a request frame starting with `bytes`.
+pub(crate) fn request_length(bytes: &[u8]) -> FrameLength {
+    let Some(&function_code) = bytes.get(1) else {
+        return FrameLength::Incomplete;
+    };
+    match FunctionCode::from(function_code) {
+        FunctionCode::ReadCoils
+        | FunctionCode::ReadDiscreteInputs
+        | FunctionCode::ReadHoldingRegisters
+        | FunctionCode::ReadInputRegisters
+        | FunctionCode::WriteSingleCoil
+        | FunctionCode::WriteSingleHoldingRegister => FrameLength::Known(8),
+        FunctionCode::WriteMultipleCoils | FunctionCode::WriteMultipleHoldingRegisters => match bytes.get(6) {
+            Some(&byte_count) => FrameLength::Known(7 + byte_count as usize + 2),
+            None => FrameLength::Incomplete,
+        },
+        FunctionCode::MaskWriteHoldingRegister => FrameLength::Known(10),
+        // Unit id, function code, MEI type, id code, object id and CRC for Read Device Identification.
+        FunctionCode::ModbusEncapsulatedInterface => match bytes.get(2) {
+            None => FrameLength::Incomplete,
+            Some(0x0E) => FrameLength::Known(7),
+            Some(_) => FrameLength::Unknown,
+        },
+        FunctionCode::Error(_) | FunctionCode::Unknown(_) => FrameLength::Unknown,
+    }
+}
+
+/// The length of a response frame starting with `bytes`.
+pub(crate) fn response_length(bytes: &[u8]) -> FrameLength {
+    let Some(&function_code) = bytes.get(1) else {
+        return FrameLength::Incomplete;
+    };
+    match FunctionCode::from(function_code) {
+        FunctionCode::Error(_) => FrameLength::Known(5),
+        FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs | FunctionCode::ReadHoldingRegisters | FunctionCode::ReadInputRegisters => {
+            match bytes.get(2) {
+                Some(&byte_count) => FrameLength::Known(3 + byte_count as usize + 2),
+                None => FrameLength::Incomplete,
+            }
+        }
+        FunctionCode::WriteSingleCoil
+        | FunctionCode::WriteSingleHoldingRegister
+        | FunctionCode::WriteMultipleCoils
+        | FunctionCode::WriteMultipleHoldingRegisters => FrameLength::Known(8),
+        FunctionCode::MaskWriteHoldingRegister => FrameLength::Known(10),
+        FunctionCode::ModbusEncapsulatedInterface => read_device_identification_length(bytes),
+        FunctionCode::Unknown(_) => FrameLength::Unknown,
+    }
+}
+
+/// Read Device Identification responses are the only encapsulated interface responses with a known layout.
+fn read_device_identification_length(bytes: &[u8]) -> FrameLength {
+    const READ_DEVICE_IDENTIFICATION: u8 = 0x0E;
+    // Unit id, function code, MEI type, id code, conformity level, more follows, next object id, number of objects.
+    const HEADER_LENGTH: usize = 8;
+
+    match bytes.get(2) {
+        None => return FrameLength::Incomplete,
+        Some(&READ_DEVICE_IDENTIFICATION) => {}
+        Some(_) => return FrameLength::Unknown,
+    }
+    let Some(&objects) = bytes.get(HEADER_LENGTH - 1) else {
+        return FrameLength::Incomplete;
+    };
+    let mut length = HEADER_LENGTH;
+    for _ in 0..objects {
+        // Object id, object length, object value.
+        match bytes.get(length + 1) {
+            Some(&object_length) => length += 2 + object_length as usize,
+            None => return FrameLength::Incomplete,
+        }
+    }
+    FrameLength::Known(length + 2)
+}
+
+/**
+ * Sans-IO encoder and decoder of Modbus RTU frames on a byte stream.
+ *
+ * RTU frames are delimited by silence on the line, which a byte buffer doesn't capture. The length of most frames
+ * can be told from their content, which is what [`Self::decode`] relies on. The others are only decoded by
+ * [`Self::decode_idle`], to be called once the line has been silent for 3.5 characters.
+ */
+#[derive(Debug, Clone)]
+pub struct RtuCodec {
+    responses: bool,
+}
+
+impl RtuCodec {
+    /// Decodes requests, as a server does.
+    pub fn requests() -> Self {
+        Self { responses: false }
+    }
+
+    /// Decodes responses, as a client does.
+    pub fn responses() -> Self {
+        Self { responses: true }
+    }
+
+    /// Decodes a frame from the start of `buffer`, removing its bytes. Returns `None` if more bytes are needed.
+    /// On error the first byte is discarded, so decoding again resynchronizes with the stream.
+    pub fn decode(&mut self, buffer: &mut BytesMut) -> Result<Option<SerialFrame>, FrameError> {
+        let length = if self.responses { response_length(buffer) } else { request_length(buffer) };
+        let length = match length {
+            FrameLength::Known(length) if length > FRAME_MAX_LENGTH => {
+                buffer.advance(1);
+                return Err(FrameError::LengthTooLong(length as u16));
+            }
+            FrameLength::Known(length) if length <= buffer.len() => length,
+            FrameLength::Known(length) => {
+                buffer.reserve(length - buffer.len());
+                return Ok(None);
+            }
+            FrameLength::Incomplete | FrameLength::Unknown => return Ok(None),
+        };
+        if let Err(error) = check_frame(&buffer[..length]) {
+            buffer.advance(1);
+            return Err(error);
+        }
+        Ok(Some(split_frame(buffer.split_to(length))))
+    }
+
+    /// Decodes all of `buffer` as a single frame, after the line has gone silent. Returns `None` if `buffer` is empty.
+    pub fn decode_idle(&mut self, buffer: &mut BytesMut) -> Result<Option<SerialFrame>, FrameError> {
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        decode_frame(buffer.split()).map(Some)
+    }
+
+    /// Appends the encoded frame to `buffer`.
+    pub fn encode(&self, frame: &SerialFrame, buffer: &mut BytesMut) -> Result<(), FrameError> {
+        let length = frame.body.len() + FRAME_MIN_LENGTH;
+        if length > FRAME_MAX_LENGTH {
+            return Err(FrameError::LengthTooLong(length.min(u16::MAX.into()) as u16));
+        }
+        buffer.put_slice(&encode_frame(frame.unit_id, frame.function_code, &frame.body));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame() {
+        // Read holding registers 0x006B-0x006D from unit 0x11, from the specification.
+        let frame = encode_frame(0x11, FunctionCode::ReadHoldingRegisters, &[0x00, 0x6B, 0x00, 0x03]);
+        assert_eq!(frame, [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]);
+        assert_eq!(
+            decode_frame(frame[..].into()).unwrap(),
+            SerialFrame {
+                unit_id: 0x11,
+                function_code: FunctionCode::ReadHoldingRegisters,
+                body: Bytes::from_static(&[0x00, 0x6B, 0x00, 0x03])
+            }
+        );
+
+        let mut corrupt = frame.clone();
+        corrupt[3] ^= 1;
+        assert_eq!(decode_frame(corrupt[..].into()), Err(FrameError::CrcMismatch));
+    }
+
+    #[test]
+    fn codec() {
+        let request = SerialFrame {
+            unit_id: 0x01,
+            function_code: FunctionCode::WriteMultipleHoldingRegisters,
+            body: Bytes::from_static(&[0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+        };
+        let mut codec = RtuCodec::requests();
+        let mut stream = BytesMut::new();
+        codec.encode(&request, &mut stream).unwrap();
+        let encoded = stream.clone();
+
+        // Byte by byte, the frame is decoded once complete.
+        let mut buffer = BytesMut::new();
+        for (i, byte) in encoded.iter().enumerate() {
+            buffer.put_u8(*byte);
+            let decoded = codec.decode(&mut buffer).unwrap();
+            assert_eq!(decoded.is_some(), i == encoded.len() - 1);
+        }
+        assert!(buffer.is_empty());
+
+        // A stray byte in front, taken as the unit id of a Read Coils request, fails the CRC and is skipped.
+        let mut buffer = BytesMut::from(&[0x02][..]);
+        buffer.put_slice(&encoded);
+        assert!(codec.decode(&mut buffer).is_err());
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(request));
+
+        // Responses of unknown length need silence on the line.
+        let mut codec = RtuCodec::responses();
+        let mut buffer = BytesMut::from(&encode_frame(0x11, FunctionCode::Unknown(0x41), &[1, 2, 3])[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(codec.decode_idle(&mut buffer).unwrap().unwrap().body, [1, 2, 3][..]);
+        assert!(buffer.is_empty());
+
+        let mut buffer = BytesMut::from(&encode_frame(0x11, FunctionCode::Unknown(0x41), &[0; 300])[..]);
+        assert_eq!(codec.decode_idle(&mut buffer), Err(FrameError::LengthTooLong(304)));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn length() {
+        assert_eq!(response_length(&[0x11]), FrameLength::Incomplete);
+        assert_eq!(response_length(&[0x11, 0x83]), FrameLength::Known(5));
+        assert_eq!(response_length(&[0x11, 0x03]), FrameLength::Incomplete);
+        assert_eq!(response_length(&[0x11, 0x03, 0x06]), FrameLength::Known(11));
+        assert_eq!(response_length(&[0x11, 0x10]), FrameLength::Known(8));
+        assert_eq!(response_length(&[0x11, 0x16]), FrameLength::Known(10));
+        assert_eq!(response_length(&[0x11, 0x41]), FrameLength::Unknown);
+
+        let device_identification = [0x11, 0x2B, 0x0E, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x01, b'a', 0x01, 0x02, b'b', b'c'];
+        for i in 2..13 {
+            assert_eq!(response_length(&device_identification[..i]), FrameLength::Incomplete);
+        }
+        // Known once the length of the last object is received.
+        assert_eq!(response_length(&device_identification[..13]), FrameLength::Known(17));
+        assert_eq!(response_length(&[0x11, 0x2B, 0x0D]), FrameLength::Unknown);
+    }
+}
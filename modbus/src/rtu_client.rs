@@ -0,0 +1,240 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::Mutex,
+    time::{self, Instant},
+};
+
+use crate::{
+    bit_buf::BitBuf,
+    client::ModbusError,
+    client_ops::{self, response_body, Transport},
+    function_code::FunctionCode,
+    modbus_encapsulated_interface::DeviceIdentification,
+    rtu::{self, FrameLength, RtuCodec, SerialFrame, FRAME_MAX_LENGTH},
+    telemetry::{self, Transaction},
+    views::Bits,
+};
+
+/// How long the line must be silent to end a response whose length can't be told from its content.
+const FRAME_END_SILENCE: Duration = Duration::from_millis(20);
+
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+struct Bus {
+    stream: Box<dyn Stream>,
+    /// When the last transaction on the bus ended.
+    last_transaction: Option<Instant>,
+}
+
+/**
+ * A Modbus RTU client, for a serial line or an RTU over TCP connection.
+ *
+ * The bus is shared by all clones of the client, and requests are sent one at a time.
+ * Every request fails with [`ModbusError::Timeout`] if no response is received within the configured timeout.
+ * Broadcasts to unit 0 aren't supported.
+ */
+#[derive(Clone)]
+pub struct ModbusRTUClient {
+    bus: Arc<Mutex<Bus>>,
+    timeout: Duration,
+    turnaround_delay: Duration,
+}
+
+impl ModbusRTUClient {
+    /// Creates a client from any byte stream, e.g. a serial port or a TCP connection to a serial device server.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self {
+            bus: Arc::new(Mutex::new(Bus {
+                stream: Box::new(stream),
+                last_transaction: None,
+            })),
+            timeout: Duration::from_secs(1),
+            turnaround_delay: Duration::ZERO,
+        }
+    }
+
+    /// Opens a serial port with 8 data bits, no parity and 1 stop bit.
+    #[cfg(feature = "serial")]
+    pub fn open(path: &str, baud_rate: u32) -> Result<Self, ModbusError> {
+        use tokio_serial::SerialPortBuilderExt;
+
+        let port = tokio_serial::new(path, baud_rate)
+            .open_native_async()
+            .map_err(|error| ModbusError::IO(Arc::new(error.into())))?;
+        Ok(Self::new(port).with_turnaround_delay(frame_gap(baud_rate)))
+    }
+
+    /// How long to wait for a response. Default is 1 second.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The minimum silence on the bus between the end of one transaction and the start of the next. Default is none.
+    pub fn with_turnaround_delay(mut self, turnaround_delay: Duration) -> Self {
+        self.turnaround_delay = turnaround_delay;
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn turnaround_delay(&self) -> Duration {
+        self.turnaround_delay
+    }
+
+    pub async fn read_coils(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        client_ops::read_coils(self, unit_id, address, length).await
+    }
+
+    pub async fn read_discrete_inputs(&self, unit_id: u8, address: u16, length: u16) -> Result<BitBuf, ModbusError> {
+        client_ops::read_discrete_inputs(self, unit_id, address, length).await
+    }
+
+    pub async fn read_input_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        client_ops::read_input_registers(self, unit_id, address, length).await
+    }
+
+    pub async fn read_holding_registers(&self, unit_id: u8, address: u16, length: u16) -> Result<Vec<u16>, ModbusError> {
+        client_ops::read_holding_registers(self, unit_id, address, length).await
+    }
+
+    pub async fn write_single_coils(&self, unit_id: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        client_ops::write_single_coils(self, unit_id, address, value).await
+    }
+
+    pub async fn write_single_holding_register(&self, unit_id: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        client_ops::write_single_holding_register(self, unit_id, address, value).await
+    }
+
+    pub async fn write_multiple_coils<'a>(&self, unit_id: u8, address: u16, values: impl Into<Bits<'a>>) -> Result<(), ModbusError> {
+        client_ops::write_multiple_coils(self, unit_id, address, values.into()).await
+    }
+
+    pub async fn write_multiple_holding_registers(&self, unit_id: u8, address: u16, values: &[u16]) -> Result<(), ModbusError> {
+        client_ops::write_multiple_holding_registers(self, unit_id, address, values).await
+    }
+
+    pub async fn mask_write_holding_registers(&self, unit_id: u8, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusError> {
+        client_ops::mask_write_holding_registers(self, unit_id, address, and_mask, or_mask).await
+    }
+
+    pub async fn modbus_encapsulated_interface(&self, unit_id: u8, interface_type: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        client_ops::modbus_encapsulated_interface(self, unit_id, interface_type, data).await
+    }
+
+    pub async fn read_device_identification(&self, unit_id: u8) -> Result<DeviceIdentification<'static>, ModbusError> {
+        client_ops::read_device_identification(self, unit_id).await
+    }
+
+    async fn exchange(&self, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> Result<Bytes, ModbusError> {
+        let mut bus = self.bus.lock().await;
+
+        if let Some(last_transaction) = bus.last_transaction {
+            time::sleep_until(last_transaction + self.turnaround_delay).await;
+        }
+
+        let frame = rtu::encode_frame(unit_id, function_code, &body);
+        let result = async {
+            bus.stream.write_all(&frame).await.map_err(io_error)?;
+            bus.stream.flush().await.map_err(io_error)?;
+            match time::timeout(self.timeout, read_frame(&mut bus.stream)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    telemetry::client_timeout();
+                    Err(ModbusError::Timeout)
+                }
+            }
+        }
+        .await;
+
+        if result.is_err() {
+            // Discard the rest of a late or broken response, so it isn't taken as the response to the next request.
+            discard_input(&mut bus.stream).await;
+        }
+        bus.last_transaction = Some(Instant::now());
+
+        let response = result?;
+        if response.unit_id != unit_id {
+            return Err(ModbusError::InvalidResponse("Unit id mismatch"));
+        }
+        response_body(function_code, response.function_code, response.body)
+    }
+}
+
+impl Transport for ModbusRTUClient {
+    async fn send_request(&self, unit_id: u8, function_code: FunctionCode, body: Vec<u8>) -> Result<Bytes, ModbusError> {
+        if unit_id == 0 {
+            return Err(ModbusError::ArgumentsOutOfRange("Broadcast isn't supported"));
+        }
+
+        let transaction = Transaction::client(0, unit_id, function_code, &body);
+        let result = transaction.instrument(self.exchange(unit_id, function_code, body)).await;
+        transaction.finish((&result).into());
+        result
+    }
+}
+
+/// Reads into a buffer, so a response is received in as few reads as the line allows.
+async fn read_frame(stream: &mut Box<dyn Stream>) -> Result<SerialFrame, ModbusError> {
+    let mut codec = RtuCodec::responses();
+    let mut buffer = BytesMut::with_capacity(FRAME_MAX_LENGTH);
+    loop {
+        let function_code = buffer.get(1).copied();
+        let decoded = if rtu::response_length(&buffer) == FrameLength::Unknown {
+            while let Ok(read) = time::timeout(FRAME_END_SILENCE, stream.read_buf(&mut buffer)).await {
+                if read.map_err(io_error)? == 0 {
+                    return Err(io_error(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+                if buffer.len() > FRAME_MAX_LENGTH {
+                    return Err(ModbusError::InvalidResponse("Frame too long"));
+                }
+            }
+            codec.decode_idle(&mut buffer)
+        } else {
+            codec.decode(&mut buffer)
+        };
+        match decoded {
+            Ok(Some(frame)) => return Ok(frame),
+            Ok(None) => {}
+            Err(error) => {
+                if let Some(function_code) = function_code {
+                    telemetry::client_decode_failed(function_code.into());
+                }
+                return Err(error.into());
+            }
+        }
+        if stream.read_buf(&mut buffer).await.map_err(io_error)? == 0 {
+            return Err(io_error(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+    }
+}
+
+async fn discard_input(stream: &mut Box<dyn Stream>) {
+    let mut buf = [0; FRAME_MAX_LENGTH];
+    while let Ok(Ok(1..)) = time::timeout(FRAME_END_SILENCE, stream.read(&mut buf)).await {}
+}
+
+/// The silent interval of 3.5 characters that separates frames, fixed above 19200 baud as recommended by the specification.
+#[cfg(feature = "serial")]
+fn frame_gap(baud_rate: u32) -> Duration {
+    // 11 bits per character.
+    if baud_rate > 19200 {
+        Duration::from_micros(1750)
+    } else {
+        Duration::from_micros(3_500_000 * 11 / baud_rate.max(1) as u64)
+    }
+}
+
+fn io_error(error: std::io::Error) -> ModbusError {
+    ModbusError::IO(Arc::new(error))
+}
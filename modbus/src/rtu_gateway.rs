@@ -0,0 +1,118 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    bit_buf::BitBuf, client::gateway_exception, modbus_encapsulated_interface::DeviceIdentification, modbus_exception::ModbusException,
+    request_context::RequestContext, rtu_client::ModbusRTUClient, server::ModbusTCPServerHandler, views::Bits,
+};
+
+/**
+ * A server handler that forwards Modbus/TCP requests to devices on RTU buses, making a [`crate::ModbusTCPServer`] a TCP/RTU gateway.
+ *
+ * Each TCP unit id maps to a bus and the unit id of the device on that bus.
+ * Requests on the same bus are sent one at a time, with the turnaround delay of the [`ModbusRTUClient`] between them.
+ *
+ * Requests to units without a bus, or to a bus that fails, are answered with [`ModbusException::GatewayPathUnavailable`].
+ * Requests the device doesn't answer in time, or answers with a broken response,
+ * are answered with [`ModbusException::GatewayTargetDeviceFailedToRespond`].
+ * Exceptions from the device are passed through.
+ */
+#[derive(Clone, Default)]
+pub struct RtuGateway {
+    units: HashMap<u8, (ModbusRTUClient, u8)>,
+}
+
+impl RtuGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forwards requests to `unit_id` to the device `rtu_unit_id` on the bus of `client`.
+    pub fn with_unit(mut self, unit_id: u8, client: ModbusRTUClient, rtu_unit_id: u8) -> Self {
+        self.units.insert(unit_id, (client, rtu_unit_id));
+        self
+    }
+
+    /// Forwards requests to each of the units to the device with the same unit id on the bus of `client`.
+    pub fn with_units(mut self, unit_ids: impl IntoIterator<Item = u8>, client: ModbusRTUClient) -> Self {
+        for unit_id in unit_ids {
+            self.units.insert(unit_id, (client.clone(), unit_id));
+        }
+        self
+    }
+
+    fn route(&self, unit_id: u8) -> Result<(&ModbusRTUClient, u8), ModbusException> {
+        self.units
+            .get(&unit_id)
+            .map(|(client, rtu_unit_id)| (client, *rtu_unit_id))
+            .ok_or(ModbusException::GatewayPathUnavailable)
+    }
+}
+
+impl ModbusTCPServerHandler for RtuGateway {
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let values = client.read_coils(unit_id, address, length).await.map_err(gateway_exception)?;
+        Ok(values.into())
+    }
+
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let values = client.read_discrete_inputs(unit_id, address, length).await.map_err(gateway_exception)?;
+        Ok(values.into())
+    }
+
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let values = client.read_input_registers(unit_id, address, length).await.map_err(gateway_exception)?;
+        Ok(values.into())
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let values = client.read_holding_registers(unit_id, address, length).await.map_err(gateway_exception)?;
+        Ok(values.into())
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        client.write_multiple_coils(unit_id, address, values).await.map_err(gateway_exception)
+    }
+
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        client.write_multiple_holding_registers(unit_id, address, values).await.map_err(gateway_exception)
+    }
+
+    async fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> Result<(), ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        client.write_single_coils(unit_id, address, value).await.map_err(gateway_exception)
+    }
+
+    async fn handle_write_single_holding_register(&self, ctx: &RequestContext, address: u16, value: u16) -> Result<(), ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        client.write_single_holding_register(unit_id, address, value).await.map_err(gateway_exception)
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        client
+            .mask_write_holding_registers(unit_id, address, and_mask, or_mask)
+            .await
+            .map_err(gateway_exception)
+    }
+
+    async fn handle_read_device_identification(&self, ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let device_identification = client.read_device_identification(unit_id).await.map_err(gateway_exception)?;
+        Ok(Cow::Owned(device_identification))
+    }
+
+    async fn handle_modbus_encapsulated_interface(&self, ctx: &RequestContext, interface_type: u8, data: &[u8]) -> Result<Cow<'_, [u8]>, ModbusException> {
+        let (client, unit_id) = self.route(ctx.unit_id)?;
+        let data = client
+            .modbus_encapsulated_interface(unit_id, interface_type, data)
+            .await
+            .map_err(gateway_exception)?;
+        Ok(data.into())
+    }
+}
@@ -0,0 +1,119 @@
+//! [`serde`] implementations that can't be derived.
+
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    bit_buf::BitBuf,
+    views::{Bits, Registers},
+};
+
+/// Bits are serialized as a sequence of booleans.
+impl Serialize for Bits<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl Serialize for BitBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<bool>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl Serialize for Registers<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// The private objects of a [`crate::DeviceIdentification`], with values as hex strings or as bytes.
+pub(crate) mod objects {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(objects: &BTreeMap<u8, Cow<'_, [u8]>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(objects.iter().map(|(id, value)| (id, Hex(value))))
+    }
+
+    pub fn deserialize<'de, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<u8, Cow<'a, [u8]>>, D::Error> {
+        let objects = BTreeMap::<u8, HexBuf>::deserialize(deserializer)?;
+        Ok(objects.into_iter().map(|(id, value)| (id, value.0.into())).collect())
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl Serialize for Hex<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+}
+
+struct HexBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for HexBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HexVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(HexVisitor)
+        }
+    }
+}
+
+struct HexVisitor;
+
+impl<'de> Visitor<'de> for HexVisitor {
+    type Value = HexBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a hex string or bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<HexBuf, E> {
+        let digit = |c: u8| (c as char).to_digit(16).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self));
+        if !value.len().is_multiple_of(2) {
+            return Err(E::invalid_length(value.len(), &"an even number of hex digits"));
+        }
+        let bytes = value.as_bytes().chunks_exact(2).map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8));
+        bytes.collect::<Result<_, E>>().map(HexBuf)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<HexBuf, E> {
+        Ok(HexBuf(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<HexBuf, E> {
+        Ok(HexBuf(value))
+    }
+
+    /// Formats without a bytes type encode bytes as a sequence.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<HexBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(HexBuf(bytes))
+    }
+}
@@ -1,33 +1,85 @@
-use std::{borrow::Cow, collections::HashMap, future::Future, marker::PhantomData, net::SocketAddr, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    pin::pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use tokio::{
     net::TcpListener,
-    sync::{Mutex, Semaphore},
-    task::JoinHandle,
+    select,
+    sync::{watch, Semaphore},
+    task::JoinSet,
+    time::{self, Instant},
 };
 
 use crate::{
+    bit_buf::BitBuf,
+    codec::CodecError,
     connection::Connection,
     consts::*,
     encoding::{Decodable, Encodable},
     function_code::FunctionCode,
-    message::{Message, MSG_MAX_LENGTH},
+    message::{Frame, FrameError, MSG_MAX_LENGTH},
     messages::*,
     modbus_encapsulated_interface::*,
     modbus_exception::ModbusException,
+    request_context::{Extensions, RequestContext},
+    server_handle::{ConnectionId, Control, ModbusTCPServerHandle, ServerState},
+    telemetry::{self, Transaction},
+    views::Bits,
 };
 
+/// Why a connection was closed, passed to [`ModbusTCPServerHandler::disconnected`].
+#[derive(Debug, Clone)]
+pub enum DisconnectReason {
+    /// The client closed the connection.
+    Closed,
+    /// Reading from the connection failed.
+    IO(Arc<io::Error>),
+    /// The client sent a corrupt frame. See [`ModbusTCPServerHandler::resync_frames`].
+    Frame(FrameError),
+    /// The connection was idle for longer than [`ModbusTCPServerHandler::idle_timeout`].
+    IdleTimeout,
+    /// The server closed the connection, through the [`ModbusTCPServerHandle`] or to make room for a new connection.
+    Server,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "Closed by client"),
+            Self::IO(error) => write!(f, "IO error: {}", error),
+            Self::Frame(error) => write!(f, "Corrupt frame: {}", error),
+            Self::IdleTimeout => write!(f, "Idle timeout"),
+            Self::Server => write!(f, "Closed by server"),
+        }
+    }
+}
+
 /**
  * Handlers to be implemented by servers.
  * Default implementation is to respond to requests with [`ModbusException::IllegalFunction`].
  */
 pub trait ModbusTCPServerHandler: Send + Sync + 'static {
     /// Whether to accept a new connection. Default is to always accept.
+    /// Values inserted into `extensions` are available to all requests on the connection through [`RequestContext::extensions`].
     #[allow(unused_variables)]
-    fn accept_connection(&self, addr: SocketAddr) -> impl Future<Output = bool> + Send {
+    fn accept_connection(&self, addr: SocketAddr, extensions: &mut Extensions) -> impl Future<Output = bool> + Send {
         async { true }
     }
     /// The maximum number of concurrent connections.
+    /// Connections beyond the limit get a [`ModbusException::ServerDeviceBusy`] response to their first request and are then closed.
+    /// Once as many rejected connections are waiting for their first request, further connections are closed without a response.
     fn max_concurrent_connections(&self) -> usize {
         100
     }
@@ -35,35 +87,50 @@ pub trait ModbusTCPServerHandler: Send + Sync + 'static {
     fn max_concurrent_requests(&self) -> usize {
         10
     }
+    /// Close connections that have had no requests in flight for this long. Default is to never close idle connections.
+    fn idle_timeout(&self) -> Option<Duration> {
+        None
+    }
+    /// Respond with [`ModbusException::ServerDeviceBusy`] if a handler takes longer than this. Default is no timeout.
+    fn request_timeout(&self) -> Option<Duration> {
+        None
+    }
+    /// When the connection limit is reached, close the connection that has been idle the longest to make room for the new one.
+    /// Default is to reject the new connection.
+    fn evict_idle_connection_when_full(&self) -> bool {
+        false
+    }
+    /// After a corrupt frame header, skip bytes until a valid header is found instead of closing the connection.
+    /// Default is to close the connection, as the stream can't be trusted.
+    fn resync_frames(&self) -> bool {
+        false
+    }
     #[allow(unused_variables)]
-    fn disconnected(&self, addr: SocketAddr) -> impl Future<Output = ()> + Send {
+    fn disconnected(&self, addr: SocketAddr, reason: &DisconnectReason) -> impl Future<Output = ()> + Send {
         async {}
     }
     #[allow(unused_variables)]
     fn handle_read_coils(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
         length: u16,
-    ) -> impl Future<Output = Result<Cow<'_, [bool]>, ModbusException>> + Send {
+    ) -> impl Future<Output = Result<Cow<'_, BitBuf>, ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
     #[allow(unused_variables)]
     fn handle_read_discrete_inputs(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
         length: u16,
-    ) -> impl Future<Output = Result<Cow<'_, [bool]>, ModbusException>> + Send {
+    ) -> impl Future<Output = Result<Cow<'_, BitBuf>, ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
     #[allow(unused_variables)]
     fn handle_read_input_registers(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
         length: u16,
     ) -> impl Future<Output = Result<Cow<'_, [u16]>, ModbusException>> + Send {
@@ -72,46 +139,73 @@ pub trait ModbusTCPServerHandler: Send + Sync + 'static {
     #[allow(unused_variables)]
     fn handle_read_holding_registers(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
         length: u16,
     ) -> impl Future<Output = Result<Cow<'_, [u16]>, ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
+    /// Write Multiple Coils, and Write Single Coil unless [`Self::handle_write_single_coil`] is implemented.
     #[allow(unused_variables)]
     fn handle_write_coils(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
-        values: &[bool],
+        values: Bits<'_>,
     ) -> impl Future<Output = Result<(), ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
+    /// Write Multiple Registers, and Write Single Register unless [`Self::handle_write_single_holding_register`] is implemented.
     #[allow(unused_variables)]
     fn handle_write_holding_registers(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         address: u16,
         values: &[u16],
     ) -> impl Future<Output = Result<(), ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
-    #[allow(unused_variables)]
-    fn handle_read_device_identification(
+    /// Write Single Coil. Default is to call [`Self::handle_write_coils`] with a single value.
+    fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> impl Future<Output = Result<(), ModbusException>> + Send {
+        async move { self.handle_write_coils(ctx, address, Bits::from(&[value])).await }
+    }
+    /// Write Single Register. Default is to call [`Self::handle_write_holding_registers`] with a single value.
+    fn handle_write_single_holding_register(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
-    ) -> impl Future<Output = Result<Cow<'_, DeviceIdentification<'_>>, ModbusException>> + Send {
+        ctx: &RequestContext,
+        address: u16,
+        value: u16,
+    ) -> impl Future<Output = Result<(), ModbusException>> + Send {
+        async move { self.handle_write_holding_registers(ctx, address, &[value]).await }
+    }
+    /**
+     * Mask Write Register, setting the register to `(current & and_mask) | (or_mask & !and_mask)`.
+     *
+     * Default is to call [`Self::handle_read_holding_registers`] followed by [`Self::handle_write_holding_registers`].
+     * That isn't atomic, so implement this to avoid racing with concurrent writers.
+     */
+    fn handle_mask_write_holding_register(
+        &self,
+        ctx: &RequestContext,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> impl Future<Output = Result<(), ModbusException>> + Send {
+        async move {
+            let current_value = self.handle_read_holding_registers(ctx, address, 1).await?;
+            validate_output(current_value.len(), 1)?;
+            let value = (current_value[0] & and_mask) | (or_mask & (!and_mask));
+            self.handle_write_holding_registers(ctx, address, &[value]).await
+        }
+    }
+    #[allow(unused_variables)]
+    fn handle_read_device_identification(&self, ctx: &RequestContext) -> impl Future<Output = Result<Cow<'_, DeviceIdentification<'_>>, ModbusException>> + Send {
         async { Err(ModbusException::IllegalFunction) }
     }
     #[allow(unused_variables)]
     fn handle_modbus_encapsulated_interface(
         &self,
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         interface_type: u8,
         data: &[u8],
     ) -> impl Future<Output = Result<Cow<'_, [u8]>, ModbusException>> + Send {
@@ -123,47 +217,113 @@ pub struct ModbusTCPServer<T> {
     phantom: PhantomData<T>,
 }
 
+/// How long a rejected connection is given to send its first request.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+
 impl<T> ModbusTCPServer<T>
 where
     T: ModbusTCPServerHandler,
 {
-    pub fn run(listener: TcpListener, handler: Arc<T>) -> JoinHandle<()> {
-        tokio::spawn(async move {
-            let connection_count = Arc::new(Mutex::new(0usize));
-
-            loop {
-                if let Ok((stream, addr)) = listener.accept().await {
-                    let max_connections = handler.max_concurrent_connections();
+    /// Starts accepting connections in a background task.
+    pub fn run(listener: TcpListener, handler: Arc<T>) -> ModbusTCPServerHandle {
+        let state = Arc::new(ServerState::new());
+        let shutdown = state.shutdown_receiver();
+        tokio::spawn(Self::accept(listener, handler, state.clone(), shutdown));
+        ModbusTCPServerHandle::new(state)
+    }
 
-                    let mut cnt = connection_count.lock().await;
+    async fn accept(listener: TcpListener, handler: Arc<T>, state: Arc<ServerState>, mut shutdown: watch::Receiver<Option<Instant>>) {
+        let connection_count = Arc::new(AtomicUsize::new(0));
+        let mut tasks = JoinSet::new();
+        let mut rejected = JoinSet::new();
+
+        loop {
+            let (stream, addr) = select! {
+                result = listener.accept() => match result {
+                    Ok(result) => result,
+                    Err(_) => continue,
+                },
+                _ = shutdown.changed() => break,
+            };
+
+            while tasks.try_join_next().is_some() {}
+            while rejected.try_join_next().is_some() {}
+
+            let connection = Connection::new(stream, handler.resync_frames());
+
+            // An evicted connection still holds its slot until it has closed, so the limit may briefly be exceeded.
+            let max_connections = handler.max_concurrent_connections();
+            let admitted = if connection_count
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |cnt| (cnt < max_connections).then_some(cnt + 1))
+                .is_ok()
+            {
+                true
+            } else if handler.evict_idle_connection_when_full() && state.evict_idle() {
+                connection_count.fetch_add(1, Ordering::AcqRel);
+                true
+            } else {
+                false
+            };
+
+            if !admitted {
+                telemetry::server_connection_rejected(addr);
+                // Rejected connections are only answered up to the connection limit, further ones are closed right away.
+                if rejected.len() < max_connections {
+                    rejected.spawn(Self::reject(connection));
+                }
+                continue;
+            }
 
-                    if *cnt >= max_connections {
-                        continue;
-                    }
+            let handler = handler.clone();
+            let connection_count = connection_count.clone();
+            let state = state.clone();
+
+            tasks.spawn(async move {
+                let mut extensions = Extensions::new();
+                if handler.accept_connection(addr, &mut extensions).await {
+                    let (id, control) = state.register(addr);
+                    telemetry::server_connected(addr);
+                    let reason = Self::process(connection, addr, Arc::new(extensions), &handler, &state, id, control).await;
+                    state.unregister(id);
+                    telemetry::server_disconnected(addr, &reason);
+                    handler.disconnected(addr, &reason).await;
+                }
+                connection_count.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
 
-                    if !handler.accept_connection(addr).await {
-                        continue;
-                    }
+        drop(listener);
+        drop(rejected);
 
-                    *cnt = cnt.saturating_add(1);
-                    drop(cnt);
+        let deadline = shutdown.borrow().unwrap_or_else(Instant::now);
+        state.control_all(Control::Drain(deadline));
+        while tasks.join_next().await.is_some() {}
 
-                    let connection = Connection::new(stream);
-                    let handler = handler.clone();
-                    let connection_count = connection_count.clone();
+        state.set_finished();
+    }
 
-                    tokio::spawn(async move {
-                        Self::process(connection, addr, &handler).await;
-                        handler.disconnected(addr).await;
-                        let mut cnt = connection_count.lock().await;
-                        *cnt = cnt.saturating_sub(1);
-                    });
-                }
-            }
-        })
+    /// Responds to the first request with [`ModbusException::ServerDeviceBusy`] and closes the connection.
+    async fn reject(connection: Connection) {
+        if let Ok(Ok(Some(msg))) = time::timeout(REJECT_TIMEOUT, connection.read_message()).await {
+            let res_msg = Frame {
+                function_code: msg.function_code.as_err(),
+                body: ExceptionMessage::from(ModbusException::ServerDeviceBusy).encode_to_bytes().unwrap().into(),
+                ..msg
+            };
+            _ = time::timeout(REJECT_TIMEOUT, connection.write_message(&res_msg)).await;
+        }
+        _ = connection.shutdown().await;
     }
 
-    async fn process(connection: Connection, addr: SocketAddr, handler: &Arc<T>) {
+    async fn process(
+        connection: Connection,
+        addr: SocketAddr,
+        extensions: Arc<Extensions>,
+        handler: &Arc<T>,
+        state: &Arc<ServerState>,
+        id: ConnectionId,
+        mut control: watch::Receiver<Control>,
+    ) -> DisconnectReason {
         let connection = Arc::new(connection);
 
         let limiter = Arc::new(Semaphore::new(match handler.max_concurrent_requests() {
@@ -171,80 +331,162 @@ where
             v => v,
         }));
 
-        while let Ok(Some(msg)) = connection.read_message().await {
-            let permit = limiter.clone().acquire_owned().await.unwrap();
+        let idle_timeout = handler.idle_timeout();
+        let request_timeout = handler.request_timeout();
+
+        let mut tasks = JoinSet::new();
+
+        let reason = 'read: loop {
+            // The same read is polled across idle checks, so it keeps the reader locked until a message arrives.
+            let mut read = pin!(connection.read_message());
+            let msg = loop {
+                // Requests in flight keep the connection alive, so check again after another timeout.
+                let idle_deadline = idle_timeout.map(|timeout| state.idle_since(id).unwrap_or_else(Instant::now) + timeout);
+                select! {
+                    result = &mut read => match result {
+                        Ok(Some(msg)) => break msg,
+                        Ok(None) => break 'read DisconnectReason::Closed,
+                        Err(CodecError::IO(error)) => break 'read DisconnectReason::IO(Arc::new(error)),
+                        Err(CodecError::Frame(error)) => break 'read DisconnectReason::Frame(error),
+                    },
+                    Ok(()) = control.changed() => break 'read DisconnectReason::Server,
+                    _ = time::sleep_until(idle_deadline.unwrap_or_else(Instant::now)), if idle_deadline.is_some() => {
+                        if state.idle_since(id).is_some_and(|since| since + idle_timeout.unwrap() <= Instant::now()) {
+                            telemetry::server_idle_timeout(addr);
+                            break 'read DisconnectReason::IdleTimeout;
+                        }
+                    }
+                }
+            };
+            let permit = select! {
+                permit = limiter.clone().acquire_owned() => permit.unwrap(),
+                Ok(()) = control.changed() => break DisconnectReason::Server,
+            };
+
+            while tasks.try_join_next().is_some() {}
+
+            state.request_started(id);
+
+            let ctx = RequestContext {
+                addr,
+                connection_id: id,
+                transaction_id: msg.transaction_id,
+                unit_id: msg.unit_id,
+                function_code: msg.function_code,
+                extensions: extensions.clone(),
+            };
             let connection = connection.clone();
             let handler = handler.clone();
-            tokio::spawn(async move {
-                let result = Self::handle_request(&msg, addr, &handler).await;
-
-                let res_msg = Message {
+            let state = state.clone();
+            tasks.spawn(async move {
+                let transaction = Transaction::server(addr, msg.transaction_id, msg.unit_id, msg.function_code, &msg.body);
+                let result = transaction
+                    .instrument(async {
+                        let request = Self::handle_request(&msg, &ctx, &handler);
+                        match request_timeout {
+                            None => request.await,
+                            Some(timeout) => time::timeout(timeout, request).await.unwrap_or_else(|_| {
+                                telemetry::server_request_timeout(msg.function_code);
+                                Err(ModbusException::ServerDeviceBusy)
+                            }),
+                        }
+                    })
+                    .await;
+                transaction.finish((&result).into());
+
+                let res_msg = Frame {
                     function_code: if result.is_err() {
                         msg.function_code.as_err()
                     } else {
                         msg.function_code
                     },
                     body: match result {
-                        Ok(body) => body,
-                        Err(code) => ExceptionMessage::from(code).encode_to_bytes().unwrap(),
+                        Ok(body) => body.into(),
+                        Err(code) => ExceptionMessage::from(code).encode_to_bytes().unwrap().into(),
                     },
                     ..msg
                 };
 
-                _ = connection.write_message(&res_msg).await; // Do something?
+                if let Err(error) = connection.write_message(&res_msg).await {
+                    telemetry::server_write_failed(addr, &error);
+                }
 
+                state.request_finished(id);
                 drop(permit);
             });
+        };
+
+        // Let in-flight requests finish, unless the connection is aborted or the drain deadline passes.
+        let drained = async { while tasks.join_next().await.is_some() {} };
+        tokio::pin!(drained);
+        loop {
+            let deadline = match *control.borrow_and_update() {
+                Control::Open => None,
+                Control::Drain(deadline) => Some(deadline),
+                Control::Abort => break,
+            };
+            select! {
+                _ = &mut drained => break,
+                Ok(()) = control.changed() => continue,
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => break,
+            }
         }
+
+        _ = connection.shutdown().await;
+        reason
     }
 
-    async fn handle_request(msg: &Message, addr: SocketAddr, handler: &Arc<T>) -> Result<Vec<u8>, ModbusException> {
+    pub(crate) async fn handle_request(msg: &Frame, ctx: &RequestContext, handler: &Arc<T>) -> Result<Vec<u8>, ModbusException> {
         let bytes = match msg.function_code {
             FunctionCode::ReadCoils => {
-                let req = ReadCoilsRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::read_coils(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: ReadCoilsRequest = decode_request(msg.function_code, &msg.body)?;
+                let values = Self::read_coils(ctx, &req, handler).await?;
+                ReadCoilsResponse { values: values.as_bits() }.encode_to_bytes()
             }
             FunctionCode::ReadDiscreteInputs => {
-                let req = ReadDiscreteInputsRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::read_discrete_inputs(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: ReadDiscreteInputsRequest = decode_request(msg.function_code, &msg.body)?;
+                let values = Self::read_discrete_inputs(ctx, &req, handler).await?;
+                ReadDiscreteInputsResponse { values: values.as_bits() }.encode_to_bytes()
             }
             FunctionCode::ReadInputRegisters => {
-                let req = ReadInputRegistersRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::read_input_registers(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: ReadInputRegistersRequest = decode_request(msg.function_code, &msg.body)?;
+                let values = Self::read_input_registers(ctx, &req, handler).await?;
+                ReadInputRegistersResponse { values: values[..].into() }.encode_to_bytes()
             }
             FunctionCode::ReadHoldingRegisters => {
-                let req = ReadHoldingRegistersRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::read_holding_registers(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: ReadHoldingRegistersRequest = decode_request(msg.function_code, &msg.body)?;
+                let values = Self::read_holding_registers(ctx, &req, handler).await?;
+                ReadHoldingRegistersResponse { values: values[..].into() }.encode_to_bytes()
             }
             FunctionCode::WriteSingleCoil => {
-                let req = WriteSingleCoilRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::write_single_coil(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: WriteSingleCoilRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::write_single_coil(ctx, &req, handler).await?.encode_to_bytes()
             }
             FunctionCode::WriteSingleHoldingRegister => {
-                let req = WriteSingleHoldingRegisterRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::write_single_holding_register(addr, msg.unit_id, &req, handler)
+                let req: WriteSingleHoldingRegisterRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::write_single_holding_register(ctx, &req, handler)
                     .await?
                     .encode_to_bytes()
             }
             FunctionCode::WriteMultipleCoils => {
-                let req = WriteMultipleCoilsRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::write_multiple_coils(addr, msg.unit_id, &req, handler).await?.encode_to_bytes()
+                let req: WriteMultipleCoilsRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::write_multiple_coils(ctx, &req, handler).await?.encode_to_bytes()
             }
             FunctionCode::WriteMultipleHoldingRegisters => {
-                let req = WriteMultipleHoldingRegistersRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::write_multiple_holding_registers(addr, msg.unit_id, &req, handler)
+                let req: WriteMultipleHoldingRegistersRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::write_multiple_holding_registers(ctx, &req, handler)
                     .await?
                     .encode_to_bytes()
             }
             FunctionCode::MaskWriteHoldingRegister => {
-                let req = MaskWriteHoldingRegisterRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::mask_write_holding_register(addr, msg.unit_id, &req, handler)
+                let req: MaskWriteHoldingRegisterRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::mask_write_holding_register(ctx, &req, handler)
                     .await?
                     .encode_to_bytes()
             }
             FunctionCode::ModbusEncapsulatedInterface => {
-                let req = ModbusEncapsulatedInterfaceRequest::decode_from_bytes(&msg.body).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                Self::modbus_encapsulated_interface(addr, msg.unit_id, &req, handler)
+                let req: ModbusEncapsulatedInterfaceRequest = decode_request(msg.function_code, &msg.body)?;
+                Self::modbus_encapsulated_interface(ctx, &req, handler)
                     .await?
                     .encode_to_bytes()
             }
@@ -255,60 +497,55 @@ where
     }
 
     async fn read_coils<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ReadCoilsRequest,
         handler: &'a Arc<T>,
-    ) -> Result<ReadCoilsResponse<'a>, ModbusException> {
+    ) -> Result<Cow<'a, BitBuf>, ModbusException> {
         validate_input(req.address, req.length, READ_COILS_MAX_LEN)?;
-        let values = handler.handle_read_coils(addr, unit_id, req.address, req.length).await?;
+        let values = handler.handle_read_coils(ctx, req.address, req.length).await?;
         validate_output(values.len(), req.length)?;
-        Ok(ReadCoilsResponse { values })
+        Ok(values)
     }
 
     async fn read_discrete_inputs<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ReadDiscreteInputsRequest,
         handler: &'a Arc<T>,
-    ) -> Result<ReadDiscreteInputsResponse<'a>, ModbusException> {
+    ) -> Result<Cow<'a, BitBuf>, ModbusException> {
         validate_input(req.address, req.length, READ_DISCRETE_INPUTS_MAX_LEN)?;
-        let values = handler.handle_read_discrete_inputs(addr, unit_id, req.address, req.length).await?;
+        let values = handler.handle_read_discrete_inputs(ctx, req.address, req.length).await?;
         validate_output(values.len(), req.length)?;
-        Ok(ReadDiscreteInputsResponse { values })
+        Ok(values)
     }
 
     async fn read_input_registers<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ReadInputRegistersRequest,
         handler: &'a Arc<T>,
-    ) -> Result<ReadInputRegistersResponse<'a>, ModbusException> {
+    ) -> Result<Cow<'a, [u16]>, ModbusException> {
         validate_input(req.address, req.length, READ_INPUT_REGISTERS_MAX_LEN)?;
-        let values = handler.handle_read_input_registers(addr, unit_id, req.address, req.length).await?;
+        let values = handler.handle_read_input_registers(ctx, req.address, req.length).await?;
         validate_output(values.len(), req.length)?;
-        Ok(ReadInputRegistersResponse { values })
+        Ok(values)
     }
 
     async fn read_holding_registers<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ReadHoldingRegistersRequest,
         handler: &'a Arc<T>,
-    ) -> Result<ReadHoldingRegistersResponse<'a>, ModbusException> {
+    ) -> Result<Cow<'a, [u16]>, ModbusException> {
         validate_input(req.address, req.length, READ_HOLDING_REGISTERS_MAX_LEN)?;
-        let values = handler.handle_read_holding_registers(addr, unit_id, req.address, req.length).await?;
+        let values = handler.handle_read_holding_registers(ctx, req.address, req.length).await?;
         validate_output(values.len(), req.length)?;
-        Ok(ReadHoldingRegistersResponse { values })
+        Ok(values)
     }
 
     async fn write_single_coil(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &WriteSingleCoilRequest,
         handler: &Arc<T>,
     ) -> Result<WriteSingleCoilResponse, ModbusException> {
-        handler.handle_write_coils(addr, unit_id, req.address, &[req.value]).await?;
+        handler.handle_write_single_coil(ctx, req.address, req.value).await?;
         Ok(WriteSingleCoilResponse {
             address: req.address,
             value: req.value,
@@ -316,14 +553,11 @@ where
     }
 
     async fn write_single_holding_register(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &WriteSingleHoldingRegisterRequest,
         handler: &Arc<T>,
     ) -> Result<WriteSingleHoldingRegisterResponse, ModbusException> {
-        handler
-            .handle_write_holding_registers(addr, unit_id, req.address, &[req.value])
-            .await?;
+        handler.handle_write_single_holding_register(ctx, req.address, req.value).await?;
         Ok(WriteSingleHoldingRegisterResponse {
             address: req.address,
             value: req.value,
@@ -331,13 +565,12 @@ where
     }
 
     async fn write_multiple_coils<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &WriteMultipleCoilsRequest<'a>,
         handler: &Arc<T>,
     ) -> Result<WriteMultipleCoilsResponse, ModbusException> {
         validate_input(req.address, req.values.len() as u16, WRITE_MULTIPLE_COILS_MAX_LEN)?;
-        handler.handle_write_coils(addr, unit_id, req.address, &req.values).await?;
+        handler.handle_write_coils(ctx, req.address, req.values).await?;
         Ok(WriteMultipleCoilsResponse {
             address: req.address,
             length: req.values.len() as u16,
@@ -345,13 +578,12 @@ where
     }
 
     async fn write_multiple_holding_registers<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &WriteMultipleHoldingRegistersRequest<'a>,
         handler: &Arc<T>,
     ) -> Result<WriteMultipleHoldingRegistersResponse, ModbusException> {
         validate_input(req.address, req.values.len() as u16, WRITE_MULTIPLE_HOLDING_REGISTERS_MAX_LEN)?;
-        handler.handle_write_holding_registers(addr, unit_id, req.address, &req.values).await?;
+        handler.handle_write_holding_registers(ctx, req.address, &req.values).await?;
         Ok(WriteMultipleHoldingRegistersResponse {
             address: req.address,
             length: req.values.len() as u16,
@@ -359,16 +591,13 @@ where
     }
 
     async fn mask_write_holding_register(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &MaskWriteHoldingRegisterRequest,
         handler: &Arc<T>,
     ) -> Result<MaskWriteHoldingRegisterResponse, ModbusException> {
-        let current_value = handler.handle_read_holding_registers(addr, unit_id, req.address, 1).await?;
-        validate_output(current_value.len(), 1)?;
-        let current_value = current_value[0];
-        let value = (current_value & req.and_mask) | (req.or_mask & (!req.and_mask));
-        handler.handle_write_holding_registers(addr, unit_id, req.address, &[value]).await?;
+        handler
+            .handle_mask_write_holding_register(ctx, req.address, req.and_mask, req.or_mask)
+            .await?;
         Ok(MaskWriteHoldingRegisterResponse {
             address: req.address,
             and_mask: req.and_mask,
@@ -377,47 +606,34 @@ where
     }
 
     async fn modbus_encapsulated_interface<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ModbusEncapsulatedInterfaceRequest<'a>,
         handler: &'a Arc<T>,
     ) -> Result<ModbusEncapsulatedInterfaceResponse<'a>, ModbusException> {
         match req.kind {
             ModbusEncapsulatedInterfaceType::ReadDeviceIdentification => {
-                let inner_req = ReadDeviceIdentificationRequest::decode_from_bytes(&req.data).map_err(|_| ModbusException::ServerDeviceFailure)?;
-                let data = Self::read_device_identification(addr, unit_id, &inner_req, handler).await?;
+                let inner_req: ReadDeviceIdentificationRequest = decode_request(FunctionCode::ModbusEncapsulatedInterface, &req.data)?;
+                let data = Self::read_device_identification(ctx, &inner_req, handler).await?;
                 Ok(ModbusEncapsulatedInterfaceResponse {
                     kind: req.kind,
                     data: data.encode_to_bytes().map_err(|_| ModbusException::ServerDeviceFailure)?.into(),
                 })
             }
             ModbusEncapsulatedInterfaceType::Unknown(kind) => {
-                let data = handler.handle_modbus_encapsulated_interface(addr, unit_id, kind, &req.data).await?;
+                let data = handler.handle_modbus_encapsulated_interface(ctx, kind, &req.data).await?;
                 Ok(ModbusEncapsulatedInterfaceResponse { kind: req.kind, data })
             }
         }
     }
 
     async fn read_device_identification<'a>(
-        addr: SocketAddr,
-        unit_id: u8,
+        ctx: &RequestContext,
         req: &ReadDeviceIdentificationRequest,
         handler: &'a Arc<T>,
     ) -> Result<ReadDeviceIdentificationResponse<'a>, ModbusException> {
-        let device_info = handler.handle_read_device_identification(addr, unit_id).await?;
-
-        let get_data = move |id: u8| -> Option<Vec<u8>> {
-            match id {
-                0 => Some(device_info.vendor_name.as_bytes().to_vec()),
-                1 => Some(device_info.product_code.as_bytes().to_vec()),
-                2 => Some(device_info.major_minor_revision.as_bytes().to_vec()),
-                3 => Some(device_info.vendor_url.as_ref()?.as_bytes().to_vec()),
-                4 => Some(device_info.product_name.as_ref()?.as_bytes().to_vec()),
-                5 => Some(device_info.model_name.as_ref()?.as_bytes().to_vec()),
-                6 => Some(device_info.user_application_name.as_ref()?.as_bytes().to_vec()),
-                _ => Some(device_info.objects.get(&id)?.to_vec()),
-            }
-        };
+        let device_info = handler.handle_read_device_identification(ctx).await?;
+
+        let get_data = move |id: u8| device_info.object(id).map(<[u8]>::to_vec);
 
         let data = get_data(req.object_id).ok_or(ModbusException::IllegalDataAddress)?;
         let max_object_id = match req.device_id_code {
@@ -428,7 +644,7 @@ where
                     conformity_level: ReadDeviceIdentificationConformityLevel::ExtendedStreamAndIndividual,
                     more_follows: false,
                     next_object_id: 0,
-                    objects: HashMap::from([(req.object_id, data.into())]),
+                    objects: BTreeMap::from([(req.object_id, data.into())]),
                 });
             }
             ReadDeviceIdentificationIdCode::Basic => 0x02,
@@ -437,7 +653,7 @@ where
         };
 
         let mut msg_length = 8 + 1 + 5 + 2 + data.len(); // 8 MSG, MEI = 1, RDI = 5, 2 per object
-        let mut objects: HashMap<u8, Cow<[u8]>> = HashMap::from([(req.object_id, data.into())]);
+        let mut objects: BTreeMap<u8, Cow<[u8]>> = BTreeMap::from([(req.object_id, data.into())]);
 
         if msg_length > MSG_MAX_LENGTH {
             return Err(ModbusException::IllegalDataValue);
@@ -469,6 +685,14 @@ where
     }
 }
 
+fn decode_request<'a, T: Decodable<'a>>(function_code: FunctionCode, bytes: &'a [u8]) -> Result<T, ModbusException> {
+    // Malformed requests, e.g. with a byte count not matching the quantity, are the client's fault.
+    T::decode_from_bytes(bytes).map_err(|_| {
+        telemetry::server_decode_failed(function_code);
+        ModbusException::IllegalDataValue
+    })
+}
+
 fn validate_input(address: u16, length: u16, max_length: u16) -> Result<(), ModbusException> {
     if length == 0 || length > max_length {
         return Err(ModbusException::IllegalDataValue);
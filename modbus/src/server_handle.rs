@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::watch, time::Instant};
+
+/// Identifies a connection for the lifetime of the server.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionId(pub u64);
+
+/// Information about an active connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub id: ConnectionId,
+    pub addr: SocketAddr,
+    pub connected_at: Instant,
+    /// When the last request was received or the last response was sent.
+    pub last_activity: Instant,
+    /// Number of requests currently being processed.
+    pub in_flight: usize,
+}
+
+/// What a connection should do, sent by the server or the handle.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) enum Control {
+    /// Keep serving requests.
+    Open,
+    /// Stop reading requests, let in-flight requests finish until the deadline, then close.
+    Drain(Instant),
+    /// Close immediately, abandoning in-flight requests.
+    Abort,
+}
+
+struct Entry {
+    info: ConnectionInfo,
+    control: watch::Sender<Control>,
+}
+
+/// State shared between the server tasks and the handles.
+pub(crate) struct ServerState {
+    next_id: AtomicU64,
+    connections: Mutex<HashMap<ConnectionId, Entry>>,
+    shutdown: watch::Sender<Option<Instant>>,
+    finished: watch::Sender<bool>,
+}
+
+impl ServerState {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            connections: Mutex::new(HashMap::new()),
+            shutdown: watch::Sender::new(None),
+            finished: watch::Sender::new(false),
+        }
+    }
+
+    /// Adds a connection. Connections added once a shutdown is requested start out draining.
+    pub fn register(&self, addr: SocketAddr) -> (ConnectionId, watch::Receiver<Control>) {
+        let id = ConnectionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let now = Instant::now();
+        let info = ConnectionInfo {
+            id,
+            addr,
+            connected_at: now,
+            last_activity: now,
+            in_flight: 0,
+        };
+        // Checked with the connections locked, so either the shutdown is seen here or the connection is reached by `control_all`.
+        // The drain is sent rather than set as the initial value, so the connection sees it as a change.
+        let mut connections = self.connections.lock().unwrap();
+        let (control, receiver) = watch::channel(Control::Open);
+        if let Some(deadline) = *self.shutdown.borrow() {
+            control.send_replace(Control::Drain(deadline));
+        }
+        connections.insert(id, Entry { info, control });
+        (id, receiver)
+    }
+
+    pub fn unregister(&self, id: ConnectionId) {
+        self.connections.lock().unwrap().remove(&id);
+    }
+
+    pub fn request_started(&self, id: ConnectionId) {
+        if let Some(entry) = self.connections.lock().unwrap().get_mut(&id) {
+            entry.info.last_activity = Instant::now();
+            entry.info.in_flight += 1;
+        }
+    }
+
+    pub fn request_finished(&self, id: ConnectionId) {
+        if let Some(entry) = self.connections.lock().unwrap().get_mut(&id) {
+            entry.info.last_activity = Instant::now();
+            entry.info.in_flight = entry.info.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// When the connection became idle, or `None` if it has requests in flight.
+    pub fn idle_since(&self, id: ConnectionId) -> Option<Instant> {
+        let connections = self.connections.lock().unwrap();
+        let info = &connections.get(&id)?.info;
+        (info.in_flight == 0).then_some(info.last_activity)
+    }
+
+    /// Aborts the connection that has been idle the longest. Returns `false` if no connection is idle.
+    pub fn evict_idle(&self) -> bool {
+        let connections = self.connections.lock().unwrap();
+        let oldest = connections
+            .values()
+            .filter(|e| e.info.in_flight == 0 && *e.control.borrow() == Control::Open)
+            .min_by_key(|e| e.info.last_activity);
+        match oldest {
+            Some(entry) => {
+                entry.control.send_replace(Control::Abort);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn control(&self, id: ConnectionId, control: Control) -> bool {
+        match self.connections.lock().unwrap().get(&id) {
+            Some(entry) => {
+                entry.control.send_replace(control);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn control_all(&self, control: Control) {
+        for entry in self.connections.lock().unwrap().values() {
+            entry.control.send_replace(control);
+        }
+    }
+
+    /// Resolves with the shutdown deadline once a shutdown is requested.
+    pub fn shutdown_receiver(&self) -> watch::Receiver<Option<Instant>> {
+        self.shutdown.subscribe()
+    }
+
+    pub fn set_finished(&self) {
+        self.finished.send_replace(true);
+    }
+}
+
+/**
+ * Handle to a running [`crate::ModbusTCPServer`].
+ *
+ * Dropping the handle doesn't stop the server.
+ * Cloning the handle is cheap, all clones control the same server.
+ */
+#[derive(Clone)]
+pub struct ModbusTCPServerHandle {
+    state: Arc<ServerState>,
+}
+
+impl ModbusTCPServerHandle {
+    pub(crate) fn new(state: Arc<ServerState>) -> Self {
+        Self { state }
+    }
+
+    /// All currently active connections.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<_> = self.state.connections.lock().unwrap().values().map(|e| e.info.clone()).collect();
+        connections.sort_by_key(|c| c.id);
+        connections
+    }
+
+    /// Closes a connection immediately, abandoning in-flight requests.
+    /// Returns `false` if there is no such connection.
+    pub fn disconnect(&self, id: ConnectionId) -> bool {
+        self.state.control(id, Control::Abort)
+    }
+
+    /**
+     * Stops accepting connections and stops reading requests on all connections.
+     * In-flight requests are given until `timeout` to finish, then all connections are closed.
+     * Resolves when all connections are closed and their `disconnected` handlers have completed.
+     */
+    pub async fn shutdown(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        self.state.shutdown.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(deadline);
+            true
+        });
+        _ = self.state.finished.subscribe().wait_for(|finished| *finished).await;
+    }
+
+    /// Whether the server has shut down.
+    pub fn is_finished(&self) -> bool {
+        *self.state.finished.borrow()
+    }
+}
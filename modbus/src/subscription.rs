@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use tokio::{
+    sync::mpsc,
+    time::{self, MissedTickBehavior},
+};
+
+use crate::{
+    bit_buf::BitBuf,
+    client::{ClientInner, ModbusError, ModbusTCPClient},
+    table::Table,
+};
+
+/// Values emitted by a [`Subscription`].
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscriptionValues {
+    /// Values of coils or discrete inputs.
+    Bits(BitBuf),
+    /// Values of input or holding registers.
+    Registers(Vec<u16>),
+}
+
+impl SubscriptionValues {
+    fn slice(&self, offset: usize, length: usize) -> Self {
+        match self {
+            SubscriptionValues::Bits(values) => SubscriptionValues::Bits(values.slice(offset..offset + length).into()),
+            SubscriptionValues::Registers(values) => SubscriptionValues::Registers(values[offset..offset + length].to_vec()),
+        }
+    }
+
+    fn differs(&self, other: &Self, deadband: u16) -> bool {
+        match (self, other) {
+            (SubscriptionValues::Bits(a), SubscriptionValues::Bits(b)) => a != b,
+            (SubscriptionValues::Registers(a), SubscriptionValues::Registers(b)) => {
+                a.len() != b.len() || a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > deadband)
+            }
+            _ => true,
+        }
+    }
+}
+
+type SubscriptionResult = Result<SubscriptionValues, ModbusError>;
+
+/// A stream of value updates created by [`ModbusTCPClient::subscribe`].
+pub struct Subscription {
+    receiver: mpsc::Receiver<SubscriptionResult>,
+    last_values: Option<SubscriptionValues>,
+    deadband: u16,
+}
+
+impl Subscription {
+    /// Register values are only considered changed if at least one register differs by more than `deadband`.
+    /// Has no effect on coils and discrete inputs.
+    pub fn with_deadband(mut self, deadband: u16) -> Self {
+        self.deadband = deadband;
+        self
+    }
+}
+
+impl Stream for Subscription {
+    type Item = SubscriptionResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(values))) => {
+                    if let Some(last_values) = &self.last_values {
+                        if !values.differs(last_values, self.deadband) {
+                            continue;
+                        }
+                    }
+                    self.last_values = Some(values.clone());
+                    return Poll::Ready(Some(Ok(values)));
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    // Emit the next successful read even if the values didn't change.
+                    self.last_values = None;
+                    return Poll::Ready(Some(Err(error)));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct GroupKey {
+    unit_id: u8,
+    table: Table,
+    interval: Duration,
+}
+
+struct Subscriber {
+    address: u16,
+    length: u16,
+    sender: mpsc::Sender<SubscriptionResult>,
+}
+
+type Group = Arc<Mutex<Vec<Subscriber>>>;
+
+/// Poll groups of a client, keyed by unit, table and interval.
+#[derive(Default)]
+pub(crate) struct SubscriptionMap {
+    groups: Mutex<HashMap<GroupKey, Group>>,
+}
+
+pub(crate) fn subscribe(
+    client: &ModbusTCPClient,
+    unit_id: u8,
+    table: Table,
+    address: u16,
+    length: u16,
+    interval: Duration,
+) -> Subscription {
+    // Each update is a full snapshot, so dropping some for a slow consumer is harmless.
+    let (sender, receiver) = mpsc::channel(16);
+    let key = GroupKey { unit_id, table, interval };
+    let subscriber = Subscriber { address, length, sender };
+
+    let mut groups = client.subscriptions().groups.lock().unwrap();
+    match groups.get(&key) {
+        Some(group) => group.lock().unwrap().push(subscriber),
+        None => {
+            let group = Arc::new(Mutex::new(vec![subscriber]));
+            groups.insert(key, group.clone());
+            tokio::spawn(poll_group(client.downgrade(), key, group));
+        }
+    }
+
+    Subscription {
+        receiver,
+        last_values: None,
+        deadband: 0,
+    }
+}
+
+async fn poll_group(client: Weak<ClientInner>, key: GroupKey, group: Group) {
+    let mut interval = time::interval(key.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let Some(client) = ModbusTCPClient::upgrade(&client) else {
+            return;
+        };
+
+        let ranges = {
+            // Lock order is map before group, same as in `subscribe`.
+            let mut groups = client.subscriptions().groups.lock().unwrap();
+            let mut subscribers = group.lock().unwrap();
+            subscribers.retain(|s| !s.sender.is_closed());
+            if subscribers.is_empty() {
+                groups.remove(&key);
+                return;
+            }
+            merge_ranges(subscribers.iter().map(|s| (s.address, s.length)), key.table.max_read_length())
+        };
+
+        let mut results = Vec::with_capacity(ranges.len());
+        for (address, length) in ranges {
+            results.push((address, length, read(&client, key, address, length).await));
+        }
+
+        let subscribers = group.lock().unwrap();
+        for subscriber in subscribers.iter() {
+            // Subscribers added since the ranges were calculated are not covered until the next tick.
+            let covering = results.iter().find(|(address, length, _)| {
+                subscriber.address >= *address && subscriber.address as u32 + subscriber.length as u32 <= *address as u32 + *length as u32
+            });
+            let Some((address, _, result)) = covering else {
+                continue;
+            };
+            let item = match result {
+                Ok(values) => Ok(values.slice((subscriber.address - address) as usize, subscriber.length as usize)),
+                Err(error) => Err(error.clone()),
+            };
+            _ = subscriber.sender.try_send(item);
+        }
+    }
+}
+
+async fn read(client: &ModbusTCPClient, key: GroupKey, address: u16, length: u16) -> SubscriptionResult {
+    Ok(match key.table {
+        Table::Coils => SubscriptionValues::Bits(client.read_coils(key.unit_id, address, length).await?),
+        Table::DiscreteInputs => SubscriptionValues::Bits(client.read_discrete_inputs(key.unit_id, address, length).await?),
+        Table::InputRegisters => SubscriptionValues::Registers(client.read_input_registers(key.unit_id, address, length).await?),
+        Table::HoldingRegisters => {
+            SubscriptionValues::Registers(client.read_holding_registers(key.unit_id, address, length).await?)
+        }
+    })
+}
+
+/// Merges overlapping and adjacent ranges, as long as the merged range doesn't exceed `max_length`.
+/// Every input range is fully contained in one of the output ranges.
+fn merge_ranges(ranges: impl Iterator<Item = (u16, u16)>, max_length: u16) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u32, u32)> = ranges.map(|(address, length)| (address as u32, address as u32 + length as u32)).collect();
+    ranges.sort_unstable();
+
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 && end.max(last.1) - last.0 <= max_length as u32 => last.1 = end.max(last.1),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged.into_iter().map(|(start, end)| (start as u16, (end - start) as u16)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge() {
+        assert_eq!(merge_ranges([(0, 10), (5, 10), (15, 1), (20, 5)].into_iter(), 125), vec![(0, 16), (20, 5)]);
+        assert_eq!(merge_ranges([(0, 100), (50, 100)].into_iter(), 125), vec![(0, 100), (50, 100)]);
+        assert_eq!(merge_ranges([(0xFFFF, 1), (0xFFF0, 16)].into_iter(), 125), vec![(0xFFF0, 16)]);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::consts::*;
+
+/// One of the four tables of a Modbus device's data model.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Table {
+    Coils,
+    DiscreteInputs,
+    InputRegisters,
+    HoldingRegisters,
+}
+
+impl Table {
+    /// The most values a single request can read from the table.
+    pub fn max_read_length(self) -> u16 {
+        match self {
+            Table::Coils => READ_COILS_MAX_LEN,
+            Table::DiscreteInputs => READ_DISCRETE_INPUTS_MAX_LEN,
+            Table::InputRegisters => READ_INPUT_REGISTERS_MAX_LEN,
+            Table::HoldingRegisters => READ_HOLDING_REGISTERS_MAX_LEN,
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Optional instrumentation of client and server transactions.
+//!
+//! With the `tracing` feature every transaction runs in a span and emits an event when finished.
+//! With the `metrics` feature counters and histograms are recorded through the `metrics` facade.
+//! Without either feature everything in this module compiles to nothing.
+
+// Which inputs are used depends on the enabled features.
+#![cfg_attr(not(all(feature = "tracing", feature = "metrics")), allow(unused_variables, dead_code))]
+
+use std::{future::Future, net::SocketAddr};
+
+use crate::{client::ModbusError, function_code::FunctionCode, modbus_exception::ModbusException, server::DisconnectReason};
+
+#[derive(Clone, Copy)]
+enum Side {
+    Client,
+    Server,
+}
+
+/// A single request/response exchange, finished with [`Transaction::finish`].
+/// Dropping it unfinished, e.g. when a timeout cancels the request, records it as cancelled.
+pub(crate) struct Transaction {
+    #[cfg(feature = "metrics")]
+    side: Side,
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    function_code: FunctionCode,
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    start: std::time::Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    finished: bool,
+}
+
+/// The result of a transaction.
+pub(crate) enum Outcome<'a> {
+    Ok,
+    Exception(ModbusException),
+    Error(&'a ModbusError),
+    /// The transaction was dropped before it finished.
+    Cancelled,
+}
+
+impl<'a, T> From<&'a Result<T, ModbusError>> for Outcome<'a> {
+    fn from(value: &'a Result<T, ModbusError>) -> Self {
+        match value {
+            Ok(_) => Outcome::Ok,
+            Err(ModbusError::ModbusException(ex)) => Outcome::Exception(*ex),
+            Err(error) => Outcome::Error(error),
+        }
+    }
+}
+
+impl<T> From<&Result<T, ModbusException>> for Outcome<'_> {
+    fn from(value: &Result<T, ModbusException>) -> Self {
+        match value {
+            Ok(_) => Outcome::Ok,
+            Err(ex) => Outcome::Exception(*ex),
+        }
+    }
+}
+
+impl Transaction {
+    pub fn client(transaction_id: u16, unit_id: u8, function_code: FunctionCode, body: &[u8]) -> Self {
+        Self::new(Side::Client, None, transaction_id, unit_id, function_code, body)
+    }
+
+    pub fn server(addr: SocketAddr, transaction_id: u16, unit_id: u8, function_code: FunctionCode, body: &[u8]) -> Self {
+        Self::new(Side::Server, Some(addr), transaction_id, unit_id, function_code, body)
+    }
+
+    fn new(side: Side, addr: Option<SocketAddr>, transaction_id: u16, unit_id: u8, function_code: FunctionCode, body: &[u8]) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = {
+            let (address, length) = request_range(function_code, body).unzip();
+            let side = match side {
+                Side::Client => "client",
+                Side::Server => "server",
+            };
+            tracing::debug_span!(
+                "modbus_transaction",
+                side,
+                peer = addr.map(tracing::field::display),
+                transaction_id,
+                unit_id,
+                function_code = u8::from(function_code),
+                address,
+                length,
+            )
+        };
+
+        Self {
+            #[cfg(feature = "metrics")]
+            side,
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            function_code,
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            start: std::time::Instant::now(),
+            #[cfg(feature = "tracing")]
+            span,
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            finished: false,
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.span.clone())
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub fn instrument<F: Future>(&self, future: F) -> F {
+        future
+    }
+
+    pub fn finish(mut self, outcome: Outcome) {
+        self.record(outcome);
+    }
+
+    fn record(&mut self, outcome: Outcome) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        {
+            self.finished = true;
+            let latency = self.start.elapsed();
+            let label = outcome_label(&outcome);
+
+            #[cfg(feature = "tracing")]
+            self.span.in_scope(|| match &outcome {
+                Outcome::Ok => tracing::debug!(latency_us = latency.as_micros() as u64, outcome = label),
+                Outcome::Exception(ex) => {
+                    tracing::debug!(latency_us = latency.as_micros() as u64, outcome = label, exception = u8::from(*ex))
+                }
+                Outcome::Error(error) => tracing::warn!(latency_us = latency.as_micros() as u64, outcome = label, %error),
+                Outcome::Cancelled => tracing::warn!(latency_us = latency.as_micros() as u64, outcome = label),
+            });
+
+            #[cfg(feature = "metrics")]
+            {
+                let (requests, duration, exceptions) = match self.side {
+                    Side::Client => (
+                        "modbus_client_requests_total",
+                        "modbus_client_request_duration_seconds",
+                        "modbus_client_exceptions_total",
+                    ),
+                    Side::Server => (
+                        "modbus_server_requests_total",
+                        "modbus_server_request_duration_seconds",
+                        "modbus_server_exceptions_total",
+                    ),
+                };
+                let function_code = u8::from(self.function_code).to_string();
+                metrics::counter!(requests, "function_code" => function_code.clone(), "outcome" => label).increment(1);
+                metrics::histogram!(duration, "function_code" => function_code.clone()).record(latency.as_secs_f64());
+                if let Outcome::Exception(ex) = outcome {
+                    metrics::counter!(
+                        exceptions,
+                        "function_code" => function_code,
+                        "exception" => u8::from(ex).to_string()
+                    )
+                    .increment(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.record(Outcome::Cancelled);
+        }
+    }
+}
+
+/// A request was abandoned because no response was received in time.
+pub(crate) fn client_timeout() {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("Modbus request timed out");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_client_timeouts_total").increment(1);
+}
+
+/// A response arrived for a request that is no longer waiting for it, e.g. because it timed out.
+pub(crate) fn client_unexpected_response(transaction_id: u16) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(transaction_id, "Ignoring Modbus response to an unknown request");
+    #[cfg(not(feature = "tracing"))]
+    let _ = transaction_id;
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_client_unexpected_responses_total").increment(1);
+}
+
+/// A response from the server couldn't be decoded.
+pub(crate) fn client_decode_failed(function_code: FunctionCode) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(function_code = u8::from(function_code), "Failed to decode Modbus response");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_client_decode_failures_total", "function_code" => u8::from(function_code).to_string()).increment(1);
+}
+
+/// A request from a client couldn't be decoded.
+pub(crate) fn server_decode_failed(function_code: FunctionCode) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(function_code = u8::from(function_code), "Failed to decode Modbus request");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_decode_failures_total", "function_code" => u8::from(function_code).to_string()).increment(1);
+}
+
+pub(crate) fn server_connected(addr: SocketAddr) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(peer = %addr, "Modbus client connected");
+    #[cfg(feature = "metrics")]
+    metrics::gauge!("modbus_server_active_connections").increment(1);
+}
+
+pub(crate) fn server_disconnected(addr: SocketAddr, reason: &DisconnectReason) {
+    #[cfg(feature = "tracing")]
+    match reason {
+        DisconnectReason::IO(_) | DisconnectReason::Frame(_) => tracing::warn!(peer = %addr, %reason, "Modbus client disconnected"),
+        _ => tracing::info!(peer = %addr, %reason, "Modbus client disconnected"),
+    }
+    #[cfg(feature = "metrics")]
+    {
+        metrics::gauge!("modbus_server_active_connections").decrement(1);
+        if let DisconnectReason::Frame(_) = reason {
+            metrics::counter!("modbus_server_frame_errors_total").increment(1);
+        }
+    }
+}
+
+/// A connection was rejected because the connection limit was reached.
+pub(crate) fn server_connection_rejected(addr: SocketAddr) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(peer = %addr, "Modbus connection rejected, too many connections");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_rejected_connections_total").increment(1);
+}
+
+/// A connection was closed because it was idle for too long.
+pub(crate) fn server_idle_timeout(addr: SocketAddr) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(peer = %addr, "Closing idle Modbus connection");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_idle_timeouts_total").increment(1);
+}
+
+/// A handler didn't respond within the request timeout.
+pub(crate) fn server_request_timeout(function_code: FunctionCode) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(function_code = u8::from(function_code), "Modbus request handler timed out");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_request_timeouts_total", "function_code" => u8::from(function_code).to_string()).increment(1);
+}
+
+/// A request was answered with Server Device Busy by the rate limit.
+pub(crate) fn server_rate_limited(addr: SocketAddr) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(peer = %addr, "Modbus request rate limited");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_rate_limited_total").increment(1);
+}
+
+pub(crate) fn server_write_failed(addr: SocketAddr, error: &dyn std::error::Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(peer = %addr, %error, "Failed to write Modbus response");
+    #[cfg(feature = "metrics")]
+    metrics::counter!("modbus_server_write_failures_total").increment(1);
+}
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+fn outcome_label(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Ok => "ok",
+        Outcome::Exception(_) => "exception",
+        Outcome::Error(ModbusError::IO(_)) => "io",
+        Outcome::Error(ModbusError::ArgumentsOutOfRange(_)) => "arguments_out_of_range",
+        Outcome::Error(ModbusError::Internal(_)) => "internal",
+        Outcome::Error(ModbusError::InvalidResponse(_)) => "invalid_response",
+        Outcome::Error(ModbusError::ModbusException(_)) => "exception",
+        Outcome::Error(ModbusError::Timeout) => "timeout",
+        Outcome::Cancelled => "cancelled",
+    }
+}
+
+/// Address and length of a request, for the function codes that have them.
+#[cfg(feature = "tracing")]
+fn request_range(function_code: FunctionCode, body: &[u8]) -> Option<(u16, u16)> {
+    let address = u16::from_be_bytes([*body.first()?, *body.get(1)?]);
+    match function_code {
+        FunctionCode::ReadCoils
+        | FunctionCode::ReadDiscreteInputs
+        | FunctionCode::ReadHoldingRegisters
+        | FunctionCode::ReadInputRegisters
+        | FunctionCode::WriteMultipleCoils
+        | FunctionCode::WriteMultipleHoldingRegisters => Some((address, u16::from_be_bytes([*body.get(2)?, *body.get(3)?]))),
+        FunctionCode::WriteSingleCoil | FunctionCode::WriteSingleHoldingRegister | FunctionCode::MaskWriteHoldingRegister => {
+            Some((address, 1))
+        }
+        _ => None,
+    }
+}
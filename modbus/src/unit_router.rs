@@ -0,0 +1,105 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use crate::{
+    bit_buf::BitBuf, dyn_handler::DynHandler, modbus_encapsulated_interface::DeviceIdentification, modbus_exception::ModbusException,
+    request_context::RequestContext, server::ModbusTCPServerHandler, views::Bits,
+};
+
+/**
+ * A server handler that dispatches requests to other handlers by unit id, e.g. to emulate a gateway with several devices behind it.
+ *
+ * Requests to units without a handler go to the fallback handler,
+ * or are answered with [`ModbusException::GatewayPathUnavailable`] if there is none.
+ *
+ * Only the request handlers are dispatched. Connection handling such as [`ModbusTCPServerHandler::accept_connection`]
+ * uses the defaults, wrap the router in another handler to change it.
+ */
+#[derive(Clone, Default)]
+pub struct UnitRouter {
+    units: HashMap<u8, Arc<dyn DynHandler>>,
+    fallback: Option<Arc<dyn DynHandler>>,
+}
+
+impl UnitRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Dispatches requests to the unit to `handler`, replacing any previous handler for the unit.
+    pub fn with_unit<H: ModbusTCPServerHandler>(mut self, unit_id: u8, handler: Arc<H>) -> Self {
+        self.units.insert(unit_id, handler);
+        self
+    }
+
+    /// Dispatches requests to all the units to the same `handler`.
+    pub fn with_units<H: ModbusTCPServerHandler>(mut self, unit_ids: impl IntoIterator<Item = u8>, handler: Arc<H>) -> Self {
+        for unit_id in unit_ids {
+            self.units.insert(unit_id, handler.clone());
+        }
+        self
+    }
+
+    /// Dispatches requests to units without a handler to `handler`.
+    pub fn with_fallback<H: ModbusTCPServerHandler>(mut self, handler: Arc<H>) -> Self {
+        self.fallback = Some(handler);
+        self
+    }
+
+    fn route(&self, unit_id: u8) -> Result<&dyn DynHandler, ModbusException> {
+        self.units
+            .get(&unit_id)
+            .or(self.fallback.as_ref())
+            .map(|handler| handler.as_ref())
+            .ok_or(ModbusException::GatewayPathUnavailable)
+    }
+}
+
+impl ModbusTCPServerHandler for UnitRouter {
+    async fn handle_read_coils(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        self.route(ctx.unit_id)?.read_coils(ctx, address, length).await
+    }
+
+    async fn handle_read_discrete_inputs(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, BitBuf>, ModbusException> {
+        self.route(ctx.unit_id)?.read_discrete_inputs(ctx, address, length).await
+    }
+
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        self.route(ctx.unit_id)?.read_input_registers(ctx, address, length).await
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        self.route(ctx.unit_id)?.read_holding_registers(ctx, address, length).await
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, address: u16, values: Bits<'_>) -> Result<(), ModbusException> {
+        self.route(ctx.unit_id)?.write_coils(ctx, address, values).await
+    }
+
+    async fn handle_write_holding_registers(&self, ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        self.route(ctx.unit_id)?.write_holding_registers(ctx, address, values).await
+    }
+
+    async fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> Result<(), ModbusException> {
+        self.route(ctx.unit_id)?.write_single_coil(ctx, address, value).await
+    }
+
+    async fn handle_write_single_holding_register(&self, ctx: &RequestContext, address: u16, value: u16) -> Result<(), ModbusException> {
+        self.route(ctx.unit_id)?.write_single_holding_register(ctx, address, value).await
+    }
+
+    async fn handle_mask_write_holding_register(&self, ctx: &RequestContext, address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        self.route(ctx.unit_id)?
+            .mask_write_holding_register(ctx, address, and_mask, or_mask)
+            .await
+    }
+
+    async fn handle_read_device_identification(&self, ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+        self.route(ctx.unit_id)?.read_device_identification(ctx).await
+    }
+
+    async fn handle_modbus_encapsulated_interface(&self, ctx: &RequestContext, interface_type: u8, data: &[u8]) -> Result<Cow<'_, [u8]>, ModbusException> {
+        self.route(ctx.unit_id)?
+            .modbus_encapsulated_interface(ctx, interface_type, data)
+            .await
+    }
+}
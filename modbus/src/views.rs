@@ -0,0 +1,339 @@
+//! Borrowed views of registers and bits, either as they are encoded in a frame or as a slice of values.
+
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    iter::FusedIterator,
+    ops::{Bound, RangeBounds},
+    slice::ChunksExact,
+};
+
+/**
+ * A view of registers, without copying them out of the frame they were decoded from.
+ *
+ * Decoded registers borrow the big-endian bytes of the frame, and registers to be encoded can borrow a slice of values.
+ */
+#[derive(Clone, Copy)]
+pub struct Registers<'a>(RegistersRepr<'a>);
+
+#[derive(Clone, Copy)]
+enum RegistersRepr<'a> {
+    Bytes(&'a [u8]),
+    Values(&'a [u16]),
+}
+
+impl<'a> Registers<'a> {
+    /// A view of big-endian registers. Returns `None` if `bytes` has an odd length.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        bytes.len().is_multiple_of(2).then_some(Self(RegistersRepr::Bytes(bytes)))
+    }
+
+    pub fn len(&self) -> usize {
+        match self.0 {
+            RegistersRepr::Bytes(bytes) => bytes.len() / 2,
+            RegistersRepr::Values(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<u16> {
+        match self.0 {
+            RegistersRepr::Bytes(bytes) => (index < bytes.len() / 2).then(|| u16::from_be_bytes([bytes[index * 2], bytes[index * 2 + 1]])),
+            RegistersRepr::Values(values) => values.get(index).copied(),
+        }
+    }
+
+    pub fn iter(&self) -> RegisterIter<'a> {
+        RegisterIter(match self.0 {
+            RegistersRepr::Bytes(bytes) => RegisterIterRepr::Bytes(bytes.chunks_exact(2)),
+            RegistersRepr::Values(values) => RegisterIterRepr::Values(values.iter()),
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+}
+
+impl<'a> From<&'a [u16]> for Registers<'a> {
+    fn from(values: &'a [u16]) -> Self {
+        Self(RegistersRepr::Values(values))
+    }
+}
+
+impl<'a> IntoIterator for Registers<'a> {
+    type Item = u16;
+    type IntoIter = RegisterIter<'a>;
+
+    fn into_iter(self) -> RegisterIter<'a> {
+        self.iter()
+    }
+}
+
+impl PartialEq for Registers<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for Registers<'_> {}
+
+impl fmt::Debug for Registers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the values of [`Registers`].
+#[derive(Clone, Debug)]
+pub struct RegisterIter<'a>(RegisterIterRepr<'a>);
+
+#[derive(Clone, Debug)]
+enum RegisterIterRepr<'a> {
+    Bytes(ChunksExact<'a, u8>),
+    Values(core::slice::Iter<'a, u16>),
+}
+
+impl Iterator for RegisterIter<'_> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        match &mut self.0 {
+            RegisterIterRepr::Bytes(chunks) => chunks.next().map(|pair| u16::from_be_bytes([pair[0], pair[1]])),
+            RegisterIterRepr::Values(values) => values.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            RegisterIterRepr::Bytes(chunks) => chunks.size_hint(),
+            RegisterIterRepr::Values(values) => values.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for RegisterIter<'_> {
+    fn next_back(&mut self) -> Option<u16> {
+        match &mut self.0 {
+            RegisterIterRepr::Bytes(chunks) => chunks.next_back().map(|pair| u16::from_be_bytes([pair[0], pair[1]])),
+            RegisterIterRepr::Values(values) => values.next_back().copied(),
+        }
+    }
+}
+
+impl ExactSizeIterator for RegisterIter<'_> {}
+
+impl FusedIterator for RegisterIter<'_> {}
+
+/**
+ * A view of coils or discrete inputs, without copying them out of the frame they were decoded from.
+ *
+ * Decoded bits borrow the packed bytes of the frame, least significant bit first,
+ * and bits to be encoded can borrow a slice of values.
+ */
+#[derive(Clone, Copy)]
+pub struct Bits<'a>(BitsRepr<'a>);
+
+#[derive(Clone, Copy)]
+enum BitsRepr<'a> {
+    /// `offset` is below 8, the bits start within the first byte after slicing.
+    Packed { bytes: &'a [u8], offset: usize, len: usize },
+    Values(&'a [bool]),
+}
+
+impl<'a> Bits<'a> {
+    /// A view of the first `len` bits of `bytes`. Returns `None` if `bytes` holds fewer bits.
+    pub fn new(bytes: &'a [u8], len: usize) -> Option<Self> {
+        (len <= bytes.len() * 8).then_some(Self(BitsRepr::Packed { bytes, offset: 0, len }))
+    }
+
+    pub fn len(&self) -> usize {
+        match self.0 {
+            BitsRepr::Packed { len, .. } => len,
+            BitsRepr::Values(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        match self.0 {
+            BitsRepr::Packed { bytes, offset, len } => (index < len).then(|| bytes[(offset + index) / 8] & (1 << ((offset + index) % 8)) != 0),
+            BitsRepr::Values(values) => values.get(index).copied(),
+        }
+    }
+
+    /// A view of a range of the bits.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds, as when slicing a slice.
+    pub fn slice(self, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(start <= end && end <= self.len(), "range {start}..{end} out of bounds for {} bits", self.len());
+        Self(match self.0 {
+            BitsRepr::Packed { bytes, offset, .. } => BitsRepr::Packed {
+                bytes: &bytes[(offset + start) / 8..],
+                offset: (offset + start) % 8,
+                len: end - start,
+            },
+            BitsRepr::Values(values) => BitsRepr::Values(&values[start..end]),
+        })
+    }
+
+    /// The packed bytes, if the bits are a view of packed bytes starting at a byte boundary.
+    /// Bits of the last byte past the end of the view aren't necessarily cleared.
+    pub fn as_packed(&self) -> Option<&'a [u8]> {
+        match self.0 {
+            BitsRepr::Packed { bytes, offset: 0, len } => Some(&bytes[..len.div_ceil(8)]),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> BitIter<'a> {
+        BitIter { bits: *self, index: 0, end: self.len() }
+    }
+
+    pub fn to_vec(&self) -> Vec<bool> {
+        self.iter().collect()
+    }
+}
+
+impl<'a> From<&'a [bool]> for Bits<'a> {
+    fn from(values: &'a [bool]) -> Self {
+        Self(BitsRepr::Values(values))
+    }
+}
+
+impl<'a, const N: usize> From<&'a [bool; N]> for Bits<'a> {
+    fn from(values: &'a [bool; N]) -> Self {
+        Self(BitsRepr::Values(values))
+    }
+}
+
+impl<'a> From<&'a Vec<bool>> for Bits<'a> {
+    fn from(values: &'a Vec<bool>) -> Self {
+        Self(BitsRepr::Values(values))
+    }
+}
+
+impl<'a> IntoIterator for Bits<'a> {
+    type Item = bool;
+    type IntoIter = BitIter<'a>;
+
+    fn into_iter(self) -> BitIter<'a> {
+        self.iter()
+    }
+}
+
+impl PartialEq for Bits<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for Bits<'_> {}
+
+impl fmt::Debug for Bits<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the values of [`Bits`].
+#[derive(Clone)]
+pub struct BitIter<'a> {
+    bits: Bits<'a>,
+    index: usize,
+    end: usize,
+}
+
+impl Iterator for BitIter<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.index == self.end {
+            return None;
+        }
+        self.index += 1;
+        self.bits.get(self.index - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl DoubleEndedIterator for BitIter<'_> {
+    fn next_back(&mut self) -> Option<bool> {
+        if self.index == self.end {
+            return None;
+        }
+        self.end -= 1;
+        self.bits.get(self.end)
+    }
+}
+
+impl ExactSizeIterator for BitIter<'_> {}
+
+impl FusedIterator for BitIter<'_> {}
+
+impl fmt::Debug for BitIter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn registers() {
+        let registers = Registers::new(&[0x01, 0x2C, 0x01, 0x2D, 0xFF, 0xFF]).unwrap();
+        assert_eq!(registers.len(), 3);
+        assert_eq!(registers.get(1), Some(301));
+        assert_eq!(registers.get(3), None);
+        assert_eq!(registers.get(usize::MAX), None);
+        assert_eq!(registers.iter().rev().collect::<Vec<_>>(), [0xFFFF, 301, 300]);
+        assert_eq!(registers, Registers::from(&[300, 301, 0xFFFF][..]));
+        assert_eq!(Registers::new(&[0x01]), None);
+    }
+
+    #[test]
+    fn bits() {
+        let bits = Bits::new(&[0b1100_1101, 0b0000_0001], 10).unwrap();
+        assert_eq!(bits.len(), 10);
+        assert_eq!(bits.to_vec(), [true, false, true, true, false, false, true, true, true, false]);
+        assert_eq!(bits.get(10), None);
+        assert_eq!(bits.get(usize::MAX), None);
+        assert_eq!(bits.iter().len(), 10);
+        assert_eq!(bits.iter().next_back(), Some(false));
+        assert_eq!(bits.as_packed(), Some(&[0b1100_1101, 0b0000_0001][..]));
+        assert_eq!(bits.slice(..3), Bits::from(&[true, false, true][..]));
+        assert_eq!(bits.slice(..3).as_packed(), Some(&[0b1100_1101][..]));
+        assert_eq!(bits.slice(6..=8).to_vec(), [true, true, true]);
+        assert_eq!(bits.slice(6..=8).as_packed(), None);
+        assert_eq!(bits.slice(8..).as_packed(), Some(&[0b0000_0001][..]));
+        assert_eq!(Bits::from(&[true, false, true][..]).slice(1..).to_vec(), [false, true]);
+        assert_eq!(Bits::new(&[0], 9), None);
+        assert_eq!(Bits::from(&vec![true; 3][..]).as_packed(), None);
+    }
+}
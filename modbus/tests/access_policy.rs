@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use modbus::{AccessPolicy, Layered, MemoryStore, ModbusError, ModbusException, ModbusTCPClient};
+
+mod common;
+
+async fn serve(policy: AccessPolicy) -> ModbusTCPClient {
+    let store = MemoryStore::new().with_holding_registers(1, 0, 100).with_holding_registers(2, 0, 100);
+    common::connect(common::serve(Arc::new(Layered::new(store, policy))).await.1).await
+}
+
+fn denied<T>(result: Result<T, ModbusError>) -> ModbusException {
+    match result {
+        Err(ModbusError::ModbusException(exception)) => exception,
+        _ => panic!("Expected an exception"),
+    }
+}
+
+#[tokio::test]
+pub async fn access_policy() {
+    let path = std::env::temp_dir().join(format!("modbus-access-policy-{}.txt", std::process::id()));
+    std::fs::write(
+        &path,
+        "
+        deny-with illegal-data-address
+        allow read from 127.0.0.0/8
+        allow write from 127.0.0.1 units 1 holding-registers 10-19 functions 16
+        ",
+    )
+    .unwrap();
+    let policy = AccessPolicy::load(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let client = serve(policy).await;
+    assert_eq!(client.read_holding_registers(2, 0, 2).await.unwrap(), [0, 0]);
+    client.write_multiple_holding_registers(1, 10, &[1, 2]).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 10, 2).await.unwrap(), [1, 2]);
+
+    assert_eq!(denied(client.write_multiple_holding_registers(1, 19, &[1, 2]).await), ModbusException::IllegalDataAddress);
+    assert_eq!(denied(client.write_multiple_holding_registers(2, 10, &[1]).await), ModbusException::IllegalDataAddress);
+    assert_eq!(denied(client.write_single_holding_register(1, 10, 1).await), ModbusException::IllegalDataAddress);
+}
+
+#[tokio::test]
+pub async fn access_policy_default() {
+    // Without rules everything is denied, even the connection.
+    let client = serve(AccessPolicy::new()).await;
+    assert!(matches!(client.read_holding_registers(1, 0, 1).await, Err(ModbusError::IO(_))));
+
+    let client = serve("allow read-write from 10.0.0.0/8".parse().unwrap()).await;
+    assert!(matches!(client.read_holding_registers(1, 0, 1).await, Err(ModbusError::IO(_))));
+
+    let client = serve("allow read".parse().unwrap()).await;
+    client.read_holding_registers(1, 0, 1).await.unwrap();
+    assert_eq!(denied(client.write_single_holding_register(1, 0, 1).await), ModbusException::IllegalFunction);
+}
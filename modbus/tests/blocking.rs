@@ -0,0 +1,38 @@
+use std::{borrow::Cow, sync::Arc, thread, time::Duration};
+
+use modbus::{blocking::ModbusTCPClient, ModbusError, ModbusException, ModbusTCPServer, ModbusTCPServerHandler, RequestContext};
+use tokio::net::TcpListener;
+
+#[test]
+pub fn blocking_client() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+    runtime.spawn(async move {
+        _ = ModbusTCPServer::run(listener, Arc::new(ServerImpl {}));
+    });
+
+    let client = thread::spawn(move || {
+        let client = ModbusTCPClient::connect(addr, Duration::from_millis(200)).unwrap();
+
+        assert_eq!(client.read_input_registers(0, 10, 3).unwrap(), vec![10, 11, 12]);
+        assert!(matches!(
+            client.read_coils(0, 0, 1),
+            Err(ModbusError::ModbusException(ModbusException::IllegalFunction))
+        ));
+        assert!(matches!(client.read_input_registers(1, 0, 1), Err(ModbusError::Timeout)));
+    });
+
+    client.join().unwrap();
+}
+
+struct ServerImpl {}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn handle_read_input_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        if ctx.unit_id == 1 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        Ok((address..address + length).collect())
+    }
+}
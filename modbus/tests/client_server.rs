@@ -1,6 +1,6 @@
-use std::{borrow::Cow, collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
 
-use modbus::{DeviceIdentification, ModbusException, ModbusTCPClient, ModbusTCPServer, ModbusTCPServerHandler};
+use modbus::{DeviceIdentification, ModbusException, ModbusTCPClient, ModbusTCPServer, ModbusTCPServerHandler, RequestContext};
 use tokio::net::{TcpListener, TcpSocket};
 
 #[tokio::test]
@@ -13,7 +13,7 @@ pub async fn client_server() {
         product_name: None,
         user_application_name: None,
         vendor_url: None,
-        objects: HashMap::new(),
+        objects: BTreeMap::new(),
     };
 
     let handler = Arc::new(ServerImpl {
@@ -37,7 +37,7 @@ struct ServerImpl<'a> {
 }
 
 impl ModbusTCPServerHandler for ServerImpl<'static> {
-    async fn handle_read_device_identification(&self, _addr: SocketAddr, _unit_id: u8) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
+    async fn handle_read_device_identification(&self, _ctx: &RequestContext) -> Result<Cow<'_, DeviceIdentification<'_>>, ModbusException> {
         Ok(Cow::Borrowed(&self.device_info))
     }
 }
@@ -0,0 +1,19 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, sync::Arc};
+
+use modbus::{ModbusTCPClient, ModbusTCPServer, ModbusTCPServerHandle, ModbusTCPServerHandler};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Runs a server with `handler` on a free local port.
+pub async fn serve<H: ModbusTCPServerHandler>(handler: Arc<H>) -> (ModbusTCPServerHandle, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    (ModbusTCPServer::run(listener, handler), addr)
+}
+
+/// Connects a client to the server at `addr`.
+pub async fn connect(addr: SocketAddr) -> ModbusTCPClient {
+    ModbusTCPClient::new(TcpStream::connect(addr).await.unwrap()).0
+}
@@ -0,0 +1,75 @@
+use std::{borrow::Cow, net::SocketAddr, sync::Arc};
+
+use modbus::{DisconnectReason, FrameError, MemoryStore, ModbusException, ModbusTCPServerHandler, RequestContext};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+};
+
+mod common;
+
+struct Handler {
+    resync: bool,
+    disconnected: mpsc::UnboundedSender<DisconnectReason>,
+}
+
+impl ModbusTCPServerHandler for Handler {
+    fn resync_frames(&self) -> bool {
+        self.resync
+    }
+
+    async fn disconnected(&self, _addr: SocketAddr, reason: &DisconnectReason) {
+        _ = self.disconnected.send(reason.clone());
+    }
+}
+
+async fn serve(resync: bool) -> (TcpStream, mpsc::UnboundedReceiver<DisconnectReason>) {
+    let (disconnected, receiver) = mpsc::unbounded_channel();
+    let (_, addr) = common::serve(Arc::new(Handler { resync, disconnected })).await;
+    (TcpStream::connect(addr).await.unwrap(), receiver)
+}
+
+#[tokio::test]
+pub async fn frame_errors() {
+    for (header, error) in [
+        ([0, 1, 0, 5, 0, 6, 1, 3], FrameError::InvalidProtocolId(5)),
+        ([0, 1, 0, 0, 0, 0, 1, 3], FrameError::LengthTooShort(0)),
+        ([0, 1, 0, 0, 0x12, 0x34, 1, 3], FrameError::LengthTooLong(0x1234)),
+    ] {
+        let (mut stream, mut disconnected) = serve(false).await;
+        stream.write_all(&header).await.unwrap();
+        assert!(matches!(disconnected.recv().await.unwrap(), DisconnectReason::Frame(e) if e == error));
+        // The server closed the connection.
+        assert_eq!(stream.read(&mut [0; 16]).await.unwrap(), 0);
+    }
+
+    let (stream, mut disconnected) = serve(false).await;
+    drop(stream);
+    assert!(matches!(disconnected.recv().await.unwrap(), DisconnectReason::Closed));
+}
+
+#[tokio::test]
+pub async fn resync() {
+    let store = MemoryStore::new().with_holding_registers(1, 0, 10);
+    let (_, addr) = common::serve(Arc::new(Resync(store))).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // Garbage followed by a Read Holding Registers request.
+    stream.write_all(&[0xDE, 0xAD, 0xBE, 0xEF, 0, 7, 0, 0, 0, 6, 1, 3, 0, 0, 0, 1]).await.unwrap();
+    let mut response = [0; 11];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(response, [0, 7, 0, 0, 0, 5, 1, 3, 2, 0, 0]);
+}
+
+struct Resync(MemoryStore);
+
+impl ModbusTCPServerHandler for Resync {
+    fn resync_frames(&self) -> bool {
+        true
+    }
+
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        self.0.handle_read_holding_registers(ctx, address, length).await
+    }
+}
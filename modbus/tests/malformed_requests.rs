@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use modbus::{MemoryStore, ModbusError};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+mod common;
+
+/// A MBAP frame for unit 1.
+fn frame(transaction_id: u16, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = transaction_id.to_be_bytes().to_vec();
+    frame.extend([0, 0]);
+    frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(1);
+    frame.extend(pdu);
+    frame
+}
+
+/// Reads a frame and returns its PDU.
+async fn read_pdu(stream: &mut TcpStream) -> Vec<u8> {
+    let mut header = [0; 7];
+    stream.read_exact(&mut header).await.unwrap();
+    let mut pdu = vec![0; u16::from_be_bytes([header[4], header[5]]) as usize - 1];
+    stream.read_exact(&mut pdu).await.unwrap();
+    pdu
+}
+
+#[tokio::test]
+pub async fn malformed_requests() {
+    let store = MemoryStore::new().with_coils(1, 0, 16).with_holding_registers(1, 0, 16);
+    let (_, addr) = common::serve(Arc::new(store)).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let illegal_data_value = |function_code: u8| vec![function_code | 0x80, 3];
+    for (pdu, response) in [
+        // Valid coil values are 0xFF00 and 0x0000 only.
+        (vec![0x05, 0, 1, 0xFF, 0x00], vec![0x05, 0, 1, 0xFF, 0x00]),
+        (vec![0x05, 0, 1, 0x12, 0x34], illegal_data_value(0x05)),
+        (vec![0x05, 0, 1, 0x00, 0x01], illegal_data_value(0x05)),
+        // Byte count not matching the quantity.
+        (vec![0x0F, 0, 0, 0, 10, 1, 0xFF], illegal_data_value(0x0F)),
+        (vec![0x10, 0, 0, 0, 2, 2, 0, 1], illegal_data_value(0x10)),
+        // Fewer values than the byte count.
+        (vec![0x10, 0, 0, 0, 2, 4, 0, 1], illegal_data_value(0x10)),
+        // Trailing and missing bytes.
+        (vec![0x03, 0, 0, 0, 1, 0xAA], illegal_data_value(0x03)),
+        (vec![0x01, 0, 0], illegal_data_value(0x01)),
+        // Quantity out of range.
+        (vec![0x03, 0, 0, 0, 0], illegal_data_value(0x03)),
+    ] {
+        stream.write_all(&frame(1, &pdu)).await.unwrap();
+        assert_eq!(read_pdu(&mut stream).await, response, "Request {:02X?}", pdu);
+    }
+}
+
+#[tokio::test]
+pub async fn malformed_responses() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        for pdu in [
+            // One register when two were requested.
+            vec![0x03, 2, 0, 1],
+            // Two bytes of coils when three coils were requested.
+            vec![0x01, 2, 0b111, 0],
+            vec![0x01, 1, 0b101],
+        ] {
+            let mut header = [0; 7];
+            stream.read_exact(&mut header).await.unwrap();
+            let mut request = vec![0; u16::from_be_bytes([header[4], header[5]]) as usize - 1];
+            stream.read_exact(&mut request).await.unwrap();
+            stream
+                .write_all(&frame(u16::from_be_bytes([header[0], header[1]]), &pdu))
+                .await
+                .unwrap();
+        }
+    });
+
+    let client = common::connect(addr).await;
+    assert!(matches!(client.read_holding_registers(1, 0, 2).await, Err(ModbusError::InvalidResponse(_))));
+    assert!(matches!(client.read_coils(1, 0, 3).await, Err(ModbusError::InvalidResponse(_))));
+    // The padding of the last byte isn't returned.
+    assert_eq!(client.read_coils(1, 0, 3).await.unwrap(), [true, false, true]);
+}
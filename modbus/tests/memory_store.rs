@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use modbus::{MemoryStore, MemoryStoreChange, ModbusError, ModbusException};
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn memory_store() {
+    let store = Arc::new(
+        MemoryStore::new()
+            .with_coils(1, 100, 10)
+            .with_input_registers(1, 0, 4)
+            .with_holding_registers(1, 0, 4)
+            .with_unit(2),
+    );
+    let mut changes = store.subscribe();
+
+    let (server, addr) = serve(store.clone()).await;
+
+    let client = connect(addr).await;
+
+    // Inputs are updated by the host application.
+    store.set_input_registers(1, 1, &[7, 8]).unwrap();
+    assert_eq!(client.read_input_registers(1, 0, 4).await.unwrap(), [0, 7, 8, 0]);
+
+    client.write_multiple_coils(1, 104, &[true, true]).await.unwrap();
+    assert_eq!(store.coils(1, 103, 4).unwrap(), [false, true, true, false]);
+
+    client.write_single_holding_register(1, 2, 0x12).await.unwrap();
+    client.mask_write_holding_registers(1, 2, 0xF2, 0x25).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 2, 1).await.unwrap(), [0x17]);
+
+    let connection_id = server.connections()[0].id;
+    assert_eq!(
+        changes.recv().await.unwrap(),
+        MemoryStoreChange::Coils {
+            connection_id,
+            unit_id: 1,
+            address: 104,
+            values: [true, true].into()
+        }
+    );
+    assert_eq!(
+        changes.recv().await.unwrap(),
+        MemoryStoreChange::HoldingRegisters {
+            connection_id,
+            unit_id: 1,
+            address: 2,
+            values: vec![0x12]
+        }
+    );
+    assert_eq!(
+        changes.recv().await.unwrap(),
+        MemoryStoreChange::HoldingRegisters {
+            connection_id,
+            unit_id: 1,
+            address: 2,
+            values: vec![0x17]
+        }
+    );
+
+    // Units are independent.
+    assert_eq!(client.read_holding_registers(2, 2, 1).await.unwrap(), [0]);
+    client.write_single_coils(2, 0xFFFF, true).await.unwrap();
+    assert_eq!(store.coils(2, 0xFFFF, 1).unwrap(), [true]);
+    assert!(matches!(changes.recv().await.unwrap(), MemoryStoreChange::Coils { unit_id: 2, address: 0xFFFF, .. }));
+
+    for result in [
+        client.read_coils(1, 99, 2).await.map(drop),
+        client.read_coils(1, 109, 2).await.map(drop),
+        client.read_discrete_inputs(1, 0, 1).await.map(drop),
+        client.write_multiple_holding_registers(1, 3, &[1, 2]).await,
+        client.mask_write_holding_registers(1, 4, 0, 0).await,
+        client.read_coils(3, 0, 1).await.map(drop),
+    ] {
+        assert!(matches!(result, Err(ModbusError::ModbusException(ModbusException::IllegalDataAddress))));
+    }
+    assert_eq!(store.holding_registers(1, 0, 4).unwrap(), [0, 0, 0x17, 0]);
+    assert!(changes.try_recv().is_err());
+}
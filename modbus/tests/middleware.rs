@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+
+use modbus::{
+    Layered, LoggingLayer, MemoryStore, Middleware, ModbusError, ModbusException, ModbusTCPClient, ModbusTCPServerHandler, Next, ReadOnlyLayer,
+    Request, RequestContext, Response,
+};
+
+mod common;
+
+/// Moves every request to the holding registers 100 addresses up, and hides register 105.
+struct Offset;
+
+impl Middleware for Offset {
+    async fn call<H: ModbusTCPServerHandler>(&self, ctx: &RequestContext, request: Request, next: Next<'_, H>) -> Result<Response, ModbusException> {
+        let request = match request {
+            Request::ReadHoldingRegisters { address, length } => {
+                if (address..address + length).contains(&5) {
+                    return Err(ModbusException::IllegalDataAddress);
+                }
+                Request::ReadHoldingRegisters { address: address + 100, length }
+            }
+            Request::WriteSingleHoldingRegister { address, value } => Request::WriteSingleHoldingRegister { address: address + 100, value },
+            request => request,
+        };
+        next.run(ctx, request).await
+    }
+}
+
+async fn serve<H: ModbusTCPServerHandler>(handler: H) -> ModbusTCPClient {
+    common::connect(common::serve(Arc::new(handler)).await.1).await
+}
+
+#[tokio::test]
+pub async fn middleware() {
+    let store = MemoryStore::new().with_holding_registers(1, 100, 10);
+    store.set_holding_registers(1, 100, &[0, 1, 2, 3]).unwrap();
+    let client = serve(Layered::new(store, Offset)).await;
+
+    assert_eq!(client.read_holding_registers(1, 1, 3).await.unwrap(), [1, 2, 3]);
+    client.write_single_holding_register(1, 2, 22).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 2, 1).await.unwrap(), [22]);
+    assert!(matches!(
+        client.read_holding_registers(1, 4, 2).await,
+        Err(ModbusError::ModbusException(ModbusException::IllegalDataAddress))
+    ));
+}
+
+#[tokio::test]
+pub async fn read_only() {
+    let store = MemoryStore::new().with_holding_registers(1, 0, 10).with_coils(1, 0, 10);
+    let client = serve(Layered::new(store, ReadOnlyLayer)).await;
+
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [0, 0]);
+    for result in [
+        client.write_single_holding_register(1, 0, 1).await,
+        client.write_multiple_holding_registers(1, 0, &[1, 2]).await,
+        client.mask_write_holding_registers(1, 0, 0, 1).await,
+        client.write_single_coils(1, 0, true).await,
+        client.write_multiple_coils(1, 0, &[true]).await,
+    ] {
+        assert!(matches!(result, Err(ModbusError::ModbusException(ModbusException::IllegalFunction))));
+    }
+}
+
+#[tokio::test]
+pub async fn logging() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let logged = lines.clone();
+    let logging = LoggingLayer::new(move |line| logged.lock().unwrap().push(line.to_string()));
+
+    // The logging layer is outermost, so it sees the request before the offset is applied.
+    let store = MemoryStore::new().with_holding_registers(1, 100, 10);
+    let client = serve(Layered::new(store, Offset).layer(logging)).await;
+
+    client.read_holding_registers(1, 0, 2).await.unwrap();
+    client.read_holding_registers(1, 5, 1).await.unwrap_err();
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("Connected"));
+    assert!(lines[1].ends_with("Unit 1: ReadHoldingRegisters { address: 0, length: 2 } -> ReadHoldingRegisters([0, 0])"));
+    assert!(lines[2].ends_with("Unit 1: ReadHoldingRegisters { address: 5, length: 1 } -> IllegalDataAddress"));
+}
@@ -0,0 +1,94 @@
+use bytes::BytesMut;
+use modbus::{AsciiCodec, BitBuf, Frame, FunctionCode, Request, Response, RtuCodec, SerialFrame};
+use proptest::{collection::vec, prelude::*};
+
+fn bits(length: impl Strategy<Value = usize>) -> impl Strategy<Value = BitBuf> {
+    length.prop_flat_map(|length| vec(any::<bool>(), length)).prop_map(BitBuf::from)
+}
+
+/// Requests that fit in a PDU. Read Device Identification is left out, it decodes as a Modbus Encapsulated Interface request.
+fn request() -> impl Strategy<Value = Request> {
+    prop_oneof![
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Request::ReadCoils { address, length }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Request::ReadDiscreteInputs { address, length }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Request::ReadInputRegisters { address, length }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Request::ReadHoldingRegisters { address, length }),
+        (any::<u16>(), any::<bool>()).prop_map(|(address, value)| Request::WriteSingleCoil { address, value }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, value)| Request::WriteSingleHoldingRegister { address, value }),
+        (any::<u16>(), bits(1..=1968usize)).prop_map(|(address, values)| Request::WriteMultipleCoils { address, values }),
+        (any::<u16>(), vec(any::<u16>(), 1..=123)).prop_map(|(address, values)| Request::WriteMultipleHoldingRegisters { address, values }),
+        (any::<u16>(), any::<u16>(), any::<u16>()).prop_map(|(address, and_mask, or_mask)| Request::MaskWriteHoldingRegister {
+            address,
+            and_mask,
+            or_mask
+        }),
+        (any::<u8>(), vec(any::<u8>(), 0..=251)).prop_map(|(interface_type, data)| Request::ModbusEncapsulatedInterface { interface_type, data }),
+    ]
+}
+
+/**
+ * Responses that fit in a PDU. Coils and discrete inputs are read in multiples of 8, as decoding pads them,
+ * and Read Device Identification is left out, it decodes as a Modbus Encapsulated Interface response.
+ */
+fn response() -> impl Strategy<Value = Response> {
+    prop_oneof![
+        bits((0..=250usize).prop_map(|bytes| bytes * 8)).prop_map(Response::ReadCoils),
+        bits((0..=250usize).prop_map(|bytes| bytes * 8)).prop_map(Response::ReadDiscreteInputs),
+        vec(any::<u16>(), 0..=125).prop_map(Response::ReadInputRegisters),
+        vec(any::<u16>(), 0..=125).prop_map(Response::ReadHoldingRegisters),
+        (any::<u16>(), any::<bool>()).prop_map(|(address, value)| Response::WriteSingleCoil { address, value }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, value)| Response::WriteSingleHoldingRegister { address, value }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Response::WriteMultipleCoils { address, length }),
+        (any::<u16>(), any::<u16>()).prop_map(|(address, length)| Response::WriteMultipleHoldingRegisters { address, length }),
+        (any::<u16>(), any::<u16>(), any::<u16>()).prop_map(|(address, and_mask, or_mask)| Response::MaskWriteHoldingRegister {
+            address,
+            and_mask,
+            or_mask
+        }),
+        (any::<u8>(), vec(any::<u8>(), 0..=251)).prop_map(|(interface_type, data)| Response::ModbusEncapsulatedInterface { interface_type, data }),
+    ]
+}
+
+/// Any function code followed by a body that fits in a frame.
+fn pdu() -> impl Strategy<Value = (FunctionCode, Vec<u8>)> {
+    (any::<u8>().prop_map(FunctionCode::from), vec(any::<u8>(), 0..=252))
+}
+
+proptest! {
+    #[test]
+    fn requests(request in request()) {
+        let pdu = request.encode().unwrap();
+        prop_assert_eq!(Request::decode(&pdu), Ok(request));
+    }
+
+    #[test]
+    fn responses(response in response()) {
+        let pdu = response.encode().unwrap();
+        prop_assert_eq!(Response::decode(&pdu), Ok(response));
+    }
+
+    #[test]
+    fn mbap_frames(transaction_id: u16, unit_id: u8, (function_code, body) in pdu()) {
+        let frame = Frame { transaction_id, protocol_id: 0, unit_id, function_code, body: body.into() };
+        let bytes = frame.encode().unwrap();
+        prop_assert_eq!(Frame::decode(&bytes), Ok((frame, &[][..])));
+    }
+
+    #[test]
+    fn rtu_frames(unit_id: u8, (function_code, body) in pdu()) {
+        let frame = SerialFrame { unit_id, function_code, body: body.into() };
+        let mut buffer = BytesMut::new();
+        RtuCodec::requests().encode(&frame, &mut buffer).unwrap();
+        prop_assert_eq!(RtuCodec::requests().decode_idle(&mut buffer), Ok(Some(frame)));
+        prop_assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn ascii_frames(unit_id: u8, (function_code, body) in pdu()) {
+        let frame = SerialFrame { unit_id, function_code, body: body.into() };
+        let mut buffer = BytesMut::new();
+        AsciiCodec::new().encode(&frame, &mut buffer).unwrap();
+        prop_assert_eq!(AsciiCodec::new().decode(&mut buffer), Ok(Some(frame)));
+        prop_assert!(buffer.is_empty());
+    }
+}
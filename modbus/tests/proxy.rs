@@ -0,0 +1,145 @@
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use modbus::{MemoryStore, ModbusError, ModbusException, ModbusProxy, ModbusTCPClient, ModbusTCPServerHandler, RequestContext, Table};
+
+use common::{connect, serve};
+
+mod common;
+
+/// The upstream device, counting the reads of holding registers. Reads are answered late, with the values at the time of the read.
+struct Device {
+    store: MemoryStore,
+    reads: AtomicUsize,
+}
+
+impl ModbusTCPServerHandler for Device {
+    async fn handle_read_holding_registers(&self, ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        let values = self.store.handle_read_holding_registers(ctx, address, length).await?.into_owned();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(values.into())
+    }
+
+    async fn handle_write_single_holding_register(&self, ctx: &RequestContext, address: u16, value: u16) -> Result<(), ModbusException> {
+        self.store.handle_write_single_holding_register(ctx, address, value).await
+    }
+
+    async fn handle_write_single_coil(&self, ctx: &RequestContext, address: u16, value: bool) -> Result<(), ModbusException> {
+        self.store.handle_write_single_coil(ctx, address, value).await
+    }
+}
+
+async fn proxy(configure: impl FnOnce(ModbusProxy<ModbusTCPClient>) -> ModbusProxy<ModbusTCPClient>) -> (Arc<Device>, std::net::SocketAddr) {
+    let device = Arc::new(Device {
+        store: MemoryStore::new().with_holding_registers(1, 0, 16).with_coils(1, 0, 16).with_holding_registers(2, 0, 16),
+        reads: AtomicUsize::new(0),
+    });
+    let upstream = connect(serve(device.clone()).await.1).await;
+    let (_, addr) = serve(Arc::new(configure(ModbusProxy::new(upstream)))).await;
+    (device, addr)
+}
+
+#[tokio::test]
+pub async fn passthrough() {
+    let (device, addr) = proxy(|proxy| proxy).await;
+    let client = connect(addr).await;
+
+    client.write_single_holding_register(1, 3, 33).await.unwrap();
+    assert_eq!(device.store.holding_registers(1, 3, 1).unwrap(), [33]);
+    assert_eq!(client.read_holding_registers(1, 2, 2).await.unwrap(), [0, 33]);
+
+    assert!(matches!(
+        client.read_holding_registers(1, 10, 10).await,
+        Err(ModbusError::ModbusException(ModbusException::IllegalDataAddress))
+    ));
+    assert!(matches!(
+        client.read_input_registers(1, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::IllegalFunction))
+    ));
+}
+
+#[tokio::test]
+pub async fn cache() {
+    let (device, addr) = proxy(|proxy| proxy.with_cache(1, Table::HoldingRegisters, 0..=7, Duration::from_millis(200))).await;
+    let client = connect(addr).await;
+
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [0, 0]);
+    device.store.set_holding_registers(1, 0, &[1, 2]).unwrap();
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [0, 0]);
+    assert_eq!(device.reads.load(Ordering::SeqCst), 1);
+
+    // Not entirely within the cached range.
+    client.read_holding_registers(1, 6, 4).await.unwrap();
+    client.read_holding_registers(1, 6, 4).await.unwrap();
+    assert_eq!(device.reads.load(Ordering::SeqCst), 3);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [1, 2]);
+
+    // Writes through the proxy remove overlapping cached reads.
+    client.write_single_holding_register(1, 1, 3).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [1, 3]);
+    assert_eq!(device.reads.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+pub async fn cache_with_concurrent_write() {
+    let (_, addr) = proxy(|proxy| proxy.with_cache(1, Table::HoldingRegisters, 0..=7, Duration::from_secs(10))).await;
+    let (reader, writer) = (connect(addr).await, connect(addr).await);
+
+    // The read is sent upstream before the write, so its values are outdated once the write succeeds.
+    let read = tokio::spawn(async move { reader.read_holding_registers(1, 0, 2).await.unwrap() });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    writer.write_single_holding_register(1, 1, 3).await.unwrap();
+    read.await.unwrap();
+    assert_eq!(writer.read_holding_registers(1, 0, 2).await.unwrap(), [0, 3]);
+}
+
+#[tokio::test]
+pub async fn deduplication() {
+    let (device, addr) = proxy(|proxy| proxy).await;
+
+    let mut tasks = Vec::new();
+    for _ in 0..5 {
+        let client = connect(addr).await;
+        tasks.push(tokio::spawn(async move { client.read_holding_registers(1, 0, 4).await.unwrap() }));
+    }
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), [0; 4]);
+    }
+    assert_eq!(device.reads.load(Ordering::SeqCst), 1);
+
+    let (device, addr) = proxy(|proxy| proxy.with_deduplication(false)).await;
+    let client = connect(addr).await;
+    let (a, b) = tokio::join!(client.read_holding_registers(1, 0, 4), client.read_holding_registers(1, 0, 4));
+    a.unwrap();
+    b.unwrap();
+    assert_eq!(device.reads.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+pub async fn write_rules() {
+    let (_, addr) = proxy(|proxy| {
+        proxy
+            .with_allowed_writes(1, Table::HoldingRegisters, 0..=9)
+            .with_denied_writes(1, Table::HoldingRegisters, 5..=5)
+    })
+    .await;
+    let client = connect(addr).await;
+
+    client.write_single_holding_register(1, 4, 1).await.unwrap();
+    for result in [client.write_single_holding_register(1, 5, 1).await, client.write_single_holding_register(1, 12, 1).await] {
+        assert!(matches!(result, Err(ModbusError::ModbusException(ModbusException::IllegalDataAddress))));
+    }
+
+    // The rules only restrict the holding registers of unit 1.
+    client.write_single_holding_register(2, 12, 1).await.unwrap();
+    client.write_single_coils(1, 0, true).await.unwrap();
+}
@@ -0,0 +1,129 @@
+use std::{
+    borrow::Cow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use modbus::{Layered, ModbusError, ModbusException, ModbusTCPServerHandler, Overload, RateLimit, RequestContext};
+
+use common::connect;
+
+mod common;
+
+struct SlowHandler;
+
+impl ModbusTCPServerHandler for SlowHandler {
+    async fn handle_read_holding_registers(&self, _ctx: &RequestContext, _address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(vec![0; length as usize].into())
+    }
+}
+
+async fn serve(rate_limit: RateLimit) -> std::net::SocketAddr {
+    common::serve(Arc::new(Layered::new(SlowHandler, rate_limit))).await.1
+}
+
+fn is_busy<T>(result: Result<T, ModbusError>) -> bool {
+    matches!(result, Err(ModbusError::ModbusException(ModbusException::ServerDeviceBusy)))
+}
+
+#[tokio::test]
+pub async fn connection_rate() {
+    let addr = serve(RateLimit::new().with_connection_rate(1.0, 2)).await;
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+    assert!(is_busy(a.read_holding_registers(1, 0, 1).await));
+    // Other connections have their own budget.
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+}
+
+#[tokio::test]
+pub async fn global_rate() {
+    let addr = serve(RateLimit::new().with_global_rate(1.0, 2)).await;
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+    assert!(is_busy(a.read_holding_registers(1, 0, 1).await));
+    assert!(is_busy(b.read_holding_registers(1, 0, 1).await));
+}
+
+#[tokio::test]
+pub async fn rejected_requests_keep_tokens() {
+    let addr = serve(RateLimit::new().with_connection_rate(0.1, 2).with_global_rate(1.0, 1)).await;
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+    // Rejected by the global rate, so the connection keeps its tokens.
+    for _ in 0..3 {
+        assert!(is_busy(a.read_holding_registers(1, 0, 1).await));
+    }
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+}
+
+#[tokio::test]
+pub async fn layers() {
+    let handler = Layered::new(SlowHandler, RateLimit::new().with_connection_rate(1.0, 2)).layer(RateLimit::new().with_connection_rate(1.0, 3));
+    let (_, addr) = common::serve(Arc::new(handler)).await;
+    let client = connect(addr).await;
+
+    // Each layer has its own bucket, the inner one with 2 tokens is exhausted first.
+    client.read_holding_registers(1, 0, 1).await.unwrap();
+    client.read_holding_registers(1, 0, 1).await.unwrap();
+    assert!(is_busy(client.read_holding_registers(1, 0, 1).await));
+}
+
+#[tokio::test]
+pub async fn rate_queue() {
+    let addr = serve(RateLimit::new().with_connection_rate(20.0, 1).with_overload(Overload::Queue)).await;
+    let client = connect(addr).await;
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.read_holding_registers(1, 0, 1).await.unwrap();
+    }
+    // Three requests of 100 ms each, the rate limit adds no wait between sequential requests.
+    assert!(start.elapsed() < Duration::from_millis(400));
+
+    let start = Instant::now();
+    let results = tokio::join!(
+        client.read_holding_registers(1, 0, 1),
+        client.read_holding_registers(1, 0, 1),
+        client.read_holding_registers(1, 0, 1)
+    );
+    results.0.unwrap();
+    results.1.unwrap();
+    results.2.unwrap();
+    // The third request had to wait for the second token.
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
+
+#[tokio::test]
+pub async fn concurrency() {
+    let addr = serve(RateLimit::new().with_max_concurrent_requests(1)).await;
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+
+    let (first, second) = tokio::join!(a.read_holding_registers(1, 0, 1), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        b.read_holding_registers(1, 0, 1).await
+    });
+    first.unwrap();
+    assert!(is_busy(second));
+
+    let addr = serve(RateLimit::new().with_max_concurrent_requests(1).with_overload(Overload::Queue)).await;
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+
+    let start = Instant::now();
+    let (first, second) = tokio::join!(a.read_holding_registers(1, 0, 1), b.read_holding_registers(1, 0, 1));
+    first.unwrap();
+    second.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
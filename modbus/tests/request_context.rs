@@ -0,0 +1,58 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use modbus::{Bits, Extensions, FunctionCode, ModbusException, ModbusTCPServerHandler, RequestContext};
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn request_context() {
+    let handler = Arc::new(ServerImpl {
+        next_session: AtomicU32::new(1),
+        writes: Mutex::new(Vec::new()),
+    });
+    let (server, addr) = serve(handler.clone()).await;
+
+    let client = connect(addr).await;
+    client.write_single_coils(3, 10, true).await.unwrap();
+    client.write_multiple_coils(4, 20, &[true, false]).await.unwrap();
+
+    let connection = &server.connections()[0];
+    let writes = handler.writes.lock().unwrap().clone();
+    assert_eq!(writes.len(), 2);
+    for write in &writes {
+        assert_eq!(write.connection_id, connection.id);
+        assert_eq!(write.addr, connection.addr);
+        assert_eq!(write.extensions.get::<Session>(), Some(&Session(1)));
+    }
+    assert_eq!((writes[0].unit_id, writes[0].function_code), (3, FunctionCode::WriteSingleCoil));
+    assert_eq!((writes[1].unit_id, writes[1].function_code), (4, FunctionCode::WriteMultipleCoils));
+    assert_ne!(writes[0].transaction_id, writes[1].transaction_id);
+}
+
+#[derive(Debug, PartialEq)]
+struct Session(u32);
+
+struct ServerImpl {
+    next_session: AtomicU32,
+    writes: Mutex<Vec<RequestContext>>,
+}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn accept_connection(&self, _addr: SocketAddr, extensions: &mut Extensions) -> bool {
+        extensions.insert(Session(self.next_session.fetch_add(1, Ordering::SeqCst)));
+        true
+    }
+
+    async fn handle_write_coils(&self, ctx: &RequestContext, _address: u16, _values: Bits<'_>) -> Result<(), ModbusException> {
+        self.writes.lock().unwrap().push(ctx.clone());
+        Ok(())
+    }
+}
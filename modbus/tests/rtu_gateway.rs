@@ -0,0 +1,99 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use modbus::{ModbusError, ModbusException, ModbusRTUClient, ModbusTCPClient, RtuGateway};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+mod common;
+
+fn crc16(bytes: &[u8]) -> [u8; 2] {
+    let mut crc = 0xFFFFu16;
+    for byte in bytes {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc.to_le_bytes()
+}
+
+/// A device with unit id 1 supporting reading and writing holding registers. Unit 2 never responds.
+async fn rtu_device(mut stream: impl AsyncRead + AsyncWrite + Unpin) {
+    let mut registers = [0u16; 16];
+    // All the requests used in the tests are 8 bytes long.
+    let mut frame = [0; 8];
+    while stream.read_exact(&mut frame).await.is_ok() {
+        assert_eq!(crc16(&frame[..6]), frame[6..]);
+        if frame[0] != 1 {
+            continue;
+        }
+        let address = u16::from_be_bytes([frame[2], frame[3]]) as usize;
+        let value = u16::from_be_bytes([frame[4], frame[5]]);
+        let mut response = vec![frame[0], frame[1]];
+        match frame[1] {
+            3 => {
+                response.push(value as u8 * 2);
+                for register in &registers[address..address + value as usize] {
+                    response.extend(register.to_be_bytes());
+                }
+            }
+            6 => {
+                registers[address] = value;
+                response.extend(&frame[2..6]);
+            }
+            function_code => response = vec![frame[0], function_code | 0x80, 1],
+        }
+        response.extend(crc16(&response));
+        stream.write_all(&response).await.unwrap();
+    }
+}
+
+async fn gateway(bus: ModbusRTUClient) -> ModbusTCPClient {
+    let gateway = RtuGateway::new().with_unit(1, bus.clone(), 1).with_unit(10, bus, 2);
+    common::connect(common::serve(Arc::new(gateway)).await.1).await
+}
+
+#[tokio::test]
+pub async fn rtu_gateway() {
+    let (bus, device) = tokio::io::duplex(256);
+    tokio::spawn(rtu_device(device));
+    let bus = ModbusRTUClient::new(bus)
+        .with_timeout(Duration::from_millis(100))
+        .with_turnaround_delay(Duration::from_millis(50));
+    let client = gateway(bus).await;
+
+    let start = Instant::now();
+    client.write_single_holding_register(1, 2, 42).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 1, 3).await.unwrap(), [0, 42, 0]);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // Exceptions from the device are passed through.
+    assert!(matches!(
+        client.read_coils(1, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::IllegalFunction))
+    ));
+    assert!(matches!(
+        client.read_holding_registers(10, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::GatewayTargetDeviceFailedToRespond))
+    ));
+    assert!(matches!(
+        client.read_holding_registers(2, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::GatewayPathUnavailable))
+    ));
+
+    // The bus still works after a timeout.
+    assert_eq!(client.read_holding_registers(1, 2, 1).await.unwrap(), [42]);
+}
+
+#[cfg(feature = "serial")]
+#[tokio::test]
+pub async fn rtu_gateway_serial() {
+    let (bus, device) = tokio_serial::SerialStream::pair().unwrap();
+    tokio::spawn(rtu_device(device));
+    let client = gateway(ModbusRTUClient::new(bus)).await;
+
+    client.write_single_holding_register(1, 0, 7).await.unwrap();
+    assert_eq!(client.read_holding_registers(1, 0, 2).await.unwrap(), [7, 0]);
+}
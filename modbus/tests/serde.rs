@@ -0,0 +1,84 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt::Debug};
+
+use modbus::{
+    BitBuf, ConnectionId, DeviceIdentification, FunctionCode, MemoryStoreChange, ModbusException, Registers, SubscriptionValues,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T, expected: serde_json::Value) {
+    let serialized = serde_json::to_value(&value).unwrap();
+    assert_eq!(serialized, expected);
+    assert_eq!(serde_json::from_value::<T>(serialized).unwrap(), value);
+}
+
+#[test]
+pub fn device_identification() {
+    let device_identification = DeviceIdentification {
+        vendor_name: "Vendor".into(),
+        product_code: "P-1".into(),
+        major_minor_revision: "1.2".into(),
+        vendor_url: None,
+        product_name: Some("Product".into()),
+        model_name: None,
+        user_application_name: None,
+        objects: BTreeMap::from([(0x80, Cow::from(&[0x01, 0xAB][..])), (0x81, Cow::from(&[][..]))]),
+    };
+    round_trip(
+        device_identification.clone(),
+        json!({
+            "vendor_name": "Vendor",
+            "product_code": "P-1",
+            "major_minor_revision": "1.2",
+            "vendor_url": null,
+            "product_name": "Product",
+            "model_name": null,
+            "user_application_name": null,
+            "objects": { "128": "01ab", "129": "" },
+        }),
+    );
+
+    // Optional fields and objects can be left out.
+    let minimal = json!({ "vendor_name": "Vendor", "product_code": "P-1", "major_minor_revision": "1.2" });
+    let decoded: DeviceIdentification = serde_json::from_value(minimal).unwrap();
+    assert_eq!(decoded.product_name, None);
+    assert!(decoded.objects.is_empty());
+
+    // Upper case digits are accepted.
+    let upper = json!({ "vendor_name": "", "product_code": "", "major_minor_revision": "", "objects": { "128": "01AB" } });
+    let decoded: DeviceIdentification = serde_json::from_value(upper).unwrap();
+    assert_eq!(decoded.objects[&0x80], device_identification.objects[&0x80]);
+
+    for objects in [json!({ "128": "0" }), json!({ "128": "zz" }), json!({ "256": "" })] {
+        let invalid = json!({ "vendor_name": "", "product_code": "", "major_minor_revision": "", "objects": objects });
+        assert!(serde_json::from_value::<DeviceIdentification>(invalid).is_err());
+    }
+}
+
+#[test]
+pub fn codes() {
+    round_trip(ModbusException::IllegalDataAddress, json!(2));
+    round_trip(ModbusException::Unknown(0x42), json!(0x42));
+    round_trip(FunctionCode::ReadHoldingRegisters, json!(3));
+    round_trip(FunctionCode::Unknown(0x41), json!(0x41));
+    round_trip(FunctionCode::ReadCoils.as_err(), json!(0x81));
+}
+
+#[test]
+pub fn values() {
+    round_trip(BitBuf::from([true, false, true]), json!([true, false, true]));
+    round_trip(SubscriptionValues::Registers(vec![1, 0xFFFF]), json!({ "Registers": [1, 0xFFFF] }));
+    round_trip(SubscriptionValues::Bits([false, true].into()), json!({ "Bits": [false, true] }));
+    round_trip(
+        MemoryStoreChange::Coils {
+            connection_id: ConnectionId(7),
+            unit_id: 1,
+            address: 100,
+            values: [true].into(),
+        },
+        json!({ "Coils": { "connection_id": 7, "unit_id": 1, "address": 100, "values": [true] } }),
+    );
+
+    let registers = Registers::new(&[0x01, 0x2C, 0xFF, 0xFF]).unwrap();
+    assert_eq!(serde_json::to_value(registers).unwrap(), json!([300, 0xFFFF]));
+}
@@ -0,0 +1,119 @@
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use modbus::{ModbusError, ModbusException, ModbusTCPServerHandler, RequestContext};
+use tokio::time;
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn reject_when_full() {
+    let (server, addr) = serve(Arc::new(ServerImpl { evict: false, idle_timeout: None })).await;
+
+    let a = connect(addr).await;
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+
+    let b = connect(addr).await;
+    assert!(matches!(
+        b.read_holding_registers(1, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::ServerDeviceBusy))
+    ));
+    assert!(b.read_holding_registers(1, 0, 1).await.is_err());
+
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+    assert_eq!(server.connections().len(), 1);
+}
+
+#[tokio::test]
+pub async fn reject_when_full_with_pending_rejects() {
+    let (_server, addr) = serve(Arc::new(ServerImpl { evict: false, idle_timeout: None })).await;
+
+    let a = connect(addr).await;
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+
+    // `b` waits to be answered, using up the pending rejects, so `c` is closed without a response.
+    let b = connect(addr).await;
+    time::sleep(Duration::from_millis(20)).await;
+    let c = connect(addr).await;
+    assert!(matches!(c.read_holding_registers(1, 0, 1).await, Err(ModbusError::IO(_))));
+    assert!(matches!(
+        b.read_holding_registers(1, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::ServerDeviceBusy))
+    ));
+}
+
+#[tokio::test]
+pub async fn evict_when_full() {
+    let (server, addr) = serve(Arc::new(ServerImpl { evict: true, idle_timeout: None })).await;
+
+    let a = connect(addr).await;
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+
+    let b = connect(addr).await;
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+
+    assert!(a.read_holding_registers(1, 0, 1).await.is_err());
+    time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.connections().len(), 1);
+}
+
+#[tokio::test]
+pub async fn idle_timeout() {
+    let (server, addr) = serve(Arc::new(ServerImpl {
+        evict: false,
+        idle_timeout: Some(Duration::from_millis(100)),
+    }))
+    .await;
+
+    let client = connect(addr).await;
+
+    // A request outlasting the idle timeout keeps the connection open.
+    client.read_holding_registers(1, 150, 1).await.unwrap();
+    client.read_holding_registers(1, 0, 1).await.unwrap();
+
+    time::sleep(Duration::from_millis(200)).await;
+    assert!(server.connections().is_empty());
+    assert!(client.read_holding_registers(1, 0, 1).await.is_err());
+}
+
+#[tokio::test]
+pub async fn request_timeout() {
+    let (_server, addr) = serve(Arc::new(ServerImpl { evict: false, idle_timeout: None })).await;
+
+    let client = connect(addr).await;
+    assert!(matches!(
+        client.read_holding_registers(1, 1000, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::ServerDeviceBusy))
+    ));
+    client.read_holding_registers(1, 0, 1).await.unwrap();
+}
+
+struct ServerImpl {
+    evict: bool,
+    idle_timeout: Option<Duration>,
+}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    fn max_concurrent_connections(&self) -> usize {
+        1
+    }
+
+    fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    fn request_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(500))
+    }
+
+    fn evict_idle_connection_when_full(&self) -> bool {
+        self.evict
+    }
+
+    /// Responds with the address after waiting that many milliseconds.
+    async fn handle_read_holding_registers(&self, _ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        time::sleep(Duration::from_millis(address as u64)).await;
+        Ok(Cow::Owned(vec![address; length as usize]))
+    }
+}
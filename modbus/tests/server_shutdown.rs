@@ -0,0 +1,121 @@
+use std::{
+    borrow::Cow,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use modbus::{DisconnectReason, Extensions, ModbusException, ModbusTCPServerHandler, RequestContext};
+use tokio::{net::TcpStream, time};
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn shutdown() {
+    let handler = Arc::new(ServerImpl {
+        disconnected: AtomicUsize::new(0),
+    });
+    let (server, addr) = serve(handler.clone()).await;
+
+    let client = connect(addr).await;
+    assert_eq!(client.read_holding_registers(1, 0, 1).await.unwrap(), [0]);
+    assert_eq!(server.connections().len(), 1);
+
+    // The slow request is in flight when the shutdown starts and is allowed to finish.
+    let slow = tokio::spawn({
+        let client = client.clone();
+        async move { client.read_holding_registers(1, 100, 1).await }
+    });
+    time::sleep(Duration::from_millis(20)).await;
+
+    server.shutdown(Duration::from_secs(5)).await;
+    assert!(server.is_finished());
+    assert_eq!(slow.await.unwrap().unwrap(), [100]);
+    assert!(server.connections().is_empty());
+    assert_eq!(handler.disconnected.load(Ordering::SeqCst), 1);
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+pub async fn shutdown_deadline() {
+    let handler = Arc::new(ServerImpl {
+        disconnected: AtomicUsize::new(0),
+    });
+    let (server, addr) = serve(handler.clone()).await;
+
+    let client = connect(addr).await;
+    let slow = tokio::spawn({
+        let client = client.clone();
+        async move { client.read_holding_registers(1, 10_000, 1).await }
+    });
+    time::sleep(Duration::from_millis(20)).await;
+
+    time::timeout(Duration::from_secs(5), server.shutdown(Duration::from_millis(50))).await.unwrap();
+    assert!(slow.await.unwrap().is_err());
+}
+
+#[tokio::test]
+pub async fn shutdown_while_accepting() {
+    let (server, addr) = serve(Arc::new(SlowAccept)).await;
+
+    // The connection is registered after the shutdown has started.
+    let client = connect(addr).await;
+    time::sleep(Duration::from_millis(20)).await;
+    time::timeout(Duration::from_secs(3), server.shutdown(Duration::from_millis(100))).await.unwrap();
+    assert!(server.connections().is_empty());
+    assert!(client.read_holding_registers(1, 0, 1).await.is_err());
+}
+
+#[tokio::test]
+pub async fn disconnect() {
+    let handler = Arc::new(ServerImpl {
+        disconnected: AtomicUsize::new(0),
+    });
+    let (server, addr) = serve(handler.clone()).await;
+
+    let a = connect(addr).await;
+    let b = connect(addr).await;
+    a.read_holding_registers(1, 0, 1).await.unwrap();
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+
+    let connections = server.connections();
+    assert_eq!(connections.len(), 2);
+    assert!(server.disconnect(connections[0].id));
+
+    assert!(a.read_holding_registers(1, 0, 1).await.is_err());
+    b.read_holding_registers(1, 0, 1).await.unwrap();
+
+    assert_eq!(server.connections().len(), 1);
+    assert!(!server.disconnect(connections[0].id));
+    assert!(!server.is_finished());
+}
+
+struct ServerImpl {
+    disconnected: AtomicUsize,
+}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn disconnected(&self, _addr: SocketAddr, _reason: &DisconnectReason) {
+        self.disconnected.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Responds with the address after waiting that many milliseconds.
+    async fn handle_read_holding_registers(&self, _ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        time::sleep(Duration::from_millis(address as u64)).await;
+        Ok(Cow::Owned(vec![address; length as usize]))
+    }
+}
+
+struct SlowAccept;
+
+impl ModbusTCPServerHandler for SlowAccept {
+    async fn accept_connection(&self, _addr: SocketAddr, _extensions: &mut Extensions) -> bool {
+        time::sleep(Duration::from_millis(200)).await;
+        true
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+use modbus::{Bits, ModbusException, ModbusTCPServerHandler, RequestContext};
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn server_writes() {
+    let handler = Arc::new(ServerImpl {
+        calls: Mutex::new(Vec::new()),
+        register: Mutex::new(0x12),
+    });
+    let (_, addr) = serve(handler.clone()).await;
+
+    let client = connect(addr).await;
+    client.write_single_coils(1, 0, true).await.unwrap();
+    client.write_multiple_coils(1, 0, &[true, false]).await.unwrap();
+    client.write_single_holding_register(1, 0, 5).await.unwrap();
+    client.mask_write_holding_registers(1, 0, 0xF2, 0x25).await.unwrap();
+
+    assert_eq!(
+        *handler.calls.lock().unwrap(),
+        ["write_single_coil", "write_coils", "write_holding_registers", "mask_write_holding_register"]
+    );
+    // Example from the specification.
+    assert_eq!(*handler.register.lock().unwrap(), 0x17);
+}
+
+struct ServerImpl {
+    calls: Mutex<Vec<&'static str>>,
+    register: Mutex<u16>,
+}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn handle_write_single_coil(&self, _ctx: &RequestContext, _address: u16, _value: bool) -> Result<(), ModbusException> {
+        self.calls.lock().unwrap().push("write_single_coil");
+        Ok(())
+    }
+
+    async fn handle_write_coils(&self, _ctx: &RequestContext, _address: u16, _values: Bits<'_>) -> Result<(), ModbusException> {
+        self.calls.lock().unwrap().push("write_coils");
+        Ok(())
+    }
+
+    async fn handle_write_holding_registers(&self, _ctx: &RequestContext, _address: u16, _values: &[u16]) -> Result<(), ModbusException> {
+        self.calls.lock().unwrap().push("write_holding_registers");
+        Ok(())
+    }
+
+    async fn handle_mask_write_holding_register(&self, _ctx: &RequestContext, _address: u16, and_mask: u16, or_mask: u16) -> Result<(), ModbusException> {
+        self.calls.lock().unwrap().push("mask_write_holding_register");
+        let mut register = self.register.lock().unwrap();
+        *register = (*register & and_mask) | (or_mask & !and_mask);
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use modbus::{ModbusException, ModbusTCPServerHandler, RequestContext, SubscriptionValues, Table};
+use tokio::sync::Mutex;
+use tokio_stream::StreamExt;
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn subscribe() {
+    let handler = Arc::new(ServerImpl {
+        holding_registers: Mutex::new(vec![0; 16]),
+    });
+    let (_, addr) = serve(handler).await;
+
+    let client = connect(addr).await;
+
+    let interval = Duration::from_millis(10);
+    let mut a = client.subscribe(1, Table::HoldingRegisters, 0, 4, interval).unwrap();
+    let mut b = client
+        .subscribe(1, Table::HoldingRegisters, 2, 4, interval)
+        .unwrap()
+        .with_deadband(5);
+
+    assert_eq!(a.next().await.unwrap().unwrap(), SubscriptionValues::Registers(vec![0, 0, 0, 0]));
+    assert_eq!(b.next().await.unwrap().unwrap(), SubscriptionValues::Registers(vec![0, 0, 0, 0]));
+
+    // Within the deadband of `b`.
+    client.write_single_holding_register(1, 3, 5).await.unwrap();
+    assert_eq!(a.next().await.unwrap().unwrap(), SubscriptionValues::Registers(vec![0, 0, 0, 5]));
+
+    client.write_single_holding_register(1, 4, 6).await.unwrap();
+    assert_eq!(b.next().await.unwrap().unwrap(), SubscriptionValues::Registers(vec![0, 5, 6, 0]));
+
+    // Errors are reported without ending the stream.
+    let mut c = client.subscribe(1, Table::Coils, 0, 1, interval).unwrap();
+    assert!(c.next().await.unwrap().is_err());
+    assert!(c.next().await.unwrap().is_err());
+
+    drop(client);
+    assert!(a.next().await.is_none());
+}
+
+struct ServerImpl {
+    holding_registers: Mutex<Vec<u16>>,
+}
+
+impl ModbusTCPServerHandler for ServerImpl {
+    async fn handle_read_holding_registers(&self, _ctx: &RequestContext, address: u16, length: u16) -> Result<Cow<'_, [u16]>, ModbusException> {
+        let holding_registers = self.holding_registers.lock().await;
+        let range = address as usize..address as usize + length as usize;
+        Ok(holding_registers.get(range).ok_or(ModbusException::IllegalDataAddress)?.to_vec().into())
+    }
+
+    async fn handle_write_holding_registers(&self, _ctx: &RequestContext, address: u16, values: &[u16]) -> Result<(), ModbusException> {
+        let mut holding_registers = self.holding_registers.lock().await;
+        let range = address as usize..address as usize + values.len();
+        holding_registers.get_mut(range).ok_or(ModbusException::IllegalDataAddress)?.copy_from_slice(values);
+        Ok(())
+    }
+}
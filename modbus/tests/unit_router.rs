@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use modbus::{MemoryStore, ModbusError, ModbusException, UnitRouter};
+
+use common::{connect, serve};
+
+mod common;
+
+#[tokio::test]
+pub async fn unit_router() {
+    let a = Arc::new(MemoryStore::new().with_holding_registers(1, 0, 1));
+    let b = Arc::new(MemoryStore::new().with_holding_registers(2, 0, 1).with_holding_registers(3, 0, 1));
+    let fallback = Arc::new(MemoryStore::new().with_holding_registers(5, 0, 1));
+
+    // Routers are handlers too, so they can be nested.
+    let inner = UnitRouter::new().with_unit(5, fallback.clone());
+    let router = UnitRouter::new().with_unit(1, a.clone()).with_units([2, 3], b.clone());
+
+    let (_, addr) = serve(Arc::new(router.clone().with_fallback(Arc::new(inner)))).await;
+
+    let client = connect(addr).await;
+    for unit_id in [1, 2, 3, 5] {
+        client.write_single_holding_register(unit_id, 0, unit_id as u16).await.unwrap();
+    }
+    assert_eq!(a.holding_registers(1, 0, 1).unwrap(), [1]);
+    assert_eq!(b.holding_registers(2, 0, 1).unwrap(), [2]);
+    assert_eq!(b.holding_registers(3, 0, 1).unwrap(), [3]);
+    assert_eq!(fallback.holding_registers(5, 0, 1).unwrap(), [5]);
+
+    assert!(matches!(
+        client.read_holding_registers(4, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::GatewayPathUnavailable))
+    ));
+
+    // Without a fallback.
+    let (_, addr) = serve(Arc::new(router)).await;
+
+    let client = connect(addr).await;
+    assert_eq!(client.read_holding_registers(2, 0, 1).await.unwrap(), [2]);
+    assert!(matches!(
+        client.read_holding_registers(5, 0, 1).await,
+        Err(ModbusError::ModbusException(ModbusException::GatewayPathUnavailable))
+    ));
+}